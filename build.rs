@@ -0,0 +1,5 @@
+fn main() {
+    // Linker flags for a Node.js addon (needed on macOS); see src/node.rs.
+    #[cfg(feature = "node")]
+    napi_build::setup();
+}
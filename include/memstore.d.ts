@@ -0,0 +1,28 @@
+// Types of the Node.js addon (src/node.rs), built with `cargo build --release
+// --features node` and loaded by copying libmemstore.so / .dylib / .dll to
+// memstore.node. Ids are 128-bit, hence BigInt. Errors throw.
+
+export interface StoredRecord {
+  id: bigint;
+  ts: number;
+  kind: string;
+  weight: number;
+  text: string;
+  tags: string[];
+  attrs: Record<string, string>;
+}
+
+/** One hit, the same shape as POST /stores/<name>/search. */
+export interface Hit {
+  score: number;
+  record: StoredRecord;
+}
+
+export class MemStore {
+  /** Opens the store file at path (created on the first add). */
+  constructor(path: string);
+  /** kind defaults to "summary"; without a weight, or with a negative one, memstore estimates it. */
+  add(text: string, kind?: string, weight?: number): bigint;
+  /** The best limit hits (3 by default); query may use the query syntax. */
+  search(query: string, limit?: number): Hit[];
+}
@@ -0,0 +1,40 @@
+/* C API of libmemstore (src/ffi.rs). Strings are UTF-8 and NUL-terminated;
+ * strings returned by the library are released with memstore_free. A panic
+ * inside the library is returned as an error, never unwound into the caller. */
+#ifndef MEMSTORE_H
+#define MEMSTORE_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MemStore MemStore;
+
+/* Opens the store file at path (created on the first add). NULL on error. */
+MemStore *memstore_open(const char *path);
+
+/* Adds a record; kind may be NULL for "summary", a negative weight lets
+ * memstore estimate one. Ids are 128 bits: unless out_id is NULL, the high
+ * half goes to out_id[0] and the low half to out_id[1].
+ * Returns 0 on success, -1 on error. */
+int memstore_add(MemStore *store, const char *text, const char *kind, float weight,
+                 uint64_t out_id[2]);
+
+/* Returns the hits as a JSON array of {"score", "record"} objects, the same
+ * body as POST /stores/<name>/search, or NULL on error. */
+char *memstore_search_json(MemStore *store, const char *query, size_t limit);
+
+/* Frees a string returned by the library. NULL is ignored. */
+void memstore_free(char *s);
+
+/* Releases a handle from memstore_open. NULL is ignored. */
+void memstore_close(MemStore *store);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
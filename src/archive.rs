@@ -0,0 +1,136 @@
+//! Cold tier: `archive --before <age>` moves old records out of the store into
+//! `<store>.archive`, a gzip-compressed store file. Searches skip it unless
+//! asked with `--include-archive`, and then scan it exhaustively.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::store::{
+    decode_store, encode_store, new_store, sibling_path, update_store, write_atomic, Store,
+};
+use crate::{
+    config, filter, local_only, now_secs, path_arg, query, resolve_path, score_records, store_error,
+    Record, SearchOptions,
+};
+
+pub fn cmd_archive(args: &[String]) -> Result<(), &'static str> {
+    let mut before: Option<String> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--before" => {
+                i += 1;
+                before = args.get(i).cloned();
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let Some(before) = before else {
+        eprintln!("Missing --before");
+        return Err("missing before");
+    };
+    let Some(age) = filter::parse_duration(&before) else {
+        eprintln!("Invalid --before: {before} (use e.g. 180d, 26w)");
+        return Err("invalid duration");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    let cutoff = now_secs() - age;
+    let archive = archive_path(&path);
+    // The archive is written before the store, under the store's lock: a crash
+    // in between leaves records in both places, never in neither.
+    let moved = update_store(&path, |store| {
+        let (old, keep): (Vec<Record>, Vec<Record>) =
+            store.records.drain(..).partition(|r| r.ts < cutoff);
+        store.records = keep;
+        if !old.is_empty() {
+            let mut cold = load_archive(&path)?;
+            cold.records.extend(old.iter().cloned());
+            save_archive(&path, &cold)?;
+        }
+        Ok(old.len())
+    })
+    .map_err(store_error(&archive, "write failed"))?;
+    eprintln!("Archived {moved} records to {}", archive.display());
+    Ok(())
+}
+
+pub fn archive_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".archive")
+}
+
+/// The archived records, or an empty store if nothing was archived yet.
+pub fn load_archive(path: &Path) -> io::Result<Store> {
+    let archive = archive_path(path);
+    if !archive.exists() {
+        return Ok(new_store());
+    }
+    let mut data = Vec::new();
+    GzDecoder::new(fs::File::open(archive)?).read_to_end(&mut data)?;
+    decode_store(&data).map(|(store, _)| store)
+}
+
+/// Drops archived records for which `keep` is false and returns how many went.
+/// Callers hold the store's lock.
+pub fn retain(path: &Path, mut keep: impl FnMut(&Record) -> bool) -> io::Result<usize> {
+    if !archive_path(path).exists() {
+        return Ok(0);
+    }
+    let mut cold = load_archive(path)?;
+    let before = cold.records.len();
+    cold.records.retain(|r| keep(r));
+    let removed = before - cold.records.len();
+    if removed > 0 {
+        save_archive(path, &cold)?;
+    }
+    Ok(removed)
+}
+
+fn save_archive(path: &Path, store: &Store) -> io::Result<()> {
+    let mut gz = GzEncoder::new(Vec::new(), Compression::best());
+    gz.write_all(&encode_store(store)?)?;
+    write_atomic(&archive_path(path), &[&gz.finish()?])
+}
+
+/// Scores every archived record that passes the filters; the archive has no index.
+pub fn search(path: &Path, query: &str, options: &SearchOptions) -> io::Result<Vec<(f32, Record)>> {
+    let parsed =
+        query::parse_query(query).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let query_vec = parsed.vector();
+    let mut filter = options.filter.clone();
+    filter.merge(parsed.filter);
+    let records = load_archive(path)?.records;
+    let matching: HashSet<usize> = (0..records.len())
+        .filter(|&i| filter.matches(&records[i]))
+        .collect();
+    Ok(score_records(
+        &query_vec,
+        &records,
+        &matching,
+        &options.scoring,
+        filter.as_of.unwrap_or_else(now_secs),
+    ))
+}
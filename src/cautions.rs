@@ -0,0 +1,45 @@
+//! Caution records: notes of kind `caution` that say what not to do ("do NOT
+//! suggest MongoDB for this project"). Ranked like other records they would
+//! surface exactly when the topic comes up, but read as plain hits; so
+//! `search` leaves them out of the results and lists the ones similar to the
+//! query in a separate section after the hits. Searching `kind:caution`
+//! ranks them normally.
+
+use std::io;
+use std::path::Path;
+
+use crate::store::{stream_records, Record};
+use crate::{cosine_sim, embed_text, shards};
+
+pub const CAUTION_KIND: &str = "caution";
+/// Cosine similarity to the query a caution needs to be shown.
+const MIN_SIMILARITY: f32 = 0.3;
+const MAX_SHOWN: usize = 3;
+
+/// Cautions similar to `query_text` (the query without filter terms), most
+/// similar first.
+pub fn find(path: &Path, query_text: &str) -> io::Result<Vec<(f32, Record)>> {
+    let query_vec = embed_text(query_text);
+    let stores = if shards::is_sharded(path) {
+        shards::in_window(path, None)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut found = Vec::new();
+    for store in stores {
+        // Streamed, so listing cautions stays cheap under `max_memory`.
+        for rec in stream_records(&store)? {
+            let rec = rec?;
+            if rec.kind != CAUTION_KIND {
+                continue;
+            }
+            let sim = cosine_sim(&query_vec, &rec.vector);
+            if sim >= MIN_SIMILARITY {
+                found.push((sim, rec));
+            }
+        }
+    }
+    found.sort_by(|a, b| b.0.total_cmp(&a.0));
+    found.truncate(MAX_SHOWN);
+    Ok(found)
+}
@@ -0,0 +1,128 @@
+//! Mean vector per kind, cached next to the store as `<store>.centroids`.
+//!
+//! The cache is keyed on the store file's size and mtime, so any write to the
+//! store invalidates it without the store format knowing about it.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::store::{file_stamp, load_store, sibling_path, VECTOR_DIM};
+use crate::{config, cosine_sim, local_only, normalize, path_arg, resolve_path, store_error};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Centroid {
+    pub kind: String,
+    pub count: usize,
+    /// Mean cosine similarity of the members to the centroid; 1.0 is a single topic.
+    pub cohesion: f32,
+    pub vector: Vec<f32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    store_len: u64,
+    store_mtime_ns: u128,
+    centroids: Vec<Centroid>,
+}
+
+pub fn cmd_centroids(args: &[String]) -> Result<(), &'static str> {
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let centroids = kind_centroids(&path).map_err(store_error(&path, "read failed"))?;
+    for c in centroids {
+        println!("{}\t{}\t{:.3}", c.kind, c.count, c.cohesion);
+    }
+    Ok(())
+}
+
+/// Kinds ranked by how close their centroid is to `query_vec`.
+pub fn nearest_kinds(path: &Path, query_vec: &[f32]) -> io::Result<Vec<(f32, Centroid)>> {
+    let mut ranked: Vec<(f32, Centroid)> = kind_centroids(path)?
+        .into_iter()
+        .map(|c| (cosine_sim(query_vec, &c.vector), c))
+        .collect();
+    ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+    Ok(ranked)
+}
+
+/// Centroids sorted by kind, from the cache when it is still valid.
+pub fn kind_centroids(path: &Path) -> io::Result<Vec<Centroid>> {
+    let (store_len, store_mtime_ns) = file_stamp(path)?;
+    let cache_path = sibling_path(path, ".centroids");
+    if let Ok(bytes) = fs::read(&cache_path) {
+        if let Ok(cache) = bincode::deserialize::<Cache>(&bytes) {
+            if cache.store_len == store_len && cache.store_mtime_ns == store_mtime_ns {
+                return Ok(cache.centroids);
+            }
+        }
+    }
+
+    let centroids = compute(path)?;
+    let cache = Cache {
+        store_len,
+        store_mtime_ns,
+        centroids,
+    };
+    // The cache is an optimisation; a read-only directory just means recomputing.
+    if let Ok(bytes) = bincode::serialize(&cache) {
+        let _ = fs::write(&cache_path, bytes);
+    }
+    Ok(cache.centroids)
+}
+
+fn compute(path: &Path) -> io::Result<Vec<Centroid>> {
+    let records = load_store(path)?.records;
+    let mut sums: BTreeMap<&str, (usize, Vec<f32>)> = BTreeMap::new();
+    for rec in &records {
+        let (count, sum) = sums
+            .entry(rec.kind.as_str())
+            .or_insert_with(|| (0, vec![0.0; VECTOR_DIM]));
+        *count += 1;
+        for (s, v) in sum.iter_mut().zip(&rec.vector) {
+            *s += v;
+        }
+    }
+    Ok(sums
+        .into_iter()
+        .map(|(kind, (count, mut vector))| {
+            normalize(&mut vector);
+            let total: f32 = records
+                .iter()
+                .filter(|r| r.kind == kind)
+                .map(|r| cosine_sim(&r.vector, &vector))
+                .sum();
+            Centroid {
+                kind: kind.to_string(),
+                count,
+                cohesion: total / count as f32,
+                vector,
+            }
+        })
+        .collect())
+}
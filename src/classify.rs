@@ -0,0 +1,63 @@
+//! Picking a kind for text added without `--kind`, so lazy callers do not
+//! file everything under `summary`. Opt-in: `add --classify`, or `classify`
+//! in the profile, which `remember` also follows.
+//!
+//! ```toml
+//! [profiles.work]
+//! classify = { mode = "rules" }
+//! # or: classify = { mode = "cmd", cmd = "my-classifier --labels decision,todo" }
+//! ```
+//!
+//! `rules` matches the keyword phrases below. `cmd` runs a shell command with
+//! the text on stdin and takes the first line it prints as the kind; if it
+//! fails, the rules decide instead and the failure is reported on stderr.
+
+use serde::{Deserialize, Serialize};
+
+use crate::consolidate::run_command;
+use crate::tokenize;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum Classify {
+    Rules,
+    Cmd { cmd: String },
+}
+
+/// Words or phrases (in `tokenize` form) that mark a kind, checked in order;
+/// anything else is a `summary`.
+const KIND_RULES: &[(&str, &[&str])] = &[
+    (
+        "decision",
+        &["decided", "decide to", "we chose", "going with", "settled on", "instead of"],
+    ),
+    ("todo", &["todo", "need to", "remind me", "follow up", "do not forget"]),
+    (
+        "profile",
+        &["prefers", "i prefer", "likes", "dislikes", "i like", "i hate", "my name is"],
+    ),
+];
+
+impl Classify {
+    pub fn kind(&self, text: &str) -> String {
+        match self {
+            Classify::Rules => guess_kind(text).to_string(),
+            Classify::Cmd { cmd } => match run_command(cmd, text) {
+                Ok(out) => out.lines().next().unwrap_or_default().trim().to_string(),
+                Err(e) => {
+                    eprintln!("Warning: classify command failed ({e}); using keyword rules");
+                    guess_kind(text).to_string()
+                }
+            },
+        }
+    }
+}
+
+pub fn guess_kind(text: &str) -> &'static str {
+    // Padded so phrases only match whole words.
+    let words = format!(" {} ", tokenize(text).join(" "));
+    KIND_RULES
+        .iter()
+        .find(|(_, phrases)| phrases.iter().any(|p| words.contains(&format!(" {p} "))))
+        .map_or("summary", |(kind, _)| kind)
+}
@@ -0,0 +1,210 @@
+//! Topic discovery: spherical k-means over the record vectors.
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::store::{load_store, update_store, Record, VECTOR_DIM};
+use crate::{
+    config, cosine_sim, local_only, normalize, parse_flag, path_arg, resolve_path, store_error,
+    tokenize, tsv_field,
+};
+
+const MAX_ITERATIONS: usize = 50;
+const TOP_TERMS: usize = 6;
+const REPRESENTATIVES: usize = 3;
+/// Prefix of the tags written by `--tag-records`; older ones are replaced on each run.
+const TAG_PREFIX: &str = "cluster-";
+
+pub fn cmd_cluster(args: &[String]) -> Result<(), &'static str> {
+    let mut k: usize = 12;
+    let mut algorithm = "kmeans".to_string();
+    let mut tag_records = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-k" | "--k" => {
+                i += 1;
+                k = parse_flag("-k", args.get(i))?;
+            }
+            "--algorithm" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    algorithm = v.clone();
+                }
+            }
+            "--tag-records" => tag_records = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if algorithm != "kmeans" {
+        eprintln!("Unknown algorithm: {algorithm} (supported: kmeans)");
+        return Err("unknown algorithm");
+    }
+    if k == 0 {
+        eprintln!("-k must be at least 1");
+        return Err("invalid k");
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    let (records, assignment, centers) = if tag_records {
+        update_store(&path, |store| {
+            let (assignment, centers) = kmeans(&store.records, k);
+            for (rec, cluster) in store.records.iter_mut().zip(&assignment) {
+                rec.tags.retain(|t| !t.starts_with(TAG_PREFIX));
+                rec.tags.push(format!("{TAG_PREFIX}{cluster}"));
+            }
+            Ok((store.records.clone(), assignment, centers))
+        })
+        .map_err(store_error(&path, "write failed"))?
+    } else {
+        let records = load_store(&path)
+            .map_err(store_error(&path, "read failed"))?
+            .records;
+        let (assignment, centers) = kmeans(&records, k);
+        (records, assignment, centers)
+    };
+    print_clusters(&records, &assignment, &centers);
+    Ok(())
+}
+
+/// Cluster index per record plus the unit-length cluster centers.
+/// Seeding is deterministic (farthest point first), so reruns give the same clusters.
+fn kmeans(records: &[Record], k: usize) -> (Vec<usize>, Vec<Vec<f32>>) {
+    if records.is_empty() {
+        return (Vec::new(), Vec::new());
+    }
+    let k = k.min(records.len());
+    let mut centers: Vec<Vec<f32>> = vec![records[0].vector.clone()];
+    while centers.len() < k {
+        let farthest = records
+            .iter()
+            .map(|r| {
+                centers
+                    .iter()
+                    .map(|c| cosine_sim(c, &r.vector))
+                    .fold(f32::MIN, f32::max)
+            })
+            .enumerate()
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        centers.push(records[farthest].vector.clone());
+    }
+
+    let mut assignment = vec![usize::MAX; records.len()];
+    for _ in 0..MAX_ITERATIONS {
+        let mut changed = false;
+        for (slot, rec) in assignment.iter_mut().zip(records) {
+            let best = nearest(&centers, &rec.vector);
+            if *slot != best {
+                *slot = best;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+        for (c, center) in centers.iter_mut().enumerate() {
+            let mut sum = vec![0.0f32; VECTOR_DIM];
+            for (rec, _) in records.iter().zip(&assignment).filter(|(_, a)| **a == c) {
+                for (s, v) in sum.iter_mut().zip(&rec.vector) {
+                    *s += v;
+                }
+            }
+            // An emptied cluster keeps its old center.
+            if sum.iter().any(|v| *v != 0.0) {
+                normalize(&mut sum);
+                *center = sum;
+            }
+        }
+    }
+    (assignment, centers)
+}
+
+fn nearest(centers: &[Vec<f32>], vector: &[f32]) -> usize {
+    centers
+        .iter()
+        .map(|c| cosine_sim(c, vector))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn print_clusters(records: &[Record], assignment: &[usize], centers: &[Vec<f32>]) {
+    // Document frequency over the whole store, to favour terms that set a cluster apart.
+    let mut df: HashMap<String, usize> = HashMap::new();
+    for rec in records {
+        for term in distinct_terms(rec) {
+            *df.entry(term).or_insert(0) += 1;
+        }
+    }
+
+    for (c, center) in centers.iter().enumerate() {
+        let members: Vec<&Record> = records
+            .iter()
+            .zip(assignment)
+            .filter(|(_, a)| **a == c)
+            .map(|(r, _)| r)
+            .collect();
+        if members.is_empty() {
+            continue;
+        }
+
+        let mut tf: HashMap<String, usize> = HashMap::new();
+        for rec in &members {
+            for term in distinct_terms(rec) {
+                *tf.entry(term).or_insert(0) += 1;
+            }
+        }
+        let mut terms: Vec<(f32, String)> = tf
+            .into_iter()
+            .map(|(term, n)| {
+                let idf = (records.len() as f32 / df[&term] as f32).ln_1p();
+                (n as f32 * idf, term)
+            })
+            .collect();
+        terms.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        let top: Vec<String> = terms.into_iter().take(TOP_TERMS).map(|(_, t)| t).collect();
+
+        println!("cluster {c}\t{} records\t{}", members.len(), top.join(", "));
+        let mut ranked: Vec<(f32, &Record)> = members
+            .into_iter()
+            .map(|r| (cosine_sim(center, &r.vector), r))
+            .collect();
+        ranked.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (sim, rec) in ranked.into_iter().take(REPRESENTATIVES) {
+            println!(
+                "  {sim:.3}\t{}\t{}\t{}",
+                rec.kind,
+                rec.id,
+                tsv_field(&rec.text)
+            );
+        }
+    }
+}
+
+/// Tokens worth showing as cluster labels; one-letter tokens are mostly noise.
+fn distinct_terms(rec: &Record) -> HashSet<String> {
+    tokenize(&rec.text)
+        .into_iter()
+        .filter(|t| t.chars().count() > 1)
+        .collect()
+}
@@ -0,0 +1,318 @@
+//! Columnar exchange formats, built only with their cargo feature so default
+//! builds stay free of the arrow crates.
+//!
+//! `export --format parquet` (feature `parquet`) writes one row per record:
+//! `id` as a decimal string (ids are 128-bit, wider than any Parquet
+//! integer), `ts`, `kind`, `weight`, `text`, `tags` as a list, `attrs` as a
+//! map and `vector` as a fixed-size list, null unless `--with-vectors`. Row
+//! groups are zstd-compressed. `import --format parquet` reads the same
+//! columns back; `tags`, `attrs` and `vector` may be missing, and rows without
+//! a `VECTOR_DIM` vector are re-embedded as with JSON Lines.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    FixedSizeListBuilder, Float32Builder, ListBuilder, MapBuilder, StringBuilder,
+};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float32Type, Int64Type};
+use arrow_array::{Array, ArrayRef, Float32Array, Int64Array, RecordBatch, StringArray};
+use arrow_schema::{ArrowError, Field, Schema};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+
+use crate::embed_text;
+use crate::store::{Record, VECTOR_DIM};
+
+/// Records buffered per row group.
+const BATCH_ROWS: usize = 4096;
+
+/// Streams records into a Parquet file, a row group every `BATCH_ROWS`.
+/// Call `finish` to write the footer; without it the file is unreadable.
+pub struct ParquetWriter<W: Write + Send> {
+    writer: ArrowWriter<W>,
+    pending: Vec<Record>,
+    with_vectors: bool,
+}
+
+impl<W: Write + Send> ParquetWriter<W> {
+    pub fn new(w: W, with_vectors: bool) -> io::Result<Self> {
+        let schema = record_batch(&[], with_vectors)
+            .map_err(io::Error::other)?
+            .schema();
+        let props = WriterProperties::builder()
+            .set_compression(Compression::ZSTD(ZstdLevel::default()))
+            .build();
+        let writer = ArrowWriter::try_new(w, schema, Some(props)).map_err(io::Error::other)?;
+        Ok(ParquetWriter {
+            writer,
+            pending: Vec::new(),
+            with_vectors,
+        })
+    }
+
+    pub fn push(&mut self, rec: Record) -> io::Result<()> {
+        self.pending.push(rec);
+        if self.pending.len() == BATCH_ROWS {
+            self.write_pending()?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.writer.into_inner().map_err(io::Error::other)?.flush()
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = record_batch(&self.pending, self.with_vectors).map_err(io::Error::other)?;
+        self.writer.write(&batch).map_err(io::Error::other)?;
+        self.writer.flush().map_err(io::Error::other)?;
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+/// `records` as one batch. The column types do not depend on the rows, so
+/// every batch (the empty one included) has the same schema.
+fn record_batch(records: &[Record], with_vectors: bool) -> Result<RecordBatch, ArrowError> {
+    let mut tags = ListBuilder::new(StringBuilder::new());
+    let mut attrs = MapBuilder::new(None, StringBuilder::new(), StringBuilder::new());
+    for rec in records {
+        for tag in &rec.tags {
+            tags.values().append_value(tag);
+        }
+        tags.append(true);
+        for (key, value) in &rec.attrs {
+            attrs.keys().append_value(key);
+            attrs.values().append_value(value);
+        }
+        attrs.append(true)?;
+    }
+    let mut vectors = FixedSizeListBuilder::new(Float32Builder::new(), VECTOR_DIM as i32);
+    for rec in records {
+        if with_vectors {
+            // Keep every row VECTOR_DIM wide even if a record vector is short.
+            for j in 0..VECTOR_DIM {
+                vectors
+                    .values()
+                    .append_value(rec.vector.get(j).copied().unwrap_or(0.0));
+            }
+            vectors.append(true);
+        } else {
+            vectors.values().append_nulls(VECTOR_DIM);
+            vectors.append(false);
+        }
+    }
+
+    let columns: [(&str, ArrayRef); 8] = [
+        (
+            "id",
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|r| r.id.to_string()),
+            )),
+        ),
+        (
+            "ts",
+            Arc::new(Int64Array::from_iter_values(records.iter().map(|r| r.ts))),
+        ),
+        (
+            "kind",
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|r| &r.kind),
+            )),
+        ),
+        (
+            "weight",
+            Arc::new(Float32Array::from_iter_values(
+                records.iter().map(|r| r.weight),
+            )),
+        ),
+        (
+            "text",
+            Arc::new(StringArray::from_iter_values(
+                records.iter().map(|r| &r.text),
+            )),
+        ),
+        ("tags", Arc::new(tags.finish())),
+        ("attrs", Arc::new(attrs.finish())),
+        ("vector", Arc::new(vectors.finish())),
+    ];
+    let fields: Vec<Field> = columns
+        .iter()
+        .map(|(name, column)| Field::new(*name, column.data_type().clone(), *name == "vector"))
+        .collect();
+    let schema = Arc::new(Schema::new(fields));
+    RecordBatch::try_new(schema, columns.into_iter().map(|(_, c)| c).collect())
+}
+
+/// Every row of a Parquet file written by `export --format parquet`, or
+/// another tool using the same column names and types.
+pub fn read_parquet(file: File) -> io::Result<Vec<Record>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .and_then(|b| b.build())
+        .map_err(|e| invalid(e.to_string()))?;
+    let mut records = Vec::new();
+    for batch in reader {
+        let batch = batch.map_err(|e| invalid(e.to_string()))?;
+        read_batch(&batch, &mut records)?;
+    }
+    Ok(records)
+}
+
+fn read_batch(batch: &RecordBatch, records: &mut Vec<Record>) -> io::Result<()> {
+    let ids = strings(batch, "id")?;
+    let kinds = strings(batch, "kind")?;
+    let texts = strings(batch, "text")?;
+    let ts = column(batch, "ts")?
+        .as_primitive_opt::<Int64Type>()
+        .ok_or_else(|| wrong_type("ts", "int64"))?;
+    let weights = column(batch, "weight")?
+        .as_primitive_opt::<Float32Type>()
+        .ok_or_else(|| wrong_type("weight", "float"))?;
+    let tags = batch
+        .column_by_name("tags")
+        .map(|c| {
+            c.as_list_opt::<i32>()
+                .ok_or_else(|| wrong_type("tags", "list of strings"))
+        })
+        .transpose()?;
+    let attrs = batch
+        .column_by_name("attrs")
+        .map(|c| {
+            c.as_map_opt()
+                .ok_or_else(|| wrong_type("attrs", "map of strings"))
+        })
+        .transpose()?;
+    let vectors = batch
+        .column_by_name("vector")
+        .map(|c| {
+            c.as_fixed_size_list_opt()
+                .ok_or_else(|| wrong_type("vector", "fixed-size list of floats"))
+        })
+        .transpose()?;
+
+    for i in 0..batch.num_rows() {
+        let row = records.len() + 1;
+        let Ok(id) = ids.value(i).parse::<u128>() else {
+            return Err(invalid(format!(
+                "row {row}: id {:?} is not a record id",
+                ids.value(i)
+            )));
+        };
+        let weight = weights.value(i);
+        if !weight.is_finite() {
+            return Err(invalid(format!(
+                "row {row}: weight must be a finite number"
+            )));
+        }
+        let mut rec = Record {
+            id,
+            ts: ts.value(i),
+            kind: kinds.value(i).to_string(),
+            weight,
+            text: texts.value(i).to_string(),
+            vector: Vec::new(),
+            tags: Vec::new(),
+            attrs: Default::default(),
+        };
+        if let Some(tags) = tags.filter(|t| t.is_valid(i)) {
+            let values = tags.value(i);
+            let values = values
+                .as_string_opt::<i32>()
+                .ok_or_else(|| wrong_type("tags", "list of strings"))?;
+            rec.tags = values.iter().flatten().map(str::to_string).collect();
+        }
+        if let Some(attrs) = attrs.filter(|a| a.is_valid(i)) {
+            let entries = attrs.value(i);
+            let (Some(keys), Some(values)) = (
+                entries.column(0).as_string_opt::<i32>(),
+                entries.column(1).as_string_opt::<i32>(),
+            ) else {
+                return Err(wrong_type("attrs", "map of strings"));
+            };
+            rec.attrs = keys
+                .iter()
+                .zip(values.iter())
+                .filter_map(|(k, v)| Some((k?.to_string(), v?.to_string())))
+                .collect();
+        }
+        if let Some(vectors) = vectors.filter(|v| v.is_valid(i)) {
+            let values = vectors.value(i);
+            if let Some(values) = values.as_primitive_opt::<Float32Type>() {
+                rec.vector = values.values().to_vec();
+            }
+        }
+        if rec.vector.len() != VECTOR_DIM {
+            rec.vector = embed_text(&rec.text);
+        }
+        records.push(rec);
+    }
+    Ok(())
+}
+
+fn column<'a>(batch: &'a RecordBatch, name: &str) -> io::Result<&'a ArrayRef> {
+    batch
+        .column_by_name(name)
+        .ok_or_else(|| invalid(format!("missing column {name:?}")))
+}
+
+fn strings<'a>(batch: &'a RecordBatch, name: &str) -> io::Result<&'a StringArray> {
+    column(batch, name)?
+        .as_string_opt::<i32>()
+        .ok_or_else(|| wrong_type(name, "string"))
+}
+
+fn wrong_type(name: &str, expected: &str) -> io::Error {
+    invalid(format!("column {name:?} is not a {expected} column"))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::store::tests::any_record;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(16))]
+
+        /// `export --format parquet --with-vectors` read back by `import` as the same records.
+        #[test]
+        fn parquet_round_trips(records in prop::collection::vec(any_record(), 0..6)) {
+            let out = env::temp_dir().join(format!("memstore-parquet-{}.parquet", std::process::id()));
+            let mut w = ParquetWriter::new(File::create(&out).unwrap(), true).unwrap();
+            for rec in records.clone() {
+                w.push(rec).unwrap();
+            }
+            w.finish().unwrap();
+            let read = read_parquet(File::open(&out).unwrap()).unwrap();
+            let _ = fs::remove_file(&out);
+            prop_assert_eq!(read.len(), records.len());
+            for (got, want) in read.iter().zip(&records) {
+                prop_assert_eq!(got.id, want.id);
+                prop_assert_eq!(got.ts, want.ts);
+                prop_assert_eq!(&got.kind, &want.kind);
+                prop_assert_eq!(got.weight, want.weight);
+                prop_assert_eq!(&got.text, &want.text);
+                prop_assert_eq!(&got.tags, &want.tags);
+                prop_assert_eq!(&got.attrs, &want.attrs);
+                prop_assert_eq!(&got.vector, &want.vector);
+            }
+        }
+    }
+}
@@ -0,0 +1,599 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use directories::{BaseDirs, ProjectDirs};
+use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item};
+
+use crate::classify::Classify;
+use crate::filter::parse_duration;
+use crate::store::write_atomic;
+use crate::{IndexKind, Scoring};
+
+/// Contents of `config.toml`.
+///
+/// ```toml
+/// auto_compact = { max_records = 20000, max_bytes = "50MB" }
+/// max_text = { max_bytes = "64KB", policy = "chunk" }
+/// weight_range = { min = 0.0, max = 5.0 }
+///
+/// [profiles.work]
+/// path = "/home/me/work/.memory/memories.hnsw"
+/// limit = 5
+/// max_memory = "512MB"
+/// default_max_age = "365d"
+/// classify = { mode = "rules" }
+///
+/// [profiles.work.scoring]
+/// similarity = 2.0
+/// weight = 0.5
+/// recency = 0.2
+/// kind_boost = { decision = 1.5, chitchat = 0.5 }
+/// decay = { mode = "exponential", half_life_days = 90 }
+/// kind_decay = { profile = { mode = "none" }, todo = { mode = "step", days = [7, 30] } }
+///
+/// [saved]
+/// standup = "kind:decision after:1d"
+///
+/// [kind_aliases]
+/// decisions = "decision"
+/// "Decision-log" = "decision"
+///
+/// [kinds]
+/// allowed = ["summary", "decision", "todo", "profile", "caution"]
+/// unknown = "reject"
+///
+/// [templates.decision]
+/// text = "Decision: {title}\nRationale: {rationale}\nAlternatives: {alternatives}"
+/// required = ["title", "rationale"]
+/// tags = ["decision-log"]
+/// attrs = ["title"]
+/// ```
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Named `--query` strings for `saved run`.
+    #[serde(default)]
+    pub saved: BTreeMap<String, String>,
+    /// Applies to every store without a profile setting, `serve` included.
+    pub auto_compact: Option<AutoCompact>,
+    /// Same scope as `auto_compact`.
+    pub max_text: Option<TextLimit>,
+    pub weight_range: Option<WeightRange>,
+    /// Kinds to search instead of one that no record has, e.g. `decisions = "decision"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub kind_aliases: BTreeMap<String, String>,
+    /// Kinds `add` expects; edited with `memstore kinds`.
+    pub kinds: Option<KindRegistry>,
+    /// Record layouts for `add --template`, see `templates.rs`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, Template>,
+}
+
+/// Text with `{field}` placeholders that `add --template` fills in.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Template {
+    pub text: String,
+    /// Kind of the records; the template's name if unset.
+    pub kind: Option<String>,
+    /// Fields that must be given.
+    #[serde(default)]
+    pub required: Vec<String>,
+    /// Added to the tags given with `--tag`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Fields also stored as record attributes, which exports carry along.
+    #[serde(default)]
+    pub attrs: Vec<String>,
+}
+
+/// The kinds records may have. An empty list allows any kind.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct KindRegistry {
+    #[serde(default)]
+    pub allowed: Vec<String>,
+    #[serde(default)]
+    pub unknown: UnknownKind,
+}
+
+/// What `add` does with a kind missing from the registry.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UnknownKind {
+    /// Add it and print a warning.
+    #[default]
+    Warn,
+    Reject,
+}
+
+/// Limits checked after each `add`; a store over either is compacted.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AutoCompact {
+    pub max_records: Option<usize>,
+    /// A size such as `"50MB"`, see `parse_size`.
+    pub max_bytes: Option<String>,
+}
+
+impl AutoCompact {
+    /// `max_bytes` in bytes; it was checked when the config was read.
+    pub fn byte_limit(&self) -> Option<u64> {
+        self.max_bytes.as_deref().and_then(parse_size)
+    }
+
+    fn check(&self, name: &str) -> Result<(), &'static str> {
+        match self.max_bytes.as_deref() {
+            Some(size) if parse_size(size).is_none() => {
+                eprintln!("{name}: invalid auto_compact.max_bytes {size:?} (use e.g. 50MB)");
+                Err("invalid size")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Largest record text `add` accepts, and what happens to longer ones.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TextLimit {
+    /// A size such as `"64KB"`, see `parse_size`.
+    pub max_bytes: String,
+    #[serde(default)]
+    pub policy: OversizePolicy,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OversizePolicy {
+    #[default]
+    Reject,
+    /// Keep the first `max_bytes`.
+    Truncate,
+    /// Store the text as several records of at most `max_bytes` each.
+    Chunk,
+}
+
+impl TextLimit {
+    /// `max_bytes` in bytes; it was checked when the config was read.
+    pub fn byte_limit(&self) -> usize {
+        parse_size(&self.max_bytes).map_or(usize::MAX, |n| n as usize)
+    }
+
+    fn check(&self, name: &str) -> Result<(), &'static str> {
+        match parse_size(&self.max_bytes) {
+            Some(n) if n > 0 => Ok(()),
+            _ => {
+                eprintln!("{name}: invalid max_text.max_bytes {:?} (use e.g. 64KB)", self.max_bytes);
+                Err("invalid size")
+            }
+        }
+    }
+}
+
+/// Weights `add` accepts, inclusive. Without one, any finite weight of at
+/// least 0 is allowed.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct WeightRange {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl WeightRange {
+    fn check(&self, name: &str) -> Result<(), &'static str> {
+        if self.min.is_finite() && self.max.is_finite() && self.min <= self.max {
+            return Ok(());
+        }
+        eprintln!("{name}: invalid weight_range {} to {}", self.min, self.max);
+        Err("invalid weight range")
+    }
+}
+
+/// What `add` checks text and weights against, from the profile or else the
+/// top level of the config.
+#[derive(Clone, Debug, Default)]
+pub struct AddLimits {
+    pub max_text: Option<TextLimit>,
+    pub weight_range: Option<WeightRange>,
+    pub kinds: Option<KindRegistry>,
+}
+
+impl AddLimits {
+    /// A weight that is finite and inside `weight_range`, or an error saying why not.
+    pub fn check_weight(&self, weight: f32) -> Result<(), String> {
+        let (min, max) = self.weight_range.map_or((0.0, f32::MAX), |r| (r.min, r.max));
+        if !weight.is_finite() {
+            return Err(format!("weight {weight} is not a finite number"));
+        }
+        if weight < min || weight > max {
+            return match self.weight_range {
+                Some(_) => Err(format!("weight {weight} is outside weight_range {min} to {max}")),
+                None => Err(format!("weight {weight} is negative")),
+            };
+        }
+        Ok(())
+    }
+
+    /// `Ok(None)` for a registered kind (or no registry), `Ok(Some(warning))`
+    /// for an unknown one under `warn`, and an error under `reject`.
+    pub fn check_kind(&self, kind: &str) -> Result<Option<String>, String> {
+        let Some(registry) = self.kinds.as_ref().filter(|r| !r.allowed.is_empty()) else {
+            return Ok(None);
+        };
+        if registry.allowed.iter().any(|k| k == kind) {
+            return Ok(None);
+        }
+        let hint = match crate::kinds::closest(kind, &registry.allowed) {
+            Some(near) => format!("; did you mean {near:?}?"),
+            None => format!(" (known: {})", registry.allowed.join(", ")),
+        };
+        let message = format!("unknown kind {kind:?}{hint}");
+        match registry.unknown {
+            UnknownKind::Warn => Ok(Some(message)),
+            UnknownKind::Reject => Err(message),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Profile {
+    pub path: Option<PathBuf>,
+    /// Only the built-in feature-hashing embedder ("hash") exists today.
+    pub embedder: Option<String>,
+    pub limit: Option<usize>,
+    /// Estimate `add` weights when `--weight` is not given.
+    pub auto_weight: Option<bool>,
+    /// Pick a kind when `--kind` is not given, see `classify.rs`.
+    pub classify: Option<Classify>,
+    /// `hnsw` (default), or `ivf` / `pq` for very large stores.
+    pub index: Option<IndexKind>,
+    /// Re-rank `pq` candidates from the full vectors.
+    pub rerank: Option<bool>,
+    /// Months of shards `search` covers in a sharded store.
+    pub shard_window: Option<usize>,
+    /// A size such as `"512MB"`; `search` streams store files larger than
+    /// this instead of loading them.
+    pub max_memory: Option<String>,
+    /// A duration such as `"365d"`; `search` and `recall` leave out older
+    /// records unless the search sets its own time bounds or `--all-time`.
+    pub default_max_age: Option<String>,
+    pub auto_compact: Option<AutoCompact>,
+    pub max_text: Option<TextLimit>,
+    pub weight_range: Option<WeightRange>,
+    #[serde(default, skip_serializing_if = "is_default_scoring")]
+    pub scoring: Scoring,
+}
+
+impl Profile {
+    /// `max_memory` in bytes; it was checked when the config was read.
+    pub fn memory_limit(&self) -> Option<u64> {
+        self.max_memory.as_deref().and_then(parse_size)
+    }
+
+    /// `default_max_age` in seconds; it was checked when the config was read.
+    pub fn max_age(&self) -> Option<i64> {
+        self.default_max_age.as_deref().and_then(parse_duration)
+    }
+}
+
+fn is_default_scoring(scoring: &Scoring) -> bool {
+    *scoring == Scoring::default()
+}
+
+/// `MEMSTORE_CONFIG`, else `~/.config/memstore/config.toml` if it exists (where
+/// every platform used to look), else the platform config directory.
+pub fn config_path() -> PathBuf {
+    if let Some(p) = env::var_os("MEMSTORE_CONFIG") {
+        return PathBuf::from(p);
+    }
+    let legacy = BaseDirs::new().map(|d| d.home_dir().join(".config/memstore/config.toml"));
+    if let Some(legacy) = legacy.filter(|p| p.exists()) {
+        return legacy;
+    }
+    match project_dirs() {
+        Some(dirs) => dirs.config_dir().join("config.toml"),
+        None => PathBuf::from("memstore.toml"),
+    }
+}
+
+/// Per-user directory for store files: `~/.local/share/memstore` on Linux,
+/// `~/Library/Application Support/memstore` on macOS, `%APPDATA%\memstore\data`
+/// on Windows. `None` when no home directory can be found.
+pub fn data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "memstore")
+}
+
+pub fn load_config() -> io::Result<Config> {
+    let path = config_path();
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    let text = fs::read_to_string(&path)?;
+    toml::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))
+}
+
+/// Writes `saved` and `kinds`, the sections the CLI edits, into the config
+/// file in place. The rest of the file, comments and key order included, and
+/// the entries of those sections that did not change are left as they were.
+/// The file is replaced atomically, so a crash never leaves half of it.
+pub fn save_config(config: &Config) -> io::Result<()> {
+    let path = config_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    let text = edit_config(&text, config)?;
+    write_atomic(&path, &[text.as_bytes()])
+}
+
+/// `text` with the `saved` and `kinds` sections of `config`.
+fn edit_config(text: &str, config: &Config) -> io::Result<String> {
+    let mut doc: DocumentMut = text.parse().map_err(invalid_config)?;
+    update_section(&mut doc, "saved", &config.saved)?;
+    match &config.kinds {
+        Some(kinds) => update_section(&mut doc, "kinds", kinds)?,
+        None => {
+            doc.remove("kinds");
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// Makes `doc[key]` hold what `value` serializes to: new entries are appended,
+/// changed ones replaced and missing ones removed.
+fn update_section(doc: &mut DocumentMut, key: &str, value: &impl Serialize) -> io::Result<()> {
+    let new: DocumentMut = toml::to_string(value)
+        .map_err(invalid_config)?
+        .parse()
+        .map_err(invalid_config)?;
+    let new = new.as_table();
+    let Some(old) = doc.get_mut(key).and_then(Item::as_table_like_mut) else {
+        if !new.is_empty() {
+            doc.insert(key, Item::Table(new.clone()));
+        }
+        return Ok(());
+    };
+    let stale: Vec<String> = old
+        .iter()
+        .map(|(k, _)| k.to_string())
+        .filter(|k| !new.contains_key(k))
+        .collect();
+    for k in stale {
+        old.remove(&k);
+    }
+    for (k, item) in new.iter() {
+        if !old.get(k).is_some_and(|o| same_value(o, item)) {
+            old.insert(k, item.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Whether two values are equal however they are written (quoting, spacing,
+/// arrays over several lines, trailing comments).
+fn same_value(a: &Item, b: &Item) -> bool {
+    let parse = |item: &Item| {
+        let value = item.as_value()?;
+        toml::from_str::<toml::Table>(&format!("v = {value}")).ok()
+    };
+    matches!((parse(a), parse(b)), (Some(a), Some(b)) if a == b)
+}
+
+fn invalid_config(e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e.to_string())
+}
+
+/// Resolves `--profile <name>`; without a name the built-in defaults apply.
+pub fn select_profile(name: Option<&str>) -> Result<Profile, &'static str> {
+    let Some(name) = name else {
+        return Ok(Profile::default());
+    };
+    let mut config = load_config().map_err(|e| {
+        eprintln!("Cannot read {}: {e}", config_path().display());
+        "config failed"
+    })?;
+    let Some(profile) = config.profiles.remove(name) else {
+        eprintln!("Unknown profile: {name}");
+        return Err("unknown profile");
+    };
+    check_profile(name, &profile)?;
+    Ok(profile)
+}
+
+/// The profile's `auto_compact`, else the top-level one.
+pub fn auto_compact(profile: &Profile) -> Result<Option<AutoCompact>, &'static str> {
+    if let Some(limits) = &profile.auto_compact {
+        return Ok(Some(limits.clone()));
+    }
+    let config = load_config().map_err(|e| {
+        eprintln!("Cannot read {}: {e}", config_path().display());
+        "config failed"
+    })?;
+    if let Some(limits) = &config.auto_compact {
+        limits.check("config")?;
+    }
+    Ok(config.auto_compact)
+}
+
+/// The profile's `max_text` and `weight_range`, each else the top-level one,
+/// and the kind registry.
+pub fn add_limits(profile: &Profile) -> Result<AddLimits, &'static str> {
+    let config = load_config().map_err(|e| {
+        eprintln!("Cannot read {}: {e}", config_path().display());
+        "config failed"
+    })?;
+    if let Some(limit) = &config.max_text {
+        limit.check("config")?;
+    }
+    if let Some(range) = &config.weight_range {
+        range.check("config")?;
+    }
+    Ok(AddLimits {
+        max_text: profile.max_text.clone().or(config.max_text),
+        weight_range: profile.weight_range.or(config.weight_range),
+        kinds: config.kinds,
+    })
+}
+
+/// Parses `512`, `800KB`, `50MB` or `2GB` (powers of 1024) into bytes.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let value: u64 = s[..split].parse().ok()?;
+    let unit = match s[split..].trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "KB" => 1 << 10,
+        "MB" => 1 << 20,
+        "GB" => 1 << 30,
+        _ => return None,
+    };
+    value.checked_mul(unit)
+}
+
+/// All configured profiles, for `search --all-profiles`.
+pub fn all_profiles() -> Result<Vec<(String, Profile)>, &'static str> {
+    let config = load_config().map_err(|e| {
+        eprintln!("Cannot read {}: {e}", config_path().display());
+        "config failed"
+    })?;
+    for (name, profile) in &config.profiles {
+        check_profile(name, profile)?;
+    }
+    Ok(config.profiles.into_iter().collect())
+}
+
+fn check_profile(name: &str, profile: &Profile) -> Result<(), &'static str> {
+    if let Some(limits) = &profile.auto_compact {
+        limits.check(&format!("Profile {name}"))?;
+    }
+    if let Some(limit) = &profile.max_text {
+        limit.check(&format!("Profile {name}"))?;
+    }
+    if let Some(range) = &profile.weight_range {
+        range.check(&format!("Profile {name}"))?;
+    }
+    if let Some(size) = profile.max_memory.as_deref() {
+        if parse_size(size).is_none() {
+            eprintln!("Profile {name}: invalid max_memory {size:?} (use e.g. 512MB)");
+            return Err("invalid size");
+        }
+    }
+    if let Some(age) = profile.default_max_age.as_deref() {
+        if parse_duration(age).is_none() {
+            eprintln!("Profile {name}: invalid default_max_age {age:?} (use e.g. 365d)");
+            return Err("invalid duration");
+        }
+    }
+    for (kind, boost) in &profile.scoring.kind_boost {
+        if !boost.is_finite() || *boost < 0.0 {
+            eprintln!("Profile {name}: kind_boost for {kind:?} must be a number of at least 0");
+            return Err("invalid kind boost");
+        }
+    }
+    let decays = std::iter::once((None, &profile.scoring.decay))
+        .chain(profile.scoring.kind_decay.iter().map(|(k, d)| (Some(k), d)));
+    for (kind, decay) in decays {
+        if let Err(e) = decay.check() {
+            match kind {
+                Some(kind) => eprintln!("Profile {name}: kind_decay for {kind:?}: {e}"),
+                None => eprintln!("Profile {name}: decay: {e}"),
+            }
+            return Err("invalid decay");
+        }
+    }
+    match profile.embedder.as_deref() {
+        None | Some("hash") => Ok(()),
+        Some(other) => {
+            eprintln!("Profile {name}: unknown embedder {other:?} (supported: hash)");
+            Err("unknown embedder")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The first ```toml block of `text` that sets up a `[profiles.work]`.
+    fn example(text: &str) -> String {
+        text.split("```toml\n")
+            .skip(1)
+            .map(|block| block.split("```").next().unwrap_or(""))
+            .find(|block| block.contains("[profiles.work]"))
+            .expect("no example config")
+            .to_string()
+    }
+
+    fn check(config: &Config) {
+        assert!(config.profiles.contains_key("work"));
+        assert_eq!(config.saved.len(), 1);
+        let limits = config.auto_compact.as_ref().expect("top-level auto_compact");
+        assert_eq!(limits.max_records, Some(20000));
+        assert_eq!(limits.byte_limit(), Some(50 << 20));
+        assert!(config.kinds.is_some());
+        assert!(config.templates.contains_key("decision"));
+    }
+
+    #[test]
+    fn readme_example_parses() {
+        let text = example(include_str!("../README.md"));
+        let config: Config = toml::from_str(&text).unwrap();
+        check(&config);
+    }
+
+    #[test]
+    fn doc_example_parses() {
+        let source = include_str!("config.rs");
+        let doc: String = source
+            .lines()
+            .filter_map(|line| line.strip_prefix("///"))
+            .map(|line| format!("{}\n", line.strip_prefix(' ').unwrap_or(line)))
+            .collect();
+        let config: Config = toml::from_str(&example(&doc)).unwrap();
+        check(&config);
+        assert!(config.max_text.is_some());
+        assert!(config.weight_range.is_some());
+    }
+
+    #[test]
+    fn saving_keeps_comments_and_other_settings() {
+        let text = "\
+# memstore settings
+weight_range = { min = 0.0, max = 5.0 } # keep weights sane
+
+[saved]
+# weekly review
+recent-decisions = \"kind:decision since:7d\"
+old = \"deploy\"
+
+[kinds]
+allowed = [
+    \"decision\", # what we chose
+    \"fact\",
+]
+";
+        let mut config: Config = toml::from_str(text).unwrap();
+        config.saved.remove("old");
+        config.saved.insert("todo".to_string(), "kind:todo".to_string());
+        let edited = edit_config(text, &config).unwrap();
+        assert!(edited.starts_with("# memstore settings\n"), "{edited}");
+        assert!(edited.contains("# keep weights sane"), "{edited}");
+        assert!(edited.contains("# weekly review\nrecent-decisions"), "{edited}");
+        assert!(edited.contains("\"decision\", # what we chose"), "{edited}");
+        assert!(!edited.contains("old ="), "{edited}");
+
+        let reread: Config = toml::from_str(&edited).unwrap();
+        assert_eq!(reread.saved, config.saved);
+        assert_eq!(reread.kinds.unwrap().allowed, ["decision", "fact"]);
+        assert!(reread.weight_range.is_some());
+    }
+}
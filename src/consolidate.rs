@@ -0,0 +1,138 @@
+//! `compact --consolidate-cmd`: replaces groups of old, similar records with a
+//! summary produced by an external command (typically an LLM wrapper).
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::store::{load_store, update_store, Record};
+use crate::{cosine_sim, embed_text, now_millis, now_secs};
+
+/// Outcome of one consolidation pass.
+pub struct Consolidation {
+    pub groups: usize,
+    pub replaced: usize,
+}
+
+/// Groups records older than `min_age` secs by kind and greedy cosine clustering
+/// at `threshold`, pipes each group of two or more to `cmd` and stores its stdout
+/// as one record listing the originals under the `supersedes` attribute.
+///
+/// The command runs without the store lock held; groups whose records were
+/// changed or removed in the meantime are skipped.
+pub fn consolidate(
+    path: &Path,
+    cmd: &str,
+    min_age: i64,
+    threshold: f32,
+) -> io::Result<Consolidation> {
+    let cutoff = now_secs() - min_age;
+    let records = load_store(path)?.records;
+    let old: Vec<&Record> = records.iter().filter(|r| r.ts < cutoff).collect();
+
+    let mut summaries: Vec<(Vec<u128>, Record)> = Vec::new();
+    for (id, group) in (now_millis()..).zip(similar_groups(&old, threshold)) {
+        let input = group
+            .iter()
+            .map(|r| r.text.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let text = run_command(cmd, &input)?;
+
+        let mut tags: Vec<String> = Vec::new();
+        for tag in group.iter().flat_map(|r| &r.tags) {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        let ids: Vec<u128> = group.iter().map(|r| r.id).collect();
+        let superseded: Vec<String> = ids.iter().map(u128::to_string).collect();
+        let summary = Record {
+            id,
+            ts: group.iter().map(|r| r.ts).max().unwrap_or(cutoff),
+            kind: group[0].kind.clone(),
+            weight: group.iter().map(|r| r.weight).fold(f32::MIN, f32::max),
+            vector: embed_text(&text),
+            text,
+            tags,
+            attrs: BTreeMap::from([("supersedes".to_string(), superseded.join(","))]),
+        };
+        summaries.push((ids, summary));
+    }
+
+    update_store(path, |store| {
+        let mut outcome = Consolidation {
+            groups: 0,
+            replaced: 0,
+        };
+        for (ids, mut summary) in summaries {
+            let present: HashSet<u128> = store.records.iter().map(|r| r.id).collect();
+            if !ids.iter().all(|id| present.contains(id)) {
+                continue;
+            }
+            // Keep ids unique even if other writers added records meanwhile.
+            while present.contains(&summary.id) {
+                summary.id += 1;
+            }
+            store.records.retain(|r| !ids.contains(&r.id));
+            store.records.push(summary);
+            outcome.groups += 1;
+            outcome.replaced += ids.len();
+        }
+        Ok(outcome)
+    })
+}
+
+/// Leader clustering within each kind: a record joins the first group whose first
+/// member is at least `threshold` similar. Only groups of two or more are returned.
+fn similar_groups<'a>(records: &[&'a Record], threshold: f32) -> Vec<Vec<&'a Record>> {
+    let mut groups: Vec<Vec<&Record>> = Vec::new();
+    for &rec in records {
+        let joined = groups
+            .iter_mut()
+            .find(|g| g[0].kind == rec.kind && cosine_sim(&g[0].vector, &rec.vector) >= threshold);
+        match joined {
+            Some(group) => group.push(rec),
+            None => groups.push(vec![rec]),
+        }
+    }
+    groups.retain(|g| g.len() > 1);
+    groups
+}
+
+/// Runs `cmd` through the shell with `input` on stdin and returns trimmed stdout.
+pub fn run_command(cmd: &str, input: &str) -> io::Result<String> {
+    let mut command = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.args(["/C", cmd]);
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.args(["-c", cmd]);
+        c
+    };
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A command that exits without reading its input is judged by its status below.
+        match stdin.write_all(input.as_bytes()) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+            _ => {}
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{cmd:?} failed with {}",
+            output.status
+        )));
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if text.is_empty() {
+        return Err(io::Error::other(format!("{cmd:?} printed nothing")));
+    }
+    Ok(text)
+}
@@ -0,0 +1,62 @@
+//! `search --exclude-similar-to-file <file>`: drops hits that repeat what the
+//! caller's prompt already holds, so injected memory adds something new.
+//!
+//! Each hit is compared with every stretch of the file as long as the hit
+//! itself (windows of its token count, overlapping by half), and dropped if
+//! the closest stretch reaches the threshold. Comparing like-sized stretches
+//! keeps a memory quoted inside a long transcript from being diluted by the
+//! text around it.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::{cosine_sim, embed_text, tokenize, Record};
+
+/// Cosine similarity at which a hit counts as already in the context.
+pub const DEFAULT_THRESHOLD: f32 = 0.8;
+
+pub struct Context {
+    tokens: Vec<String>,
+}
+
+impl Context {
+    /// Reads `path`, or stdin for `-`.
+    pub fn load(path: &Path) -> io::Result<Context> {
+        let text = if path == Path::new("-") {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text)?;
+            text
+        } else {
+            fs::read_to_string(path)?
+        };
+        Ok(Context {
+            tokens: tokenize(&text),
+        })
+    }
+
+    /// Highest similarity between `rec` and a stretch of the context.
+    pub fn overlap(&self, rec: &Record) -> f32 {
+        let len = tokenize(&rec.text).len();
+        if len == 0 || self.tokens.is_empty() {
+            return 0.0;
+        }
+        let query = embed_text(&rec.text);
+        let step = (len / 2).max(1);
+        let mut best = 0.0f32;
+        let mut start = 0;
+        loop {
+            let end = (start + len).min(self.tokens.len());
+            let window = embed_text(&self.tokens[start..end].join(" "));
+            best = best.max(cosine_sim(&query, &window));
+            if end == self.tokens.len() {
+                return best;
+            }
+            start += step;
+        }
+    }
+
+    pub fn contains(&self, rec: &Record, threshold: f32) -> bool {
+        self.overlap(rec) >= threshold
+    }
+}
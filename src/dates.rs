@@ -0,0 +1,173 @@
+//! UTC calendar conversions for timestamps, without a date library.
+//! Based on Howard Hinnant's `days_from_civil` / `civil_from_days`.
+
+/// `(year, month, day)` for a unix timestamp in seconds.
+pub fn civil_from_ts(ts: i64) -> (i64, u32, u32) {
+    let z = ts.div_euclid(86400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Unix timestamp of midnight UTC on the given date.
+pub fn ts_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let m = i64::from(month);
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe - 719_468) * 86400
+}
+
+/// `YYYY-MM-DD`.
+pub fn format_date(ts: i64) -> String {
+    let (y, m, d) = civil_from_ts(ts);
+    format!("{y:04}-{m:02}-{d:02}")
+}
+
+/// `YYYY-MM` for monthly grouping.
+pub fn format_month(ts: i64) -> String {
+    let (y, m, _) = civil_from_ts(ts);
+    format!("{y:04}-{m:02}")
+}
+
+/// `HH:MM` within the UTC day.
+pub fn format_time(ts: i64) -> String {
+    let secs = ts.rem_euclid(86400);
+    format!("{:02}:{:02}", secs / 3600, secs % 3600 / 60)
+}
+
+/// Timestamp of the Monday starting the week that contains `ts`.
+pub fn week_start(ts: i64) -> i64 {
+    let days = ts.div_euclid(86400);
+    // 1970-01-01 was a Thursday, three days after a Monday.
+    let monday = days - (days + 3).rem_euclid(7);
+    monday * 86400
+}
+
+/// Parses `YYYY-MM-DD` into midnight UTC. Years run from 0 to 9999, which
+/// keeps the arithmetic in `ts_from_civil` far from overflowing.
+pub fn parse_date(s: &str) -> Option<i64> {
+    let mut parts = s.trim().splitn(3, '-');
+    let year: i64 = parts.next()?.parse().ok()?;
+    let month: u32 = parts.next()?.parse().ok()?;
+    let day: u32 = parts.next()?.parse().ok()?;
+    if !(0..=9999).contains(&year) || !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let ts = ts_from_civil(year, month, day);
+    // Reject days past the end of the month (e.g. 2024-02-31).
+    (civil_from_ts(ts) == (year, month, day)).then_some(ts)
+}
+
+/// Parses an RFC 3339 / ISO 8601 timestamp such as `2024-07-01T12:00:00Z`,
+/// `2024-07-01T12:00:00.123456-07:00` or `2024-07-01 12:00:00` (taken as UTC)
+/// into unix seconds. A bare date is midnight UTC.
+pub fn parse_datetime(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let (date, rest) = match s.find(['T', 't', ' ']) {
+        Some(at) => (&s[..at], &s[at + 1..]),
+        None => return parse_date(s),
+    };
+    let day = parse_date(date)?;
+    // The zone starts at a trailing `Z` or the first sign after the seconds.
+    let zone_at = rest.find(['Z', 'z', '+', '-']).unwrap_or(rest.len());
+    let (time, zone) = rest.split_at(zone_at);
+    let mut parts = time.splitn(3, ':');
+    let hour: i64 = parts.next()?.parse().ok()?;
+    let minute: i64 = parts.next()?.parse().ok()?;
+    let second: i64 = match parts.next() {
+        // Fractional seconds are dropped.
+        Some(sec) => sec.split('.').next()?.parse().ok()?,
+        None => 0,
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let offset = match zone {
+        "" | "Z" | "z" => 0,
+        _ => {
+            let sign = if zone.starts_with('-') { -1 } else { 1 };
+            let digits = &zone[1..];
+            // Taken apart by byte offset, so only ASCII gets that far.
+            if !digits.is_ascii() {
+                return None;
+            }
+            let (h, m) = match digits.split_once(':') {
+                Some(hm) => hm,
+                None if digits.len() == 4 => digits.split_at(2),
+                None => (digits, "0"),
+            };
+            let (h, m) = (h.parse::<i64>().ok()?, m.parse::<i64>().ok()?);
+            if h > 23 || m > 59 {
+                return None;
+            }
+            sign * (h * 3600 + m * 60)
+        }
+    };
+    Some(day + hour * 3600 + minute * 60 + second - offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_outside_years_0_to_9999_are_errors() {
+        assert_eq!(parse_date("1970-01-01"), Some(0));
+        assert_eq!(parse_date("9999-12-31"), Some(253_402_214_400));
+        assert!(parse_date("0000-03-01").is_some());
+        for s in [
+            "10000-01-01",
+            "99999999999999999-01-01",
+            "-1-01-01",
+            "+10000-01-01",
+        ] {
+            assert_eq!(parse_date(s), None, "{s}");
+            assert_eq!(parse_datetime(&format!("{s}T00:00Z")), None, "{s}");
+        }
+    }
+
+    #[test]
+    fn datetimes_parse_with_and_without_zones() {
+        let noon = 1_704_110_400;
+        for s in [
+            "2024-01-01T12:00:00Z",
+            "2024-01-01t12:00z",
+            "2024-01-01 12:00:00",
+            "2024-01-01T12:00:00.123456Z",
+            "2024-01-01T13:00:00+01:00",
+            "2024-01-01T05:30:00-0630",
+            "2024-01-01T14:00+02",
+        ] {
+            assert_eq!(parse_datetime(s), Some(noon), "{s}");
+        }
+        assert_eq!(parse_datetime("2024-01-01"), Some(noon - 12 * 3600));
+    }
+
+    #[test]
+    fn malformed_datetimes_are_errors() {
+        for s in [
+            "",
+            "T",
+            "2024-01-01T",
+            "2024-01-01T12:00+\u{20ac}1",
+            "2024-01-01T12:00+\u{20ac}\u{20ac}",
+            "2024-01-01T12:00+12\u{e9}",
+            "2024-01-01T12:00+99999999999999999999",
+            "2024-01-01T12:00+9999999999999999:00",
+            "2024-01-01T24:00:00Z",
+            "2024-02-30T12:00:00Z",
+            "2024-01-01T12:00:00+24:00",
+        ] {
+            assert_eq!(parse_datetime(s), None, "{s}");
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! `drift`: checks that stored vectors still match what the embedder makes of
+//! their text today. Vectors are computed once, on add, so a change to the
+//! tokenizer or the hashing leaves old records embedded the old way; searches
+//! then quietly rank them wrong, with nothing failing.
+//!
+//! An evenly spaced sample of records is re-embedded and compared with the
+//! stored vectors by cosine similarity. Records below the threshold, or with a
+//! vector of the wrong length, are listed worst first and the command fails.
+
+use std::path::PathBuf;
+
+use crate::store::{load_store, VECTOR_DIM};
+use crate::{
+    config, cosine_sim, embed_text, local_only, parse_flag, parse_float, path_arg, resolve_path,
+    store_error,
+};
+
+const DEFAULT_SAMPLE: usize = 200;
+/// The embedder is deterministic, so an unchanged one reproduces vectors up to
+/// float rounding.
+const DEFAULT_THRESHOLD: f32 = 0.99;
+/// Drifted records listed; the count covers the rest.
+const SHOWN: usize = 10;
+
+pub fn cmd_drift(args: &[String]) -> Result<(), &'static str> {
+    let mut sample = DEFAULT_SAMPLE;
+    let mut threshold = DEFAULT_THRESHOLD;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sample" => {
+                i += 1;
+                sample = parse_flag("--sample", args.get(i))?;
+            }
+            "--threshold" => {
+                i += 1;
+                threshold = parse_float("--threshold", args.get(i))?;
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    if sample == 0 {
+        eprintln!("--sample must be at least 1");
+        return Err("invalid sample");
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+    let stride = (store.records.len() / sample).max(1);
+
+    let mut checked = 0;
+    let mut total = 0.0f32;
+    let mut drifted = Vec::new();
+    for rec in store.records.iter().step_by(stride).take(sample) {
+        // A vector of another length was made by another embedder altogether.
+        let sim = if rec.vector.len() == VECTOR_DIM {
+            cosine_sim(&rec.vector, &embed_text(&rec.text))
+        } else {
+            0.0
+        };
+        checked += 1;
+        total += sim;
+        if sim < threshold {
+            drifted.push((sim, rec));
+        }
+    }
+    if checked == 0 {
+        println!("ok\t{}: empty store", path.display());
+        return Ok(());
+    }
+    let mean = total / checked as f32;
+
+    drifted.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (sim, rec) in drifted.iter().take(SHOWN) {
+        println!(
+            "drift\t{sim:.3}\t{}\t{}\t{} dims",
+            rec.id,
+            rec.kind,
+            rec.vector.len()
+        );
+    }
+    if !drifted.is_empty() {
+        eprintln!(
+            "{}: {} of {checked} sampled records drifted below {threshold} (mean similarity {mean:.3}); export and re-import the store to re-embed it",
+            path.display(),
+            drifted.len()
+        );
+        return Err("drift found");
+    }
+    println!(
+        "ok\t{}: {checked} of {} records sampled, mean similarity {mean:.3}",
+        path.display(),
+        store.records.len()
+    );
+    Ok(())
+}
@@ -0,0 +1,104 @@
+//! `explore --plus <id>... --minus <id>...`: neighbours of a point made from
+//! stored vectors, for probing the memory space by hand. The point is the sum
+//! of the `--plus` vectors minus the `--minus` ones, normalized; `--plus a
+//! --minus b` asks for records like `a` but less like `b`.
+//!
+//! Like `neighbors`, results are ranked by cosine similarity alone, with no
+//! weight or recency, and the records the point was made from are left out.
+
+use std::path::PathBuf;
+
+use crate::store::{load_store, VECTOR_DIM};
+use crate::{
+    collect_vectors, config, index_cache, local_only, normalize, parse_flag, path_arg, resolve_path,
+    store_error, tsv_field, HNSW_EF_SEARCH,
+};
+
+pub fn cmd_explore(args: &[String]) -> Result<(), &'static str> {
+    let mut plus: Vec<u128> = Vec::new();
+    let mut minus: Vec<u128> = Vec::new();
+    let mut k: usize = 10;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--plus" => {
+                i += 1;
+                plus.push(parse_flag("--plus", args.get(i))?);
+            }
+            "--minus" => {
+                i += 1;
+                minus.push(parse_flag("--minus", args.get(i))?);
+            }
+            "-k" | "--k" => {
+                i += 1;
+                k = parse_flag("-k", args.get(i))?;
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if plus.is_empty() {
+        eprintln!("Usage: explore --plus <id>... [--minus <id>]... [-k <n>]");
+        return Err("missing id");
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let fingerprint = index_cache::Fingerprint::of(&path);
+    let records = load_store(&path)
+        .map_err(store_error(&path, "read failed"))?
+        .records;
+
+    let mut point = vec![0.0f32; VECTOR_DIM];
+    for (ids, sign) in [(&plus, 1.0), (&minus, -1.0)] {
+        for id in ids {
+            let Some(rec) = records.iter().find(|r| r.id == *id) else {
+                eprintln!("No record with id {id}");
+                return Err("unknown id");
+            };
+            for (p, v) in point.iter_mut().zip(&rec.vector) {
+                *p += sign * v;
+            }
+        }
+    }
+    if point.iter().all(|v| v.abs() < 1e-6) {
+        eprintln!("The vectors cancel out; there is no point to explore from");
+        return Err("zero vector");
+    }
+    normalize(&mut point);
+
+    let vecs = collect_vectors(&records);
+    let used = plus.len() + minus.len();
+    let want = (k + used).min(vecs.len());
+    let neighbours = index_cache::with_index(&path, fingerprint, &vecs, false, |hnsw| {
+        hnsw.search(&point, want, HNSW_EF_SEARCH.max(want))
+    });
+    let hits = neighbours
+        .into_iter()
+        .map(|n| (1.0 - n.distance, &records[n.d_id]))
+        .filter(|(_, rec)| !plus.contains(&rec.id) && !minus.contains(&rec.id))
+        .take(k);
+    for (sim, rec) in hits {
+        println!(
+            "{sim:.4}\t{}\t{}\t{}",
+            rec.kind,
+            rec.id,
+            tsv_field(&rec.text)
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,989 @@
+//! Bulk exchange with other tools.
+//!
+//! `export` / `import` move records as JSON Lines, one record per line with the
+//! same fields as the HTTP API plus an optional `vector`. DuckDB
+//! (`read_json_auto`) and pandas (`read_json(lines=True)`) read it directly.
+//! Both stream, gzip- or zstd-compressed by `.gz` / `.zst` name or
+//! `--compress`, so large transfers need no uncompressed copy on disk.
+//!
+//! `export --format qdrant-jsonl` and `chroma-jsonl` write one point per line
+//! in the shape those databases' upsert APIs take, vectors included, so a store
+//! can be loaded into a hosted vector database with a short script.
+//!
+//! `export --format markdown` writes a document for people rather than tools:
+//! YAML front-matter describing the export, then one section per kind or per
+//! day (`--group-by`), each record under a heading with its time and id. The
+//! store then doubles as a logbook, kept up to date by re-exporting.
+//!
+//! `export --format parquet` / `import --format parquet` need the `parquet`
+//! cargo feature; see `columnar.rs` for the columns.
+//!
+//! `export-vectors` writes the stored embeddings as a NumPy `.npy` file, one
+//! row per record in store order, so they load with `numpy.load` without
+//! re-embedding anything. With the `arrow` cargo feature, `--format arrow`
+//! writes an Arrow IPC file with the ids alongside instead.
+//!
+//! `import --format mem0|langchain|chatgpt` reads other tools' dumps; see
+//! `importers.rs`.
+//!
+//! Import input is untrusted: a line longer than `MAX_LINE_BYTES` or a
+//! `vector` with more than `MAX_VECTOR_DIM` entries is rejected, with its line
+//! number, as soon as the limit is passed rather than after buffering
+//! whatever the file holds.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::bufread::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::{SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[cfg(feature = "parquet")]
+use crate::columnar;
+use crate::filter::RecordFilter;
+use crate::importers;
+use crate::store::{
+    load_store, sibling_path, stream_records, update_store, Record, VECTOR_DIM,
+};
+use crate::{
+    config, dates, embed_text, local_only, now_secs, parse_since, path_arg, resolve_path,
+    store_error,
+};
+
+/// A Qdrant point: `PUT /collections/<name>/points` takes a list of these.
+#[derive(Serialize)]
+struct QdrantPoint {
+    /// Qdrant ids are u64 or UUIDs; millisecond ids fit in a u64.
+    id: u64,
+    vector: Vec<f32>,
+    payload: QdrantPayload,
+}
+
+#[derive(Serialize)]
+struct QdrantPayload {
+    kind: String,
+    ts: i64,
+    weight: f32,
+    text: String,
+    tags: Vec<String>,
+    attrs: BTreeMap<String, String>,
+}
+
+/// The columns of Chroma's `collection.add(ids=, embeddings=, documents=, metadatas=)`,
+/// one record per line. Chroma metadata values must be scalars, so tags are
+/// joined with commas and attrs become `attr_<key>` entries.
+#[derive(Serialize)]
+struct ChromaRecord {
+    id: String,
+    embedding: Vec<f32>,
+    document: String,
+    metadata: BTreeMap<String, serde_json::Value>,
+}
+
+/// One line of a JSON Lines export.
+#[derive(Serialize, Deserialize)]
+struct LineRecord {
+    id: u128,
+    ts: i64,
+    kind: String,
+    weight: f32,
+    text: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    attrs: BTreeMap<String, String>,
+    /// Left out unless exported with `--with-vectors`; re-embedded on import
+    /// when missing or of another dimension.
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "bounded_vector"
+    )]
+    vector: Vec<f32>,
+}
+
+/// Longest import line accepted, vector and text included.
+const MAX_LINE_BYTES: usize = 64 << 20;
+/// Most `vector` entries accepted; anything up to it that is not `VECTOR_DIM`
+/// long is re-embedded.
+const MAX_VECTOR_DIM: usize = 8192;
+
+pub fn cmd_export(args: &[String]) -> Result<(), &'static str> {
+    let mut format = "jsonl".to_string();
+    let mut out: Option<PathBuf> = None;
+    let mut with_vectors = false;
+    let mut compress: Option<Codec> = None;
+    let mut group_by: Option<String> = None;
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--format" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    format = v.clone();
+                }
+            }
+            "--out" => {
+                i += 1;
+                out = args.get(i).map(|v| path_arg(v));
+            }
+            "--with-vectors" => with_vectors = true,
+            "--group-by" => {
+                i += 1;
+                group_by = args.get(i).cloned();
+            }
+            "--compress" => {
+                i += 1;
+                match args.get(i).and_then(|v| Codec::parse(v)) {
+                    Some(c) => compress = Some(c),
+                    None => {
+                        eprintln!("--compress expects gz, zst or none");
+                        return Err("invalid argument");
+                    }
+                }
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let formats = ["jsonl", "qdrant-jsonl", "chroma-jsonl", "markdown", "parquet"];
+    check_format(&format, &formats)?;
+    if format == "parquet" && compress.is_some() {
+        eprintln!("--compress does not apply to --format parquet (its row groups are zstd-compressed)");
+        return Err("invalid argument");
+    }
+    let group_by = match (format.as_str(), group_by.as_deref()) {
+        ("markdown", None | Some("kind")) => GroupBy::Kind,
+        ("markdown", Some("day")) => GroupBy::Day,
+        (_, None) => GroupBy::Kind,
+        (_, Some(other)) => {
+            eprintln!("--group-by {other:?} needs --format markdown and kind or day");
+            return Err("invalid argument");
+        }
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    // Records are written as they are read, so exports need no memory for the
+    // store; a checksum mismatch fails the export after the last record.
+    let records = stream_records(&path).map_err(store_error(&path, "read failed"))?;
+
+    let target = out.clone().unwrap_or_else(|| PathBuf::from("-"));
+    let codec = compress.unwrap_or_else(|| Codec::for_path(out.as_deref()));
+    // A file is written next to `--out` and renamed over it once complete, so
+    // a failed export never leaves a truncated file behind.
+    let file = out.as_deref().filter(|p| *p != Path::new("-"));
+    let tmp = file.map(|p| sibling_path(p, ".tmp"));
+    let export = || -> Result<usize, &'static str> {
+        #[cfg(feature = "parquet")]
+        if format == "parquet" {
+            let w: Box<dyn Write + Send> = match tmp.as_deref() {
+                Some(p) => Box::new(BufWriter::new(
+                    File::create(p).map_err(store_error(&target, "write failed"))?,
+                )),
+                None => Box::new(BufWriter::new(io::stdout())),
+            };
+            let mut w = columnar::ParquetWriter::new(w, with_vectors)
+                .map_err(store_error(&target, "write failed"))?;
+            let mut n = 0;
+            for rec in records {
+                let rec = rec.map_err(store_error(&path, "read failed"))?;
+                if filter.matches(&rec) {
+                    w.push(rec).map_err(store_error(&target, "write failed"))?;
+                    n += 1;
+                }
+            }
+            w.finish().map_err(store_error(&target, "write failed"))?;
+            return Ok(n);
+        }
+        let mut w =
+            open_output(tmp.as_deref(), codec).map_err(store_error(&target, "write failed"))?;
+        if format == "markdown" {
+            // Sections need every record first; only the matching ones are kept, without vectors.
+            let mut kept = Vec::new();
+            for rec in records {
+                let mut rec = rec.map_err(store_error(&path, "read failed"))?;
+                if filter.matches(&rec) {
+                    rec.vector = Vec::new();
+                    kept.push(rec);
+                }
+            }
+            let n = kept.len();
+            write_markdown(&mut w, &path, kept, group_by)
+                .map_err(store_error(&target, "write failed"))?;
+            w.finish().map_err(store_error(&target, "write failed"))?;
+            return Ok(n);
+        }
+        let mut n = 0;
+        for rec in records {
+            let rec = rec.map_err(store_error(&path, "read failed"))?;
+            if !filter.matches(&rec) {
+                continue;
+            }
+            let json = match format.as_str() {
+                "qdrant-jsonl" => serde_json::to_string(&qdrant_point(rec)?),
+                "chroma-jsonl" => serde_json::to_string(&chroma_record(rec)),
+                _ => serde_json::to_string(&line_record(rec, with_vectors)),
+            };
+            let json = json.map_err(|_| "encode failed")?;
+            writeln!(w, "{json}").map_err(store_error(&target, "write failed"))?;
+            n += 1;
+        }
+        w.finish().map_err(store_error(&target, "write failed"))?;
+        Ok(n)
+    };
+    let exported = export();
+    if let (Some(file), Some(tmp)) = (file, &tmp) {
+        let renamed = match exported {
+            Ok(_) => fs::rename(tmp, file),
+            Err(_) => Ok(()),
+        };
+        if exported.is_err() || renamed.is_err() {
+            let _ = fs::remove_file(tmp);
+        }
+        renamed.map_err(store_error(&target, "write failed"))?;
+    }
+    eprintln!("Exported {} records", exported?);
+    Ok(())
+}
+
+#[derive(Clone, Copy)]
+enum GroupBy {
+    Kind,
+    Day,
+}
+
+/// The Markdown export: front-matter, then a `##` section per group (kinds
+/// alphabetically, days oldest first) with a `###` heading per record, oldest
+/// first, and its tags, weight and attributes in a line under the text.
+fn write_markdown(
+    w: &mut impl Write,
+    path: &Path,
+    mut records: Vec<Record>,
+    group_by: GroupBy,
+) -> io::Result<()> {
+    records.sort_by_key(|r| (r.ts, r.id));
+    let group_of = |rec: &Record| match group_by {
+        GroupBy::Kind => rec.kind.clone(),
+        GroupBy::Day => dates::format_date(rec.ts),
+    };
+    let mut groups: BTreeMap<String, Vec<&Record>> = BTreeMap::new();
+    for rec in &records {
+        groups.entry(group_of(rec)).or_default().push(rec);
+    }
+
+    // JSON strings are valid YAML, and quote whatever the path holds.
+    let quoted = |s: &str| serde_json::to_string(s).unwrap_or_default();
+    writeln!(w, "---")?;
+    writeln!(w, "title: Memory log")?;
+    writeln!(w, "store: {}", quoted(&path.display().to_string()))?;
+    writeln!(w, "exported_at: {}", quoted(&stamp(now_secs())))?;
+    writeln!(w, "records: {}", records.len())?;
+    let group_name = match group_by {
+        GroupBy::Kind => "kind",
+        GroupBy::Day => "day",
+    };
+    writeln!(w, "group_by: {group_name}")?;
+    if let (Some(first), Some(last)) = (records.first(), records.last()) {
+        writeln!(w, "from: {}", dates::format_date(first.ts))?;
+        writeln!(w, "to: {}", dates::format_date(last.ts))?;
+    }
+    writeln!(w, "---")?;
+
+    for (group, recs) in &groups {
+        writeln!(w)?;
+        writeln!(w, "## {group} ({})", recs.len())?;
+        for rec in recs {
+            // The heading says what the section does not: the day under a kind, the kind under a day.
+            let label = match group_by {
+                GroupBy::Kind => stamp(rec.ts),
+                GroupBy::Day => format!("{} {}", dates::format_time(rec.ts), rec.kind),
+            };
+            writeln!(w)?;
+            writeln!(w, "### {label} `{}`", rec.id)?;
+            writeln!(w)?;
+            writeln!(w, "{}", rec.text.trim_end())?;
+            let mut meta = vec![format!("weight {}", rec.weight)];
+            if !rec.tags.is_empty() {
+                meta.push(format!("tags: {}", rec.tags.join(", ")));
+            }
+            for (key, value) in &rec.attrs {
+                meta.push(format!("{key}: {value}"));
+            }
+            writeln!(w)?;
+            writeln!(w, "*{}*", meta.join(" · "))?;
+        }
+    }
+    Ok(())
+}
+
+/// `YYYY-MM-DD HH:MM`, UTC.
+fn stamp(ts: i64) -> String {
+    format!("{} {}", dates::format_date(ts), dates::format_time(ts))
+}
+
+/// Adds every record of a JSON Lines file (or stdin for `-`). Nothing is written
+/// unless the whole file parses. Ids already in the store move to the next free
+/// id, like concurrent adds.
+pub fn cmd_import(args: &[String]) -> Result<(), &'static str> {
+    let mut format = "jsonl".to_string();
+    let mut input: Option<PathBuf> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--format" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    format = v.clone();
+                }
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => input = Some(PathBuf::from(other)),
+        }
+        i += 1;
+    }
+
+    check_format(&format, &["jsonl", "mem0", "langchain", "chatgpt", "parquet"])?;
+    let Some(input) = input else {
+        eprintln!(
+            "Usage: import <file | -> [--format jsonl|mem0|langchain|chatgpt|parquet] [--path <file>]"
+        );
+        return Err("missing input");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    #[cfg(feature = "parquet")]
+    if format == "parquet" {
+        // The footer is at the end, so Parquet needs a seekable file rather than a pipe.
+        if input == Path::new("-") {
+            eprintln!("--format parquet reads a file, not stdin");
+            return Err("invalid argument");
+        }
+        let file = File::open(&input).map_err(store_error(&input, "read failed"))?;
+        let records = columnar::read_parquet(file).map_err(|e| {
+            eprintln!("{}: {e}", input.display());
+            "invalid input"
+        })?;
+        let (added, moved) =
+            add_records(&path, records).map_err(store_error(&path, "write failed"))?;
+        eprintln!("Imported {added} records ({moved} given new ids)");
+        return Ok(());
+    }
+
+    let mut reader = open_input(&input).map_err(store_error(&input, "read failed"))?;
+    if importers::FORMATS.contains(&format.as_str()) {
+        let mut json = String::new();
+        reader
+            .read_to_string(&mut json)
+            .map_err(store_error(&input, "read failed"))?;
+        let records = importers::convert(&format, &json).map_err(|e| {
+            eprintln!("{}: {e}", input.display());
+            "invalid input"
+        })?;
+        let (added, moved) =
+            add_records(&path, records).map_err(store_error(&path, "write failed"))?;
+        eprintln!("Imported {added} records from {format} ({moved} given new ids)");
+        return Ok(());
+    }
+    let mut records = Vec::new();
+    let mut n = 0;
+    loop {
+        n += 1;
+        let line = read_line_bounded(&mut reader, MAX_LINE_BYTES).map_err(|e| {
+            eprintln!("{}:{n}: {e}", input.display());
+            "read failed"
+        })?;
+        let Some(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let rec = parse_record_line(&line).map_err(|e| {
+            eprintln!("{}:{n}: {e}", input.display());
+            "invalid record"
+        })?;
+        records.push(rec);
+    }
+
+    let (added, moved) = add_records(&path, records).map_err(store_error(&path, "write failed"))?;
+    eprintln!("Imported {added} records ({moved} given new ids)");
+    Ok(())
+}
+
+/// Appends `records`, moving ids that are taken to the next free one. Returns
+/// how many were added and how many were given new ids. `supersedes` and
+/// `chunk_of` links between the imported records follow the ids they name.
+pub fn add_records(path: &Path, mut records: Vec<Record>) -> io::Result<(usize, usize)> {
+    update_store(path, |store| {
+        let mut taken: HashSet<u128> = store.records.iter().map(|r| r.id).collect();
+        let mut first = HashSet::new();
+        let mut moved_to = HashMap::new();
+        let mut moved = 0;
+        for rec in records.iter_mut() {
+            let old = rec.id;
+            while taken.contains(&rec.id) {
+                rec.id += 1;
+            }
+            if rec.id != old {
+                moved += 1;
+            }
+            // Links name the first record with an id, not a later duplicate.
+            if first.insert(old) && rec.id != old {
+                moved_to.insert(old, rec.id);
+            }
+            taken.insert(rec.id);
+        }
+        if !moved_to.is_empty() {
+            for rec in records.iter_mut() {
+                relink(rec, &moved_to);
+            }
+        }
+        let added = records.len();
+        store.records.extend(records);
+        Ok((added, moved))
+    })
+}
+
+/// Points `rec`'s `supersedes` and `chunk_of` at the new ids in `moved_to`.
+fn relink(rec: &mut Record, moved_to: &HashMap<u128, u128>) {
+    let new_id = |id: &str| match id.trim().parse::<u128>() {
+        Ok(old) => moved_to.get(&old).map_or(id.to_string(), u128::to_string),
+        Err(_) => id.to_string(),
+    };
+    if let Some(ids) = rec.attrs.get_mut("supersedes") {
+        *ids = ids.split(',').map(new_id).collect::<Vec<_>>().join(",");
+    }
+    if let Some(head) = rec.attrs.get_mut("chunk_of") {
+        *head = new_id(head);
+    }
+}
+
+/// One line of a JSON Lines export as a record, or what is wrong with it.
+pub fn parse_record_line(line: &str) -> Result<Record, String> {
+    let rec: LineRecord = serde_json::from_str(line).map_err(|e| e.to_string())?;
+    if !rec.weight.is_finite() {
+        return Err("weight must be a finite number".to_string());
+    }
+    Ok(into_record(rec))
+}
+
+/// The next line of `reader` without its line ending, or `None` at the end.
+/// A line over `max` bytes is an error once `max` bytes of it are read.
+fn read_line_bounded(reader: &mut impl BufRead, max: usize) -> io::Result<Option<String>> {
+    let mut buf = Vec::new();
+    reader.take(max as u64 + 1).read_until(b'\n', &mut buf)?;
+    if buf.is_empty() {
+        return Ok(None);
+    }
+    if buf.len() > max && buf.last() != Some(&b'\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("line is longer than {} MiB", max >> 20),
+        ));
+    }
+    while matches!(buf.last(), Some(b'\n' | b'\r')) {
+        buf.pop();
+    }
+    let line = String::from_utf8(buf)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.utf8_error()))?;
+    Ok(Some(line))
+}
+
+/// A `vector` array, failing as soon as it runs past `MAX_VECTOR_DIM`
+/// entries rather than after collecting all of them.
+fn bounded_vector<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<f32>, D::Error> {
+    struct Bounded;
+
+    impl<'de> Visitor<'de> for Bounded {
+        type Value = Vec<f32>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "an array of at most {MAX_VECTOR_DIM} numbers")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Vec<f32>, A::Error> {
+            let mut vector = Vec::with_capacity(VECTOR_DIM);
+            while let Some(v) = seq.next_element::<f32>()? {
+                if vector.len() == MAX_VECTOR_DIM {
+                    return Err(serde::de::Error::custom(format!(
+                        "vector has more than {MAX_VECTOR_DIM} dimensions"
+                    )));
+                }
+                vector.push(v);
+            }
+            Ok(vector)
+        }
+    }
+
+    deserializer.deserialize_seq(Bounded)
+}
+
+fn line_record(rec: Record, with_vectors: bool) -> LineRecord {
+    LineRecord {
+        id: rec.id,
+        ts: rec.ts,
+        kind: rec.kind,
+        weight: rec.weight,
+        text: rec.text,
+        tags: rec.tags,
+        attrs: rec.attrs,
+        vector: if with_vectors { rec.vector } else { Vec::new() },
+    }
+}
+
+fn into_record(rec: LineRecord) -> Record {
+    let vector = if rec.vector.len() == VECTOR_DIM {
+        rec.vector
+    } else {
+        embed_text(&rec.text)
+    };
+    Record {
+        id: rec.id,
+        ts: rec.ts,
+        kind: rec.kind,
+        weight: rec.weight,
+        text: rec.text,
+        vector,
+        tags: rec.tags,
+        attrs: rec.attrs,
+    }
+}
+
+fn qdrant_point(rec: Record) -> Result<QdrantPoint, &'static str> {
+    let Ok(id) = u64::try_from(rec.id) else {
+        eprintln!("Record id {} does not fit a Qdrant id (u64)", rec.id);
+        return Err("id out of range");
+    };
+    Ok(QdrantPoint {
+        id,
+        vector: rec.vector,
+        payload: QdrantPayload {
+            kind: rec.kind,
+            ts: rec.ts,
+            weight: rec.weight,
+            text: rec.text,
+            tags: rec.tags,
+            attrs: rec.attrs,
+        },
+    })
+}
+
+fn chroma_record(rec: Record) -> ChromaRecord {
+    let mut metadata = BTreeMap::from([
+        ("kind".to_string(), rec.kind.into()),
+        ("ts".to_string(), rec.ts.into()),
+        ("weight".to_string(), rec.weight.into()),
+        ("tags".to_string(), rec.tags.join(",").into()),
+    ]);
+    for (key, value) in rec.attrs {
+        metadata.insert(format!("attr_{key}"), value.into());
+    }
+    ChromaRecord {
+        id: rec.id.to_string(),
+        embedding: rec.vector,
+        document: rec.text,
+        metadata,
+    }
+}
+
+fn check_format(format: &str, supported: &[&str]) -> Result<(), &'static str> {
+    match format {
+        #[cfg(not(feature = "parquet"))]
+        "parquet" => {
+            eprintln!(
+                "--format parquet needs memstore built with `--features parquet`; or use jsonl and \
+                 COPY (SELECT * FROM read_json_auto('memories.jsonl')) TO 'memories.parquet' in DuckDB"
+            );
+            Err("unsupported format")
+        }
+        f if supported.contains(&f) => Ok(()),
+        other => {
+            eprintln!("Unknown --format {other:?} (expected {})", supported.join(", "));
+            Err("invalid argument")
+        }
+    }
+}
+
+/// Stream compression for `export` / `import`.
+#[derive(Clone, Copy)]
+enum Codec {
+    Plain,
+    Gzip,
+    Zstd,
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+impl Codec {
+    /// From `--compress`.
+    fn parse(s: &str) -> Option<Codec> {
+        match s {
+            "none" => Some(Codec::Plain),
+            "gz" | "gzip" => Some(Codec::Gzip),
+            "zst" | "zstd" => Some(Codec::Zstd),
+            _ => None,
+        }
+    }
+
+    /// From the file extension; stdout and other names are plain.
+    fn for_path(path: Option<&Path>) -> Codec {
+        match path.and_then(Path::extension).and_then(|e| e.to_str()) {
+            Some("gz") => Codec::Gzip,
+            Some("zst") => Codec::Zstd,
+            _ => Codec::Plain,
+        }
+    }
+}
+
+/// An export destination. Compressed streams have a trailer, so call `finish`
+/// rather than relying on drop.
+enum Output {
+    Plain(Box<dyn Write>),
+    Gzip(GzEncoder<Box<dyn Write>>),
+    Zstd(zstd::Encoder<'static, Box<dyn Write>>),
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Plain(w) => w.write(buf),
+            Output::Gzip(w) => w.write(buf),
+            Output::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Plain(w) => w.flush(),
+            Output::Gzip(w) => w.flush(),
+            Output::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+impl Output {
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Output::Plain(mut w) => w.flush(),
+            Output::Gzip(w) => w.finish()?.flush(),
+            Output::Zstd(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+/// `path`, or stdout for `None` and `-`, compressed with `codec`.
+fn open_output(path: Option<&Path>, codec: Codec) -> io::Result<Output> {
+    let w: Box<dyn Write> = match path {
+        Some(p) if p != Path::new("-") => Box::new(BufWriter::new(File::create(p)?)),
+        _ => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    Ok(match codec {
+        Codec::Plain => Output::Plain(w),
+        Codec::Gzip => Output::Gzip(GzEncoder::new(w, Compression::default())),
+        // Level 0 is zstd's default (3).
+        Codec::Zstd => Output::Zstd(zstd::Encoder::new(w, 0)?),
+    })
+}
+
+/// `path`, or stdin for `-`. Gzip and zstd input is recognised by its magic
+/// bytes rather than the name, so compressed pipes work too.
+fn open_input(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut r: Box<dyn BufRead> = if path == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(path)?))
+    };
+    let head = r.fill_buf()?;
+    let (is_gzip, is_zstd) = (head.starts_with(&GZIP_MAGIC), head.starts_with(&ZSTD_MAGIC));
+    if is_gzip {
+        // Multi-member, so `cat a.gz b.gz` and pigz output read in full.
+        return Ok(Box::new(BufReader::new(MultiGzDecoder::new(r))));
+    }
+    if is_zstd {
+        return Ok(Box::new(BufReader::new(zstd::Decoder::with_buffer(r)?)));
+    }
+    Ok(r)
+}
+
+pub fn cmd_export_vectors(args: &[String]) -> Result<(), &'static str> {
+    let mut format = "npy".to_string();
+    let mut out: Option<PathBuf> = None;
+    let mut ids_out: Option<PathBuf> = None;
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--format" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    format = v.clone();
+                }
+            }
+            "--out" => {
+                i += 1;
+                out = args.get(i).map(|v| path_arg(v));
+            }
+            "--with-ids" => {
+                i += 1;
+                ids_out = args.get(i).map(|v| path_arg(v));
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    match format.as_str() {
+        "npy" => {}
+        #[cfg(feature = "arrow")]
+        "arrow" => {}
+        #[cfg(not(feature = "arrow"))]
+        "arrow" => {
+            eprintln!(
+                "--format arrow needs memstore built with `--features arrow`; \
+                 or use npy and pyarrow.array(numpy.load(...))"
+            );
+            return Err("unsupported format");
+        }
+        other => {
+            eprintln!("Unknown --format {other:?} (expected npy or arrow)");
+            return Err("invalid argument");
+        }
+    }
+    let Some(out) = out else {
+        eprintln!("Missing --out <file>");
+        return Err("missing out");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+    let records: Vec<&Record> = store.records.iter().filter(|r| filter.matches(r)).collect();
+
+    let written = match format.as_str() {
+        #[cfg(feature = "arrow")]
+        "arrow" => write_arrow(&out, &records),
+        _ => write_npy(&out, &records),
+    };
+    written.map_err(store_error(&out, "write failed"))?;
+    if let Some(ids_out) = ids_out {
+        // Row i of the matrix is element i; serde_json writes u128 ids as plain numbers.
+        let ids: Vec<u128> = records.iter().map(|r| r.id).collect();
+        let json = serde_json::to_vec(&ids).map_err(|_| "encode failed")?;
+        std::fs::write(&ids_out, json).map_err(store_error(&ids_out, "write failed"))?;
+    }
+    eprintln!("Exported {} vectors of dimension {VECTOR_DIM}", records.len());
+    Ok(())
+}
+
+/// NPY format 1.0: magic, version, a little-endian u16 header length, and a
+/// Python dict literal padded with spaces so the data starts on a 64-byte
+/// boundary. The data is the `(n, VECTOR_DIM)` matrix of little-endian f32s.
+fn write_npy(out: &Path, records: &[&Record]) -> io::Result<()> {
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': ({}, {VECTOR_DIM}), }}",
+        records.len()
+    );
+    // 6 magic bytes, 2 version bytes, 2 length bytes, then the header ending in '\n'.
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat(unpadded.next_multiple_of(64) - unpadded));
+    header.push('\n');
+
+    let mut w = BufWriter::new(File::create(out)?);
+    w.write_all(b"\x93NUMPY\x01\x00")?;
+    w.write_all(&(header.len() as u16).to_le_bytes())?;
+    w.write_all(header.as_bytes())?;
+    for rec in records {
+        // Keep every row VECTOR_DIM wide even if a record vector is short.
+        for j in 0..VECTOR_DIM {
+            let v = rec.vector.get(j).copied().unwrap_or(0.0);
+            w.write_all(&v.to_le_bytes())?;
+        }
+    }
+    w.flush()
+}
+
+/// Arrow IPC file format: an `id` column of decimal strings (ids are 128-bit,
+/// wider than any Arrow integer) and a `vector` column of `VECTOR_DIM`-wide
+/// f32 lists, one row per record. `pyarrow.ipc.open_file` and Polars
+/// `read_ipc` load it as is.
+#[cfg(feature = "arrow")]
+fn write_arrow(out: &Path, records: &[&Record]) -> io::Result<()> {
+    use std::sync::Arc;
+
+    use arrow_array::builder::{FixedSizeListBuilder, Float32Builder};
+    use arrow_array::{ArrayRef, RecordBatch, StringArray};
+    use arrow_ipc::writer::FileWriter;
+
+    let ids = StringArray::from_iter_values(records.iter().map(|r| r.id.to_string()));
+    let mut vectors = FixedSizeListBuilder::new(Float32Builder::new(), VECTOR_DIM as i32);
+    for rec in records {
+        for j in 0..VECTOR_DIM {
+            let v = rec.vector.get(j).copied().unwrap_or(0.0);
+            vectors.values().append_value(v);
+        }
+        vectors.append(true);
+    }
+    let columns: [(&str, ArrayRef); 2] =
+        [("id", Arc::new(ids)), ("vector", Arc::new(vectors.finish()))];
+    let batch = RecordBatch::try_from_iter(columns).map_err(io::Error::other)?;
+    let mut w = FileWriter::try_new_buffered(File::create(out)?, &batch.schema())
+        .map_err(io::Error::other)?;
+    w.write(&batch).map_err(io::Error::other)?;
+    w.into_inner().map_err(io::Error::other)?.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::store::tests::any_record;
+
+    fn bytes(records: &[Record]) -> Vec<u8> {
+        bincode::serialize(records).unwrap()
+    }
+
+    #[test]
+    fn moved_ids_carry_their_links() {
+        let dir =
+            std::env::temp_dir().join(format!("memstore-import-links-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("memories.hnsw");
+        let record = |id: u128, attrs: &[(&str, &str)]| Record {
+            id,
+            ts: 1_700_000_000,
+            kind: "fact".to_string(),
+            weight: 1.0,
+            text: format!("record {id}"),
+            vector: embed_text("record"),
+            tags: Vec::new(),
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        add_records(&path, vec![record(10, &[]), record(11, &[])]).unwrap();
+
+        let batch = vec![
+            record(10, &[]),
+            record(11, &[("chunk_of", "10")]),
+            record(20, &[("supersedes", "10,11,99")]),
+        ];
+        assert_eq!(add_records(&path, batch).unwrap(), (3, 2));
+
+        let store = load_store(&path).unwrap();
+        let imported = &store.records[2..];
+        let ids: Vec<u128> = imported.iter().map(|r| r.id).collect();
+        assert_eq!(ids, [12, 13, 20]);
+        assert_eq!(imported[1].attrs["chunk_of"], "12");
+        assert_eq!(imported[2].attrs["supersedes"], "12,13,99");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        /// `export --with-vectors` lines read back by `import` as the same records.
+        #[test]
+        fn jsonl_round_trips(records in prop::collection::vec(any_record(), 0..6)) {
+            let mut out = Vec::new();
+            for rec in records.clone() {
+                writeln!(out, "{}", serde_json::to_string(&line_record(rec, true)).unwrap()).unwrap();
+            }
+            let mut reader = io::Cursor::new(out);
+            let mut imported = Vec::new();
+            while let Some(line) = read_line_bounded(&mut reader, MAX_LINE_BYTES).unwrap() {
+                imported.push(parse_record_line(&line).unwrap());
+            }
+            prop_assert_eq!(bytes(&imported), bytes(&records));
+        }
+
+        /// Without vectors, import re-embeds the text and keeps everything else.
+        #[test]
+        fn jsonl_without_vectors_reembeds(rec in any_record()) {
+            let line = serde_json::to_string(&line_record(rec.clone(), false)).unwrap();
+            let imported = parse_record_line(&line).unwrap();
+            prop_assert_eq!(&imported.vector, &embed_text(&rec.text));
+            let blank = |rec: &Record| Record { vector: Vec::new(), ..rec.clone() };
+            prop_assert_eq!(bytes(&[blank(&imported)]), bytes(&[blank(&rec)]));
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! C API for embedding the store without a process boundary; see
+//! `include/memstore.h`. Built into the `cdylib` next to the binary.
+//!
+//! Strings are UTF-8 and NUL-terminated. Strings returned by the library are
+//! owned by the caller and released with `memstore_free`. A panic never
+//! crosses into the caller: it is reported like any other error.
+
+use std::collections::BTreeMap;
+use std::ffi::{c_char, c_float, c_int, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::server::{handle_add, handle_search, AddRequest, SearchRequest};
+use crate::store::load_store;
+
+/// Opaque handle returned by `memstore_open`.
+pub struct MemStore {
+    path: PathBuf,
+}
+
+/// Reads a borrowed C string, `None` for null or invalid UTF-8.
+///
+/// # Safety
+///
+/// `s` must be null or point to a NUL-terminated string.
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Runs `f`, or returns `on_panic` if it panics; unwinding out of an
+/// `extern "C"` function aborts the caller's process.
+fn guard<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// Opens the store file at `path`, which is created on the first add. Returns
+/// null if the path is not valid UTF-8 or an existing file cannot be read.
+///
+/// # Safety
+///
+/// `path` must be null or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn memstore_open(path: *const c_char) -> *mut MemStore {
+    guard(ptr::null_mut(), || {
+        let Some(path) = str_arg(path) else {
+            return ptr::null_mut();
+        };
+        let path = PathBuf::from(path);
+        if load_store(&path).is_err() {
+            return ptr::null_mut();
+        }
+        Box::into_raw(Box::new(MemStore { path }))
+    })
+}
+
+/// Adds a record and writes its 128-bit id to `out_id` unless that is null,
+/// as two halves: `out_id[0]` the high 64 bits, `out_id[1]` the low ones.
+/// `kind` may be null for `summary`; a negative `weight` lets memstore
+/// estimate one. Returns 0 on success and -1 on error.
+///
+/// # Safety
+///
+/// `store` must come from `memstore_open`; `text` and `kind` must be null or
+/// NUL-terminated strings; `out_id` must be null or valid for writing two
+/// `u64`s.
+#[no_mangle]
+pub unsafe extern "C" fn memstore_add(
+    store: *mut MemStore,
+    text: *const c_char,
+    kind: *const c_char,
+    weight: c_float,
+    out_id: *mut u64,
+) -> c_int {
+    guard(-1, || {
+        let (Some(store), Some(text)) = (store.as_ref(), str_arg(text)) else {
+            return -1;
+        };
+        let req = AddRequest {
+            text: text.to_string(),
+            kind: str_arg(kind).unwrap_or("summary").to_string(),
+            weight,
+            tags: Vec::new(),
+            attrs: BTreeMap::new(),
+            auto_weight: weight < 0.0,
+            expected_generation: None,
+        };
+        match handle_add(&store.path, req) {
+            Ok(resp) => {
+                if !out_id.is_null() {
+                    *out_id = (resp.id >> 64) as u64;
+                    *out_id.add(1) = resp.id as u64;
+                }
+                0
+            }
+            Err(_) => -1,
+        }
+    })
+}
+
+/// Searches like `POST /stores/<name>/search` and returns the hits as the same
+/// JSON array, or null on error. `query` may use the query syntax.
+///
+/// # Safety
+///
+/// `store` must come from `memstore_open` and `query` must be null or a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn memstore_search_json(
+    store: *mut MemStore,
+    query: *const c_char,
+    limit: usize,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let (Some(store), Some(query)) = (store.as_ref(), str_arg(query)) else {
+            return ptr::null_mut();
+        };
+        let req = SearchRequest {
+            query: query.to_string(),
+            limit,
+            must: Vec::new(),
+            must_not: Vec::new(),
+            per_kind: None,
+            kind: None,
+            tags: Vec::new(),
+            since: None,
+            until: None,
+            as_of: None,
+            lang: None,
+            max_age: None,
+        };
+        handle_search(&store.path, req)
+            .ok()
+            .and_then(|hits| serde_json::to_string(&hits).ok())
+            .and_then(|json| CString::new(json).ok())
+            .map_or(ptr::null_mut(), CString::into_raw)
+    })
+}
+
+/// Frees a string returned by the library. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must be null or a string from this library that was not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn memstore_free(s: *mut c_char) {
+    guard((), || {
+        if !s.is_null() {
+            drop(CString::from_raw(s));
+        }
+    })
+}
+
+/// Releases a handle from `memstore_open`. Null is ignored.
+///
+/// # Safety
+///
+/// `store` must be null or a handle that was not closed yet.
+#[no_mangle]
+pub unsafe extern "C" fn memstore_close(store: *mut MemStore) {
+    guard((), || {
+        if !store.is_null() {
+            drop(Box::from_raw(store));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn add_returns_the_full_id_and_search_finds_it() {
+        let dir = env::temp_dir().join(format!("memstore-ffi-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = CString::new(dir.join("memories.hnsw").to_str().unwrap()).unwrap();
+        let text = CString::new("the deploy runs from the release branch").unwrap();
+        let query = CString::new("release branch deploy").unwrap();
+
+        unsafe {
+            let store = memstore_open(path.as_ptr());
+            assert!(!store.is_null());
+            let mut id = [u64::MAX; 2];
+            let added = memstore_add(store, text.as_ptr(), ptr::null(), 1.0, id.as_mut_ptr());
+            assert_eq!(added, 0);
+            let id = (id[0] as u128) << 64 | id[1] as u128;
+
+            let json = memstore_search_json(store, query.as_ptr(), 5);
+            assert!(!json.is_null());
+            let hits: Vec<crate::server::ApiHit> =
+                serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+            memstore_free(json);
+            assert_eq!(hits.len(), 1);
+            assert_eq!(hits[0].record.id, id);
+            assert_eq!(hits[0].record.kind, "summary");
+
+            assert_eq!(
+                memstore_add(store, ptr::null(), ptr::null(), 1.0, ptr::null_mut()),
+                -1
+            );
+            assert!(memstore_search_json(ptr::null_mut(), query.as_ptr(), 5).is_null());
+            memstore_close(store);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_panic_becomes_the_error_value() {
+        assert_eq!(guard(-1, || panic!("boom")), -1);
+        assert_eq!(guard(-1, || 0), 0);
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+
+use crate::{lang, Record};
+
+/// Record predicates shared by search and the listing/counting commands.
+/// Unset fields match everything; all tags must be present.
+#[derive(Clone, Debug, Default)]
+pub struct RecordFilter {
+    pub kind: Option<String>,
+    pub tags: Vec<String>,
+    /// Inclusive lower bound on `ts`.
+    pub since: Option<i64>,
+    /// Exclusive upper bound on `ts`.
+    pub until: Option<i64>,
+    /// Terms that must / must not appear in the text, compared token by token
+    /// and case-insensitively, so `k8s` does not match `k8sx`.
+    pub must: Vec<String>,
+    pub must_not: Vec<String>,
+    /// Only records that existed at this time: written before it (`ts`) and
+    /// added to the store before it. Ids are the millisecond they were added,
+    /// so this leaves out later imports and consolidated summaries, whose
+    /// `ts` is that of older records.
+    pub as_of: Option<i64>,
+    /// Language code, stored or guessed (see `lang.rs`).
+    pub lang: Option<String>,
+}
+
+impl RecordFilter {
+    pub fn matches(&self, rec: &Record) -> bool {
+        if let Some(kind) = &self.kind {
+            if &rec.kind != kind {
+                return false;
+            }
+        }
+        if let Some(since) = self.since {
+            if rec.ts < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if rec.ts >= until {
+                return false;
+            }
+        }
+        if let Some(as_of) = self.as_of {
+            let added = (rec.id / 1000).min(i64::MAX as u128) as i64;
+            if rec.ts >= as_of || added >= as_of {
+                return false;
+            }
+        }
+        if !self.tags.iter().all(|t| rec.tags.contains(t)) {
+            return false;
+        }
+        if let Some(wanted) = &self.lang {
+            if lang::of(rec) != Some(wanted.as_str()) {
+                return false;
+            }
+        }
+        if self.must.is_empty() && self.must_not.is_empty() {
+            return true;
+        }
+        // Terms are compared the way the record's language spells them.
+        let rec_lang = rec.attrs.get(lang::ATTR).map(String::as_str);
+        let tokens: HashSet<String> = lang::terms(&rec.text, rec_lang).into_iter().collect();
+        let contains = |term: &String| {
+            let term_tokens = lang::terms(term, rec_lang);
+            !term_tokens.is_empty() && term_tokens.iter().all(|t| tokens.contains(t))
+        };
+        self.must.iter().all(contains) && !self.must_not.iter().any(contains)
+    }
+
+    /// Narrows `self` by `other`: both filters must match afterwards.
+    /// When both name a kind or a language, `other` wins.
+    pub fn merge(&mut self, other: RecordFilter) {
+        if other.kind.is_some() {
+            self.kind = other.kind;
+        }
+        self.tags.extend(other.tags);
+        self.since = self.since.max(other.since);
+        self.until = match (self.until, other.until) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        self.must.extend(other.must);
+        self.must_not.extend(other.must_not);
+        self.as_of = match (self.as_of, other.as_of) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        if other.lang.is_some() {
+            self.lang = other.lang;
+        }
+    }
+}
+
+/// Parses `90s`, `30m`, `12h`, `7d` or `2w` into seconds.
+pub fn parse_duration(s: &str) -> Option<i64> {
+    let s = s.trim();
+    let unit = s.chars().last()?;
+    let value: i64 = s[..s.len() - unit.len_utf8()].parse().ok()?;
+    let scale = match unit {
+        's' => 1,
+        'm' => 60,
+        'h' => 3600,
+        'd' => 86400,
+        'w' => 7 * 86400,
+        _ => return None,
+    };
+    if value < 0 {
+        return None;
+    }
+    value.checked_mul(scale)
+}
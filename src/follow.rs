@@ -0,0 +1,107 @@
+//! `recent --follow`: like `tail -f` for a store. Prints the latest records
+//! oldest first, then keeps running and prints each record as it is added,
+//! until interrupted.
+//!
+//! The store is polled every `--interval` seconds rather than watched, so
+//! this works the same on every platform and against a remote store. A local
+//! store is only reloaded when its size or modification time changes; of a
+//! sharded store, only the newest shard is watched. A record is printed once,
+//! by id, so edits to it do not show up again.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use crate::store::{load_store, Record};
+use crate::{recent_records, remote, shards, tsv_field};
+
+/// Records a remote store is asked for per poll; adds beyond this many
+/// between two polls are missed.
+const REMOTE_WINDOW: usize = 1000;
+
+pub fn follow(path: &Path, limit: usize, interval: Duration) -> Result<(), &'static str> {
+    let read_error = |e: io::Error| {
+        eprintln!("{}: {e}", path.display());
+        "read failed"
+    };
+    // Stamp, then snapshot, then the records shown: an add in between is
+    // either shown now or changes the stamp, so none is missed or repeated.
+    let mut stamp = watched_stamp(path).map_err(read_error)?;
+    let mut seen: HashSet<u128> = snapshot(path)
+        .map_err(read_error)?
+        .iter()
+        .map(|r| r.id)
+        .collect();
+    let mut latest = match remote::store_url(path) {
+        Some(url) => remote::recent(url, limit).map_err(read_error)?,
+        None => recent_records(path, limit).map_err(read_error)?,
+    };
+    latest.reverse();
+    for rec in &latest {
+        print_record(rec);
+    }
+    seen.extend(latest.iter().map(|r| r.id));
+
+    loop {
+        thread::sleep(interval);
+        if remote::store_url(path).is_none() {
+            let now = watched_stamp(path).map_err(read_error)?;
+            if now == stamp {
+                continue;
+            }
+            stamp = now;
+        }
+        let records = snapshot(path).map_err(read_error)?;
+        let mut added: Vec<&Record> = records.iter().filter(|r| !seen.contains(&r.id)).collect();
+        added.sort_by_key(|r| (r.ts, r.id));
+        for rec in added {
+            print_record(rec);
+        }
+        seen = records.iter().map(|r| r.id).collect();
+    }
+}
+
+/// The records new ones are looked for in.
+fn snapshot(path: &Path) -> io::Result<Vec<Record>> {
+    if let Some(url) = remote::store_url(path) {
+        return remote::recent(url, REMOTE_WINDOW);
+    }
+    match watched_file(path)? {
+        Some(file) => Ok(load_store(&file)?.records),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The file adds land in: the store itself, or the newest shard.
+fn watched_file(path: &Path) -> io::Result<Option<PathBuf>> {
+    if shards::is_sharded(path) {
+        return shards::newest(path);
+    }
+    Ok(Some(path.to_path_buf()))
+}
+
+/// Size, modification time and name of the file adds land in; `None` while
+/// it does not exist yet.
+fn watched_stamp(path: &Path) -> io::Result<Option<(u64, SystemTime, PathBuf)>> {
+    let Some(file) = watched_file(path)? else {
+        return Ok(None);
+    };
+    match fs::metadata(&file) {
+        Ok(meta) => Ok(Some((meta.len(), meta.modified()?, file))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn print_record(rec: &Record) {
+    println!(
+        "{}\t{}\t{}\t{}",
+        rec.kind,
+        rec.id,
+        rec.ts,
+        tsv_field(&rec.text)
+    );
+}
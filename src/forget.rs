@@ -0,0 +1,172 @@
+//! `forget` (alias `delete`): removes records by id, optionally shredding the
+//! old copies of the file that still contain them. On a sharded store every
+//! shard is searched for the ids.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::store::{load_store, sibling_path, update_store, STORE_VERSION};
+use crate::{
+    archive, config, index_cache, ivf, path_arg, pq, remote, resolve_path, shards, store_error,
+};
+
+pub fn cmd_forget(args: &[String]) -> Result<(), &'static str> {
+    let mut ids: Vec<u128> = Vec::new();
+    let mut shred = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--id" => {
+                i += 1;
+                match args.get(i).and_then(|v| v.parse().ok()) {
+                    Some(id) => ids.push(id),
+                    None => {
+                        eprintln!("Invalid --id");
+                        return Err("invalid id");
+                    }
+                }
+            }
+            "--shred" => shred = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if ids.is_empty() {
+        eprintln!("Missing --id");
+        return Err("missing id");
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    if remote::store_url(&path).is_some() {
+        eprintln!("This command only works on local stores");
+        return Err("remote unsupported");
+    }
+    // A sharded store has no file of its own; the ids may be in any month.
+    let sharded = shards::is_sharded(&path);
+    let stores = if sharded {
+        shards::in_window(&path, None).map_err(store_error(&path, "read failed"))?
+    } else {
+        vec![path.clone()]
+    };
+
+    let mut removed = 0;
+    let mut old_files = Vec::new();
+    for store in &stores {
+        let (n, files) = remove_ids(store, &ids, shred).map_err(store_error(store, "write failed"))?;
+        removed += n;
+        old_files.push((store, files));
+    }
+    eprintln!("Forgot {removed} of {} records", ids.len());
+
+    if shred {
+        for (store, files) in old_files {
+            for file in files {
+                overwrite(file).map_err(store_error(store, "shred failed"))?;
+            }
+            shred_backups(store, &ids)?;
+            // The cached search indexes are built from the vectors of removed records.
+            let caches = index_cache::cache_files(store)
+                .into_iter()
+                .chain(ivf::cache_files(store))
+                .chain(pq::cache_files(store));
+            for cache in caches {
+                let shredded = OpenOptions::new()
+                    .write(true)
+                    .open(&cache)
+                    .and_then(overwrite)
+                    .and_then(|_| fs::remove_file(&cache));
+                shredded.map_err(store_error(&cache, "shred failed"))?;
+            }
+        }
+        // Backups made before the store was split into shards.
+        if sharded {
+            shred_backups(&path, &ids)?;
+        }
+    }
+    Ok(())
+}
+
+/// Removes `ids` from the store at `path` and its archive. Returns how many
+/// records went and, with `shred`, the replaced files, still open so their
+/// old bytes can be overwritten.
+fn remove_ids(path: &Path, ids: &[u128], shred: bool) -> io::Result<(usize, Vec<File>)> {
+    // The save renames a new file over the old one, which leaves the old bytes on
+    // disk; holding the old file open keeps its inode around to overwrite afterwards.
+    let archive = archive::archive_path(path);
+    update_store(path, |store| {
+        let old_store = if shred {
+            Some(OpenOptions::new().write(true).open(path)?)
+        } else {
+            None
+        };
+        let old_archive = if shred && archive.exists() {
+            Some(OpenOptions::new().write(true).open(&archive)?)
+        } else {
+            None
+        };
+        let before = store.records.len();
+        store.records.retain(|r| !ids.contains(&r.id));
+        let archived = archive::retain(path, |r| !ids.contains(&r.id))?;
+        // An archive without any of the ids was not rewritten; that file is the live one.
+        let old_archive = old_archive.filter(|_| archived > 0);
+        let old_files: Vec<File> = old_store.into_iter().chain(old_archive).collect();
+        Ok((before - store.records.len() + archived, old_files))
+    })
+}
+
+/// `upgrade` backups holding any of `ids` are overwritten and removed.
+fn shred_backups(path: &Path, ids: &[u128]) -> Result<(), &'static str> {
+    for version in 1..STORE_VERSION {
+        let backup = sibling_path(path, &format!(".v{version}.bak"));
+        if !backup.exists() {
+            continue;
+        }
+        let contains = match load_store(&backup) {
+            Ok(store) => store.records.iter().any(|r| ids.contains(&r.id)),
+            Err(e) => {
+                eprintln!("Warning: {} could not be read ({e}); it was left in place", backup.display());
+                continue;
+            }
+        };
+        if contains {
+            let file = OpenOptions::new()
+                .write(true)
+                .open(&backup)
+                .map_err(store_error(&backup, "shred failed"))?;
+            overwrite(file).map_err(store_error(&backup, "shred failed"))?;
+            fs::remove_file(&backup).map_err(store_error(&backup, "shred failed"))?;
+            eprintln!("Shredded {}", backup.display());
+        }
+    }
+    Ok(())
+}
+
+/// Overwrites the whole file with zeros and syncs it. Copy-on-write filesystems
+/// and SSD wear leveling may still keep older blocks; this is best effort.
+fn overwrite(mut file: File) -> io::Result<()> {
+    let len = file.metadata()?.len();
+    let zeros = [0u8; 64 * 1024];
+    file.seek(SeekFrom::Start(0))?;
+    let mut left = len;
+    while left > 0 {
+        let n = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        left -= n as u64;
+    }
+    file.sync_all()
+}
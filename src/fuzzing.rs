@@ -0,0 +1,43 @@
+//! Entry points for the targets in `fuzz/`, compiled only under `cargo fuzz`.
+//! Each feeds arbitrary bytes to a parser that reads files or lines from
+//! outside and ignores the outcome: an error is fine, a panic, hang or huge
+//! allocation is a bug.
+
+use std::env;
+use std::fs;
+use std::process;
+
+use crate::{dates, export, store};
+
+pub fn decode_store(data: &[u8]) {
+    let _ = store::decode_store(data);
+}
+
+/// `stream_records` reads from a file, so the input is written to one first.
+pub fn stream_records(data: &[u8]) {
+    let path = env::temp_dir().join(format!("memstore-fuzz-{}.mem", process::id()));
+    if fs::write(&path, data).is_err() {
+        return;
+    }
+    if let Ok(records) = store::stream_records(&path) {
+        for rec in records {
+            if rec.is_err() {
+                break;
+            }
+        }
+    }
+    let _ = fs::remove_file(&path);
+}
+
+pub fn parse_record_line(data: &[u8]) {
+    if let Ok(line) = std::str::from_utf8(data) {
+        let _ = export::parse_record_line(line);
+    }
+}
+
+/// Timestamps in files from other tools, e.g. mem0's `created_at`.
+pub fn parse_datetime(data: &[u8]) {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = dates::parse_datetime(s);
+    }
+}
@@ -0,0 +1,63 @@
+//! Initial weight for `add --auto-weight`, from cheap signals instead of a flat 1.0.
+
+use crate::store::Record;
+use crate::{cosine_sim, tokenize};
+
+const MIN_WEIGHT: f32 = 0.1;
+const MAX_WEIGHT: f32 = 3.0;
+
+/// Words that mark a note as worth surfacing, for any kind.
+const MARKERS: &[&str] = &[
+    "important", "critical", "must", "never", "always", "remember", "deadline",
+];
+
+/// Extra markers per kind.
+const KIND_MARKERS: &[(&str, &[&str])] = &[
+    ("decision", &["decided", "chose", "because", "instead", "tradeoff"]),
+    ("todo", &["urgent", "asap", "blocker", "due"]),
+    ("profile", &["prefers", "dislikes", "wants"]),
+];
+
+/// Starts at 1.0 and adjusts for:
+/// - length: very short notes carry little, detailed ones more;
+/// - novelty: the closest existing vector, so near-duplicates rank low;
+/// - markers: generic and kind-specific keywords, up to +0.6.
+///
+/// The result is clamped to `[0.1, 3.0]` and rounded to two decimals.
+pub fn estimate(text: &str, kind: &str, vector: &[f32], existing: &[Record]) -> f32 {
+    let tokens = tokenize(text);
+    let mut weight = 1.0f32;
+
+    weight += match tokens.len() {
+        0..=3 => -0.3,
+        4..=19 => 0.0,
+        20..=79 => 0.2,
+        _ => 0.4,
+    };
+
+    let closest = existing
+        .iter()
+        .map(|r| cosine_sim(vector, &r.vector))
+        .fold(0.0f32, f32::max);
+    if closest >= 0.95 {
+        weight *= 0.5;
+    } else if closest >= 0.8 {
+        weight -= 0.3;
+    } else if closest < 0.3 {
+        weight += 0.3;
+    }
+
+    let kind_markers = KIND_MARKERS
+        .iter()
+        .find(|(k, _)| *k == kind)
+        .map(|(_, m)| *m)
+        .unwrap_or(&[]);
+    let hits = MARKERS
+        .iter()
+        .chain(kind_markers)
+        .filter(|m| tokens.iter().any(|t| t == *m))
+        .count();
+    weight += (hits as f32 * 0.2).min(0.6);
+
+    (weight.clamp(MIN_WEIGHT, MAX_WEIGHT) * 100.0).round() / 100.0
+}
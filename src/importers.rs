@@ -0,0 +1,214 @@
+//! `import --format mem0|langchain|chatgpt`: maps other tools' memory dumps
+//! onto records. Their embeddings come from other models, so every record is
+//! re-embedded; the original id, if any, is kept in the `source_id` attribute.
+//!
+//! - `mem0`: the output of `Memory.get_all()`, either `{"results": [...]}` or a
+//!   bare list. `memory` becomes the text, `categories` become tags and
+//!   `user_id` a `user:<id>` tag. Kind `memory` unless `metadata.kind` says otherwise.
+//! - `langchain`: `InMemoryVectorStore.dump()` (`{"<id>": {"text", "metadata"}}`)
+//!   or a list of serialized documents (`page_content`, `metadata`). Kind
+//!   `document` unless `metadata.kind` says otherwise; string metadata becomes attrs.
+//! - `chatgpt`: `conversations.json` from a ChatGPT data export. Each user or
+//!   assistant message becomes a `chat` record tagged `chatgpt` and `role:<role>`,
+//!   with the conversation title in `conversation`.
+
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::dates::parse_datetime;
+use crate::store::Record;
+use crate::{embed_text, now_millis, now_secs};
+
+pub const FORMATS: [&str; 3] = ["mem0", "langchain", "chatgpt"];
+
+/// Records for the dump in `json`, with fresh ids in document order.
+pub fn convert(format: &str, json: &str) -> Result<Vec<Record>, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| format!("invalid JSON: {e}"))?;
+    let drafts = match format {
+        "mem0" => mem0(&value)?,
+        "langchain" => langchain(&value)?,
+        "chatgpt" => chatgpt(&value)?,
+        other => return Err(format!("unknown import format {other}")),
+    };
+    let base = now_millis();
+    Ok(drafts
+        .into_iter()
+        .zip(0..)
+        .map(|(d, i)| Record {
+            id: base + i,
+            ts: d.ts.unwrap_or_else(now_secs),
+            kind: d.kind,
+            weight: 1.0,
+            vector: embed_text(&d.text),
+            text: d.text,
+            tags: d.tags,
+            attrs: d.attrs,
+        })
+        .collect())
+}
+
+/// A record before it has an id and a vector.
+struct Draft {
+    text: String,
+    kind: String,
+    ts: Option<i64>,
+    tags: Vec<String>,
+    attrs: BTreeMap<String, String>,
+}
+
+fn mem0(value: &Value) -> Result<Vec<Draft>, String> {
+    let items = value
+        .get("results")
+        .unwrap_or(value)
+        .as_array()
+        .ok_or("expected a list of memories or {\"results\": [...]}")?;
+    let mut drafts = Vec::new();
+    for item in items {
+        let Some(text) = str_field(item, "memory").filter(|t| !t.trim().is_empty()) else {
+            continue;
+        };
+        let metadata = item.get("metadata");
+        let mut tags: Vec<String> = item
+            .get("categories")
+            .and_then(Value::as_array)
+            .map(|c| c.iter().filter_map(Value::as_str).map(str::to_string).collect())
+            .unwrap_or_default();
+        if let Some(user) = str_field(item, "user_id") {
+            tags.push(format!("user:{user}"));
+        }
+        let mut attrs = BTreeMap::new();
+        if let Some(id) = id_field(item) {
+            attrs.insert("source_id".to_string(), id);
+        }
+        drafts.push(Draft {
+            text: text.to_string(),
+            kind: metadata
+                .and_then(|m| str_field(m, "kind"))
+                .unwrap_or("memory")
+                .to_string(),
+            ts: str_field(item, "created_at").and_then(parse_datetime),
+            tags,
+            attrs,
+        });
+    }
+    Ok(drafts)
+}
+
+fn langchain(value: &Value) -> Result<Vec<Draft>, String> {
+    // A dump is keyed by id; a document list carries `page_content`.
+    let entries: Vec<&Value> = match value {
+        Value::Object(map) => map.values().collect(),
+        Value::Array(list) => list.iter().collect(),
+        _ => return Err("expected a vector store dump or a list of documents".to_string()),
+    };
+    let mut drafts = Vec::new();
+    for entry in entries {
+        // Serialized documents nest their fields under `kwargs`.
+        let doc = entry.get("kwargs").unwrap_or(entry);
+        let text = str_field(doc, "text").or_else(|| str_field(doc, "page_content"));
+        let Some(text) = text.filter(|t| !t.trim().is_empty()) else {
+            continue;
+        };
+        let metadata = doc.get("metadata").and_then(Value::as_object);
+        let mut attrs = BTreeMap::new();
+        if let Some(id) = id_field(doc) {
+            attrs.insert("source_id".to_string(), id);
+        }
+        let mut kind = "document".to_string();
+        let mut ts = None;
+        for (key, v) in metadata.into_iter().flatten() {
+            match (key.as_str(), v) {
+                ("kind", Value::String(k)) => kind = k.clone(),
+                ("ts", Value::Number(n)) => ts = n.as_i64(),
+                ("created_at", Value::String(s)) => ts = parse_datetime(s),
+                (_, Value::String(s)) => {
+                    attrs.insert(key.clone(), s.clone());
+                }
+                (_, Value::Number(n)) => {
+                    attrs.insert(key.clone(), n.to_string());
+                }
+                _ => {}
+            }
+        }
+        drafts.push(Draft {
+            text: text.to_string(),
+            kind,
+            ts,
+            tags: Vec::new(),
+            attrs,
+        });
+    }
+    Ok(drafts)
+}
+
+fn chatgpt(value: &Value) -> Result<Vec<Draft>, String> {
+    let conversations = value
+        .as_array()
+        .ok_or("expected the list in conversations.json")?;
+    let mut drafts = Vec::new();
+    for conv in conversations {
+        let title = str_field(conv, "title").unwrap_or("").to_string();
+        let conv_ts = conv.get("create_time").and_then(Value::as_f64);
+        let Some(mapping) = conv.get("mapping").and_then(Value::as_object) else {
+            continue;
+        };
+        let mut messages: Vec<(f64, Draft)> = Vec::new();
+        for node in mapping.values() {
+            let Some(msg) = node.get("message").filter(|m| !m.is_null()) else {
+                continue;
+            };
+            let role = msg
+                .get("author")
+                .and_then(|a| str_field(a, "role"))
+                .unwrap_or("");
+            if role != "user" && role != "assistant" {
+                continue;
+            }
+            let parts = msg
+                .get("content")
+                .and_then(|c| c.get("parts"))
+                .and_then(Value::as_array);
+            let text: Vec<&str> = parts
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .filter(|p| !p.trim().is_empty())
+                .collect();
+            if text.is_empty() {
+                continue;
+            }
+            let created = msg.get("create_time").and_then(Value::as_f64).or(conv_ts);
+            let mut attrs = BTreeMap::from([("conversation".to_string(), title.clone())]);
+            if let Some(id) = id_field(msg) {
+                attrs.insert("source_id".to_string(), id);
+            }
+            let draft = Draft {
+                text: text.join("\n"),
+                kind: "chat".to_string(),
+                ts: created.map(|t| t as i64),
+                tags: vec!["chatgpt".to_string(), format!("role:{role}")],
+                attrs,
+            };
+            messages.push((created.unwrap_or(0.0), draft));
+        }
+        // The mapping is a tree keyed by node id; time order is close enough to
+        // reading order for retrieval.
+        messages.sort_by(|a, b| a.0.total_cmp(&b.0));
+        drafts.extend(messages.into_iter().map(|(_, d)| d));
+    }
+    Ok(drafts)
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+/// A string or numeric `id` as text.
+fn id_field(value: &Value) -> Option<String> {
+    match value.get("id")? {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,258 @@
+//! HNSW index over every record, persisted next to the store so searches on an
+//! unchanged store skip the rebuild.
+//!
+//! `hnsw_rs` dumps the graph as `<store>.index.<unique>.hnsw.graph` /
+//! `.hnsw.data`, and `<store>.index` records the fingerprint they were built
+//! from (the store's size, mtime and body checksum) and their name. Any
+//! difference means a rebuild.
+//!
+//! Searches in other processes or `serve` threads may save at the same time.
+//! Saves take `<store>.index.lock`, dump under a name no other save uses and
+//! replace `<store>.index` atomically last, so a reader finds either the old
+//! complete dump or the new one; the replaced dump is removed afterwards.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+use hnsw_rs::anndists::dist::distances::DistCosine;
+use hnsw_rs::api::AnnT;
+use hnsw_rs::hnswio::HnswIo;
+use hnsw_rs::prelude::Hnsw;
+use serde::{Deserialize, Serialize};
+
+use crate::store::{body_checksum, crc32, file_stamp, lock_store, sibling_path, write_atomic};
+use crate::{build_index, now_millis, print_timing};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    store_len: u64,
+    store_mtime_ns: u128,
+    body_crc: u32,
+}
+
+impl Fingerprint {
+    /// Take this before loading the store: a write in between then only causes
+    /// a cache miss, never an index saved under the wrong fingerprint.
+    /// `None` for missing files and files without checksums, which are not cached.
+    pub fn of(path: &Path) -> Option<Fingerprint> {
+        let (store_len, store_mtime_ns) = file_stamp(path).ok()?;
+        let body_crc = body_checksum(path).ok()??;
+        Some(Fingerprint {
+            store_len,
+            store_mtime_ns,
+            body_crc,
+        })
+    }
+
+    /// For files without a stored checksum (`index add-vector`): `stamp` is
+    /// the `file_stamp` taken before `data`, the whole file, was read.
+    pub fn of_data((store_len, store_mtime_ns): (u64, u128), data: &[u8]) -> Fingerprint {
+        Fingerprint {
+            store_len,
+            store_mtime_ns,
+            body_crc: crc32(data),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Meta {
+    fingerprint: Fingerprint,
+    /// Basename `hnsw_rs` actually dumped under.
+    basename: String,
+    points: usize,
+}
+
+/// Runs `f` on the index over `vecs` (all records of the store at `path`, in
+/// file order), loading it from the cache when `fingerprint` matches and
+/// building and caching it otherwise. With `timing`, prints how long the load
+/// or build took.
+pub fn with_index<R>(
+    path: &Path,
+    fingerprint: Option<Fingerprint>,
+    vecs: &[Vec<f32>],
+    timing: bool,
+    f: impl FnOnce(&Hnsw<'_, f32, DistCosine>) -> R,
+) -> R {
+    let started = Instant::now();
+    let build = || {
+        let hnsw = build_index(vecs);
+        if timing {
+            print_timing(&format!("index build ({} vectors)", vecs.len()), started);
+        }
+        hnsw
+    };
+    let Some(fingerprint) = fingerprint else {
+        return f(&build());
+    };
+    let dir = cache_dir(path);
+    let meta = read_meta(path)
+        .filter(|m| m.fingerprint == fingerprint && m.points == vecs.len());
+    if let Some(meta) = meta {
+        let mut hnswio = HnswIo::new(&dir, &meta.basename);
+        // Bound first, so the index is dropped before the loader it borrows.
+        let loaded = hnswio.load_hnsw::<f32, DistCosine>();
+        if let Ok(hnsw) = loaded {
+            if timing {
+                print_timing("index load (cached)", started);
+            }
+            return f(&hnsw);
+        }
+    }
+
+    let hnsw = build();
+    let out = f(&hnsw);
+    // The cache is an optimisation; a read-only directory just means rebuilding.
+    let _ = save(path, &dir, &hnsw, fingerprint, vecs.len());
+    out
+}
+
+/// Every file the cache may have written, for callers that need them gone.
+pub fn cache_files(path: &Path) -> Vec<PathBuf> {
+    let meta_path = sibling_path(path, ".index");
+    let mut files = vec![sibling_path(&meta_path, ".lock"), meta_path];
+    // Dumps left behind by interrupted saves are named like the current one.
+    let prefix = format!("{}.", default_basename(path));
+    if let Ok(entries) = fs::read_dir(cache_dir(path)) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            let dump = name.ends_with(".hnsw.graph") || name.ends_with(".hnsw.data");
+            if dump && name.starts_with(prefix.as_str()) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.retain(|f| f.exists());
+    files
+}
+
+fn save(
+    path: &Path,
+    dir: &Path,
+    hnsw: &Hnsw<'_, f32, DistCosine>,
+    fingerprint: Fingerprint,
+    points: usize,
+) -> io::Result<()> {
+    let meta_path = sibling_path(path, ".index");
+    let _lock = lock_store(&meta_path)?;
+    let old = read_meta(path);
+    let unique = format!("{}.{}", default_basename(path), dump_id());
+    let basename = match hnsw.file_dump(dir, &unique) {
+        Ok(basename) => basename,
+        Err(e) => {
+            remove_dump(dir, &unique);
+            return Err(io::Error::other(e.to_string()));
+        }
+    };
+    let meta = Meta {
+        fingerprint,
+        basename,
+        points,
+    };
+    let bytes = bincode::serialize(&meta).map_err(|_| io::ErrorKind::InvalidData)?;
+    if let Err(e) = write_atomic(&meta_path, &[&bytes]) {
+        remove_dump(dir, &meta.basename);
+        return Err(e);
+    }
+    if let Some(old) = old.filter(|old| old.basename != meta.basename) {
+        remove_dump(dir, &old.basename);
+    }
+    Ok(())
+}
+
+/// Unique across processes, the threads of this one and earlier processes
+/// that had the same pid.
+fn dump_id() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let n = NEXT.fetch_add(1, Ordering::Relaxed);
+    format!("{}-{}-{n}", process::id(), now_millis())
+}
+
+fn remove_dump(dir: &Path, basename: &str) {
+    for ext in ["hnsw.graph", "hnsw.data"] {
+        let _ = fs::remove_file(dir.join(format!("{basename}.{ext}")));
+    }
+}
+
+fn read_meta(path: &Path) -> Option<Meta> {
+    let bytes = fs::read(sibling_path(path, ".index")).ok()?;
+    bincode::deserialize(&bytes).ok()
+}
+
+fn cache_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn default_basename(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    format!("{name}.index")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::thread;
+
+    use super::*;
+    use crate::store::{load_store, update_store, Record};
+    use crate::{collect_vectors, embed_text};
+
+    #[test]
+    fn concurrent_saves_leave_one_complete_dump() {
+        let dir = env::temp_dir().join(format!("memstore-index-cache-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("memories.hnsw");
+        update_store(&path, |store| {
+            for n in 0..50u128 {
+                let text = format!("note {n} about deploys and rollbacks");
+                store.records.push(Record {
+                    id: n,
+                    ts: 1_700_000_000,
+                    kind: "fact".to_string(),
+                    weight: 1.0,
+                    vector: embed_text(&text),
+                    text,
+                    tags: Vec::new(),
+                    attrs: Default::default(),
+                });
+            }
+            Ok(())
+        })
+        .unwrap();
+        let fingerprint = Fingerprint::of(&path);
+        let vecs = collect_vectors(&load_store(&path).unwrap().records);
+
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    let points = with_index(&path, fingerprint, &vecs, false, |h| h.get_nb_point());
+                    assert_eq!(points, vecs.len());
+                });
+            }
+        });
+
+        let meta = read_meta(&path).unwrap();
+        assert_eq!(Some(meta.fingerprint), fingerprint);
+        let dumps: Vec<PathBuf> = cache_files(&path)
+            .into_iter()
+            .filter(|f| f.extension().is_some_and(|e| e == "graph" || e == "data"))
+            .collect();
+        assert_eq!(dumps.len(), 2, "{dumps:?}");
+        assert!(dumps
+            .iter()
+            .all(|f| f.to_string_lossy().contains(&meta.basename)));
+        let mut hnswio = HnswIo::new(&dir, &meta.basename);
+        let loaded = hnswio.load_hnsw::<f32, DistCosine>().unwrap();
+        assert_eq!(loaded.get_nb_point(), vecs.len());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,182 @@
+//! Inverted-file index (IVF-flat): a coarse spherical k-means over the vectors,
+//! with each record listed under its nearest center. A query scans only the
+//! lists of its closest centers, so the index is a few centers plus one `u32`
+//! per record instead of an HNSW graph.
+//!
+//! Cached as `<store>.ivf` under the same fingerprint as the HNSW cache.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index_cache::Fingerprint;
+use crate::progress::Progress;
+use crate::store::{sibling_path, Record};
+use crate::{cosine_sim, normalize, print_timing};
+
+const TRAIN_ITERATIONS: usize = 10;
+/// Training points per list; k-means runs on an evenly spaced sample of this size.
+const TRAIN_PER_LIST: usize = 32;
+
+#[derive(Serialize, Deserialize)]
+struct Ivf {
+    fingerprint: Option<Fingerprint>,
+    centers: Vec<Vec<f32>>,
+    /// Positions in the store, per center.
+    lists: Vec<Vec<u32>>,
+}
+
+/// Up to `k` positions from `allowed` (sorted) nearest to `query`, scanning the
+/// lists of the `sqrt(nlist)` closest centers.
+pub fn search(
+    path: &Path,
+    fingerprint: Option<Fingerprint>,
+    records: &[Record],
+    query: &[f32],
+    k: usize,
+    allowed: &[usize],
+    timing: bool,
+) -> Vec<usize> {
+    let ivf = load_or_build(path, fingerprint, records, timing);
+    let nprobe = ((ivf.centers.len() as f32).sqrt().ceil() as usize).max(1);
+    let mut ranked_lists: Vec<(f32, usize)> = ivf
+        .centers
+        .iter()
+        .map(|c| cosine_sim(c, query))
+        .enumerate()
+        .map(|(i, sim)| (sim, i))
+        .collect();
+    ranked_lists.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut hits: Vec<(f32, usize)> = ranked_lists
+        .iter()
+        .take(nprobe)
+        .flat_map(|&(_, list)| &ivf.lists[list])
+        .map(|&pos| pos as usize)
+        .filter(|pos| allowed.binary_search(pos).is_ok())
+        .map(|pos| (cosine_sim(query, &records[pos].vector), pos))
+        .collect();
+    hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+    hits.into_iter().take(k).map(|(_, pos)| pos).collect()
+}
+
+/// Every file this index may have written.
+pub fn cache_files(path: &Path) -> Vec<PathBuf> {
+    let file = sibling_path(path, ".ivf");
+    if file.exists() {
+        vec![file]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Loads the cached index, building and caching it if it is stale; for `maintain`.
+pub fn warm(path: &Path, fingerprint: Option<Fingerprint>, records: &[Record]) {
+    load_or_build(path, fingerprint, records, false);
+}
+
+fn load_or_build(
+    path: &Path,
+    fingerprint: Option<Fingerprint>,
+    records: &[Record],
+    timing: bool,
+) -> Ivf {
+    let started = Instant::now();
+    let cache_path = sibling_path(path, ".ivf");
+    if fingerprint.is_some() {
+        let cached = fs::read(&cache_path)
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<Ivf>(&bytes).ok())
+            .filter(|ivf| {
+                ivf.fingerprint == fingerprint
+                    && ivf.lists.iter().map(Vec::len).sum::<usize>() == records.len()
+            });
+        if let Some(ivf) = cached {
+            if timing {
+                print_timing("ivf load (cached)", started);
+            }
+            return ivf;
+        }
+    }
+
+    let ivf = build(records, fingerprint);
+    if timing {
+        let label = format!(
+            "ivf build ({} lists, {} vectors)",
+            ivf.centers.len(),
+            records.len()
+        );
+        print_timing(&label, started);
+    }
+    if fingerprint.is_some() {
+        // The cache is an optimisation; a read-only directory just means rebuilding.
+        let _ = save(&cache_path, &ivf);
+    }
+    ivf
+}
+
+fn build(records: &[Record], fingerprint: Option<Fingerprint>) -> Ivf {
+    let nlist = ((records.len() as f32).sqrt().round() as usize).clamp(1, 4096);
+    let stride = (records.len() / (nlist * TRAIN_PER_LIST)).max(1);
+    let sample: Vec<&[f32]> = records
+        .iter()
+        .step_by(stride)
+        .map(|r| r.vector.as_slice())
+        .collect();
+
+    // Seeds are spread evenly through the sample, so builds are deterministic.
+    let seed_stride = (sample.len() / nlist).max(1);
+    let mut centers: Vec<Vec<f32>> = sample
+        .iter()
+        .step_by(seed_stride)
+        .take(nlist)
+        .map(|v| v.to_vec())
+        .collect();
+    for _ in 0..TRAIN_ITERATIONS {
+        let mut sums = vec![vec![0.0f32; centers[0].len()]; centers.len()];
+        for v in &sample {
+            let c = nearest(&centers, v);
+            for (s, x) in sums[c].iter_mut().zip(v.iter()) {
+                *s += x;
+            }
+        }
+        // An emptied list keeps its old center.
+        for (center, mut sum) in centers.iter_mut().zip(sums) {
+            if sum.iter().any(|v| *v != 0.0) {
+                normalize(&mut sum);
+                *center = sum;
+            }
+        }
+    }
+
+    let mut lists = vec![Vec::new(); centers.len()];
+    let progress = Progress::new("ivf build", records.len());
+    for (pos, rec) in records.iter().enumerate() {
+        lists[nearest(&centers, &rec.vector)].push(pos as u32);
+        progress.inc(1);
+    }
+    progress.finish();
+    Ivf {
+        fingerprint,
+        centers,
+        lists,
+    }
+}
+
+fn nearest(centers: &[Vec<f32>], vector: &[f32]) -> usize {
+    centers
+        .iter()
+        .map(|c| cosine_sim(c, vector))
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn save(cache_path: &Path, ivf: &Ivf) -> io::Result<()> {
+    let bytes = bincode::serialize(ivf).map_err(|_| io::ErrorKind::InvalidData)?;
+    fs::write(cache_path, bytes)
+}
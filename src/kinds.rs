@@ -0,0 +1,280 @@
+//! Matching a requested kind (`--kind`, `kind:`) against the kinds actually
+//! stored, so `decisions` finds `decision` records instead of nothing.
+//!
+//! When no record has the exact kind, in order:
+//! 1. `kind_aliases` in the config maps it to another kind;
+//! 2. a stored kind that differs only in case, punctuation or a plural `s`;
+//! 3. the stored kind closest in spelling, at most two edits away.
+//!
+//! A substitution is reported on stderr, as is a kind that matches nothing.
+//!
+//! `kinds` edits the registry of kinds `add` accepts (see `KindRegistry`), so
+//! typos such as `sumary` are caught before they split the store; `kind rename`
+//! and `kind merge` fix the records already split.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{self, KindRegistry, UnknownKind};
+use crate::filter::RecordFilter;
+use crate::store::{stream_records, update_store, Record};
+use crate::{local_only, path_arg, resolve_path, store_error};
+
+/// Edits allowed between a requested kind and a stored one.
+const MAX_EDITS: usize = 2;
+
+/// Rewrites `filter.kind` to the stored kind it most likely means.
+pub fn resolve(filter: &mut RecordFilter, records: &[Record], aliases: &BTreeMap<String, String>) {
+    if filter.kind.is_none() {
+        return;
+    }
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for rec in records {
+        *counts.entry(rec.kind.as_str()).or_insert(0) += 1;
+    }
+    resolve_counted(filter, counts, aliases);
+}
+
+/// `resolve` for a store read as a stream: one pass over `path` counts the
+/// stored kinds, holding no records.
+pub fn resolve_streamed(
+    filter: &mut RecordFilter,
+    path: &Path,
+    aliases: &BTreeMap<String, String>,
+) -> io::Result<()> {
+    if filter.kind.is_none() {
+        return Ok(());
+    }
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for rec in stream_records(path)? {
+        *counts.entry(rec?.kind).or_insert(0) += 1;
+    }
+    let counts = counts.iter().map(|(k, n)| (k.as_str(), *n)).collect();
+    resolve_counted(filter, counts, aliases);
+    Ok(())
+}
+
+/// `resolve`, given how many records each stored kind has.
+fn resolve_counted(
+    filter: &mut RecordFilter,
+    counts: HashMap<&str, usize>,
+    aliases: &BTreeMap<String, String>,
+) {
+    let Some(requested) = filter.kind.clone() else {
+        return;
+    };
+    if counts.contains_key(requested.as_str()) {
+        return;
+    }
+    if let Some(target) = aliases.get(&requested) {
+        filter.kind = Some(target.clone());
+        return;
+    }
+    // Most records first, so ties go to the kind in common use.
+    let mut stored: Vec<(&str, usize)> = counts.into_iter().collect();
+    stored.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let stored: Vec<String> = stored.into_iter().map(|(k, _)| k.to_string()).collect();
+    match closest(&requested, &stored) {
+        Some(kind) => {
+            eprintln!("No records of kind {requested:?}; searching kind {kind:?}");
+            filter.kind = Some(kind.to_string());
+        }
+        None if !stored.is_empty() => eprintln!("No records of kind {requested:?}"),
+        None => {}
+    }
+}
+
+/// The first of `candidates` that differs from `kind` only in case,
+/// punctuation or a plural `s`, else the one closest in spelling within
+/// `MAX_EDITS`.
+pub fn closest<'a>(kind: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let wanted = normalize(kind);
+    candidates
+        .iter()
+        .find(|c| normalize(c) == wanted)
+        .or_else(|| {
+            candidates
+                .iter()
+                .map(|c| (edit_distance(&normalize(c), &wanted), c))
+                .filter(|(d, _)| *d <= MAX_EDITS && *d < wanted.len())
+                .min_by_key(|(d, _)| *d)
+                .map(|(_, c)| c)
+        })
+        .map(String::as_str)
+}
+
+/// Lowercase letters and digits only, without a trailing plural `s`.
+fn normalize(kind: &str) -> String {
+    let mut s: String = kind
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if s.len() > 3 && s.ends_with('s') {
+        s.pop();
+    }
+    s
+}
+
+/// Levenshtein distance over chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (above + 1)
+                .min(row[j] + 1)
+                .min(diagonal + usize::from(ca != *cb));
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+pub fn cmd_kinds(args: &[String]) -> Result<(), &'static str> {
+    let config_error = |e: io::Error| {
+        eprintln!("{}: {e}", config::config_path().display());
+        "config failed"
+    };
+    let mut cfg = config::load_config().map_err(config_error)?;
+    let registry = cfg.kinds.get_or_insert_with(KindRegistry::default);
+    match (args.first().map(String::as_str), &args[args.len().min(1)..]) {
+        (Some("list"), _) => {
+            for kind in &registry.allowed {
+                println!("{kind}");
+            }
+            if registry.allowed.is_empty() {
+                eprintln!("No kind registry; add accepts any kind");
+            }
+            return Ok(());
+        }
+        (Some("add"), kinds) if !kinds.is_empty() => {
+            for kind in kinds {
+                if !registry.allowed.contains(kind) {
+                    registry.allowed.push(kind.clone());
+                }
+            }
+        }
+        (Some("rm"), kinds) if !kinds.is_empty() => {
+            registry.allowed.retain(|k| !kinds.contains(k));
+        }
+        (Some("policy"), [policy]) => {
+            registry.unknown = match policy.as_str() {
+                "warn" => UnknownKind::Warn,
+                "reject" => UnknownKind::Reject,
+                _ => {
+                    eprintln!("Policy must be warn or reject");
+                    return Err("invalid policy");
+                }
+            };
+        }
+        _ => {
+            eprintln!("Usage: kinds list | add <kind>... | rm <kind>... | policy warn|reject");
+            return Err("unknown kinds command");
+        }
+    }
+    config::save_config(&cfg).map_err(config_error)
+}
+
+/// `kind rename --from <old> --to <new>` and `kind merge --into <kind> <old>...`:
+/// rewrites the kind of every matching record in one locked pass. `rename`
+/// refuses a target that is already in use (that is a merge); both update the
+/// registry if the old kinds were in it.
+pub fn cmd_kind(args: &[String]) -> Result<(), &'static str> {
+    let Some(sub) = args.first().map(String::as_str) else {
+        eprintln!("Usage: kind rename --from <kind> --to <kind> | kind merge --into <kind> <kind>...");
+        return Err("missing kind command");
+    };
+    let mut from: Vec<String> = Vec::new();
+    let mut to: Option<String> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--from" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    from.push(v.clone());
+                }
+            }
+            "--to" | "--into" => {
+                i += 1;
+                to = args.get(i).cloned();
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other if sub == "merge" => from.push(other.to_string()),
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let rename = match sub {
+        "rename" => true,
+        "merge" => false,
+        other => {
+            eprintln!("Unknown kind command {other:?} (expected rename or merge)");
+            return Err("unknown kind command");
+        }
+    };
+    let Some(to) = to else {
+        eprintln!("Missing --to / --into");
+        return Err("missing kind");
+    };
+    from.retain(|k| *k != to);
+    if from.is_empty() || (rename && from.len() != 1) {
+        eprintln!("Usage: kind rename --from <kind> --to <kind> | kind merge --into <kind> <kind>...");
+        return Err("missing kind");
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    let changed = update_store(&path, |store| {
+        if rename && store.records.iter().any(|r| r.kind == to) {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("kind {to:?} is already in use; use kind merge"),
+            ));
+        }
+        let mut changed = 0;
+        for rec in store.records.iter_mut().filter(|r| from.contains(&r.kind)) {
+            rec.kind = to.clone();
+            changed += 1;
+        }
+        Ok(changed)
+    })
+    .map_err(store_error(&path, "write failed"))?;
+    eprintln!("Changed the kind of {changed} records to {to:?}");
+
+    // Keep the registry in step, so the new name is accepted and the old one flagged.
+    let config_error = |e: io::Error| {
+        eprintln!("{}: {e}", config::config_path().display());
+        "config failed"
+    };
+    let mut cfg = config::load_config().map_err(config_error)?;
+    if let Some(registry) = cfg.kinds.as_mut() {
+        if registry.allowed.iter().any(|k| from.contains(k)) {
+            registry.allowed.retain(|k| !from.contains(k));
+            if !registry.allowed.contains(&to) {
+                registry.allowed.push(to.clone());
+            }
+            config::save_config(&cfg).map_err(config_error)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,157 @@
+//! Guessing the language of record text. `add` stores the guess in the `lang`
+//! attribute; `search --lang de` and the `lang:` query term filter on it, and
+//! fall back to guessing for records added before it was stored.
+//!
+//! Text in a non-Latin script is named by its script (`zh`, `ja`, `ko`, `ru`,
+//! ...). Latin-script text is scored on common function words for `en`, `de`,
+//! `fr`, `es` and `nl`; short or mixed text with no clear winner gets no
+//! language rather than a wrong one.
+//!
+//! `--must` / `--must-not` compare terms in the record's language: German
+//! folds case, umlauts and `ß` (`Über` matches `ueber`, `Strasse` matches
+//! `Straße`), other languages fold case beyond ASCII. Vectors are unchanged;
+//! every language shares the hashing embedder.
+
+use crate::{tokenize, Record};
+
+pub const ATTR: &str = "lang";
+
+/// Function words per language, lowercase.
+const STOPWORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "was", "were", "to", "of", "that", "it", "for", "with",
+            "this", "we", "you", "not", "be", "have", "has", "will", "should", "but", "or",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ein", "eine", "einen", "mit", "zu", "den",
+            "dem", "des", "von", "auf", "für", "wir", "ich", "sie", "es", "auch", "sich", "im",
+            "wird", "werden", "sind", "oder", "aber", "dass", "bei", "nach",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "des", "une", "un", "pour", "pas", "que", "qui",
+            "dans", "avec", "sur", "nous", "vous", "ce", "il", "elle", "sont", "du", "au",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "y", "es", "que", "de", "en", "un", "una", "para", "con",
+            "por", "no", "se", "del", "al", "como", "pero", "está", "son",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "van", "niet", "dat", "op", "te", "met", "voor",
+            "zijn", "wij", "ik", "maar", "ook", "er",
+        ],
+    ),
+];
+
+/// Letters that hint at a language, worth one function word each.
+const HINTS: &[(&str, &[char])] = &[("de", &['ä', 'ö', 'ü', 'ß']), ("es", &['ñ', '¿', '¡'])];
+
+/// Function words the winner needs before it is trusted.
+const MIN_HITS: usize = 2;
+
+/// The language `text` is most likely in, as an ISO 639-1 code.
+pub fn detect(text: &str) -> Option<&'static str> {
+    if let Some(lang) = by_script(text) {
+        return Some(lang);
+    }
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .collect();
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| {
+            let hits = words.iter().filter(|w| stopwords.contains(*w)).count();
+            let hints = HINTS
+                .iter()
+                .filter(|(l, _)| l == lang)
+                .map(|(_, chars)| lower.chars().filter(|c| chars.contains(c)).count().min(1))
+                .sum::<usize>();
+            (*lang, hits + hints)
+        })
+        .collect();
+    scores.sort_by_key(|s| std::cmp::Reverse(s.1));
+    match scores.as_slice() {
+        [(lang, best), (_, second), ..] if *best >= MIN_HITS && best > second => Some(*lang),
+        _ => None,
+    }
+}
+
+/// The stored language of `rec`, else a guess from its text.
+pub fn of(rec: &Record) -> Option<&str> {
+    match rec.attrs.get(ATTR) {
+        Some(lang) => Some(lang.as_str()),
+        None => detect(&rec.text),
+    }
+}
+
+/// Tokens of `text` for term matching in `lang`; see the module docs.
+pub fn terms(text: &str, lang: Option<&str>) -> Vec<String> {
+    match lang {
+        None | Some("en") => tokenize(text),
+        Some("de") => tokenize(&fold_german(text)),
+        Some(_) => tokenize(&text.to_lowercase()),
+    }
+}
+
+fn fold_german(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.to_lowercase().chars() {
+        match c {
+            'ä' => out.push_str("ae"),
+            'ö' => out.push_str("oe"),
+            'ü' => out.push_str("ue"),
+            'ß' => out.push_str("ss"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The language of text mostly written in one non-Latin script.
+fn by_script(text: &str) -> Option<&'static str> {
+    let mut letters = 0;
+    let mut counts: Vec<(&'static str, usize)> = Vec::new();
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        letters += 1;
+        let lang = match c as u32 {
+            0x3040..=0x30FF => "ja",
+            0x3400..=0x4DBF | 0x4E00..=0x9FFF => "zh",
+            0x1100..=0x11FF | 0xAC00..=0xD7AF => "ko",
+            0x0400..=0x04FF => "ru",
+            0x0370..=0x03FF => "el",
+            0x0590..=0x05FF => "he",
+            0x0600..=0x06FF => "ar",
+            0x0900..=0x097F => "hi",
+            _ => continue,
+        };
+        match counts.iter_mut().find(|(l, _)| *l == lang) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((lang, 1)),
+        }
+    }
+    // Japanese mixes kana with kanji, which would otherwise count as Chinese.
+    let kana = counts.iter().any(|(l, _)| *l == "ja");
+    let non_latin: usize = counts.iter().map(|(_, n)| n).sum();
+    if non_latin * 2 <= letters {
+        return None;
+    }
+    if kana {
+        return Some("ja");
+    }
+    counts.into_iter().max_by_key(|(_, n)| *n).map(|(l, _)| l)
+}
@@ -0,0 +1,2626 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use hnsw_rs::anndists::dist::distances::DistCosine;
+use hnsw_rs::prelude::{Hnsw, Neighbour};
+use serde::{Deserialize, Serialize};
+
+mod archive;
+mod cautions;
+mod centroids;
+mod classify;
+mod cluster;
+#[cfg(feature = "parquet")]
+mod columnar;
+mod config;
+mod consolidate;
+mod context;
+mod dates;
+mod drift;
+mod explore;
+mod export;
+mod ffi;
+mod filter;
+mod follow;
+mod forget;
+#[cfg(fuzzing)]
+pub mod fuzzing;
+mod importance;
+mod importers;
+mod index_cache;
+mod ivf;
+mod kinds;
+mod lang;
+mod maintain;
+mod multi_query;
+#[cfg(feature = "node")]
+mod node;
+mod oversize;
+mod pq;
+mod progress;
+mod query;
+mod random;
+mod recency;
+mod redact;
+mod remember;
+mod remote;
+mod repair;
+mod reports;
+mod review;
+mod rpc;
+mod schema;
+mod search_cache;
+mod selftest;
+mod server;
+mod shards;
+mod snippet;
+mod spill;
+mod store;
+mod subject;
+mod tags;
+mod templates;
+mod tx;
+mod vector_index;
+mod warm;
+
+use config::Profile;
+use filter::RecordFilter;
+use recency::Decay;
+use store::{
+    ensure_parent_dir, load_store, load_store_versioned, lock_store, new_store, resign_file,
+    save_store, sibling_path, stream_records, update_store, update_store_if, verify_store,
+    Signature, STORE_VERSION, VECTOR_DIM,
+};
+
+/// For reading store files from Rust without the CLI: `Store::iter_records`
+/// decodes one record at a time from any reader.
+pub use store::{Record, RecordStream, Store};
+
+const HNSW_M: usize = 16;
+const HNSW_EF_CONSTRUCTION: usize = 200;
+const HNSW_NB_LAYER: usize = 16;
+const HNSW_EF_SEARCH: usize = 50;
+/// Points per `parallel_insert` call; large enough to keep every core busy.
+const INSERT_BATCH: usize = 10_000;
+
+/// Multipliers for the three parts of the search score.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+struct Scoring {
+    similarity: f32,
+    weight: f32,
+    recency: f32,
+    /// Multiplies the whole score of records of a kind; kinds not listed keep 1.0.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    kind_boost: BTreeMap<String, f32>,
+    /// How the recency part falls off with age.
+    #[serde(skip_serializing_if = "is_default_decay")]
+    decay: Decay,
+    /// `decay` for particular kinds.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    kind_decay: BTreeMap<String, Decay>,
+}
+
+fn is_default_decay(decay: &Decay) -> bool {
+    *decay == Decay::default()
+}
+
+/// Everything that shapes a search besides the query text.
+#[derive(Clone, Debug)]
+struct SearchOptions {
+    limit: usize,
+    scoring: Scoring,
+    filter: RecordFilter,
+    /// At most this many hits of any one kind.
+    per_kind: Option<usize>,
+    index: IndexKind,
+    /// Re-rank product-quantized candidates from the full vectors.
+    rerank: bool,
+    /// Months of shards to search in a sharded store; `None` searches all.
+    window: Option<usize>,
+    /// Also scan the cold archive.
+    include_archive: bool,
+    /// Stream store files larger than this many bytes instead of loading them.
+    max_memory: Option<u64>,
+    /// Leave out records older than this many seconds, unless the search
+    /// already bounds time itself (`after:`, `before:`) or searches the archive.
+    max_age: Option<i64>,
+    /// Print per-phase timings to stderr.
+    timing: bool,
+}
+
+impl SearchOptions {
+    /// What `profile` sets for its searches, with no filters; for callers
+    /// such as `rpc` that take the query, limit and filters per request.
+    fn from_profile(profile: &Profile) -> SearchOptions {
+        SearchOptions {
+            limit: profile.limit.unwrap_or(3),
+            scoring: profile.scoring.clone(),
+            filter: RecordFilter::default(),
+            per_kind: None,
+            index: profile.index.unwrap_or_default(),
+            rerank: profile.rerank.unwrap_or(false),
+            window: profile.shard_window,
+            include_archive: false,
+            max_memory: profile.memory_limit(),
+            max_age: profile.max_age(),
+            timing: false,
+        }
+    }
+}
+
+/// Approximate index behind searches on large stores.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum IndexKind {
+    #[default]
+    Hnsw,
+    /// Inverted lists over coarse centroids: far less memory and a faster
+    /// build than HNSW, at some cost in recall.
+    Ivf,
+    /// Product-quantized codes, 32 bytes per vector, compared to the query
+    /// through a lookup table.
+    Pq,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Scoring {
+            similarity: 2.0,
+            weight: 0.5,
+            recency: 1.0,
+            kind_boost: BTreeMap::new(),
+            decay: Decay::default(),
+            kind_decay: BTreeMap::new(),
+        }
+    }
+}
+
+/// Flags whose value is a file or directory, and so may be any OS string.
+const PATH_FLAGS: &[&str] = &[
+    "--path",
+    "--out",
+    "--dir",
+    "--from",
+    "--tokens",
+    "--new-key-file",
+    "--with-ids",
+    "--exclude-similar-to-file",
+];
+
+/// Path flag values that are not valid UTF-8, as given. The commands see
+/// `OS_ARG` and an index into this in their place, which `path_arg` turns
+/// back into the path; no real argument can hold the NUL it starts with.
+static OS_ARGS: OnceLock<Vec<OsString>> = OnceLock::new();
+const OS_ARG: &str = "\0os-arg:";
+
+/// The path a path flag's value names, whether or not it was valid UTF-8.
+fn path_arg(value: &str) -> PathBuf {
+    let stashed = value
+        .strip_prefix(OS_ARG)
+        .and_then(|n| n.parse::<usize>().ok())
+        .and_then(|n| OS_ARGS.get()?.get(n));
+    match stashed {
+        Some(os) => PathBuf::from(os),
+        None => PathBuf::from(value),
+    }
+}
+
+/// Runs the CLI with the process arguments; `src/main.rs` is a thin wrapper.
+pub fn run() {
+    let mut args: Vec<String> = Vec::new();
+    let mut os_args = Vec::new();
+    for arg in env::args_os().skip(1) {
+        match arg.into_string() {
+            Ok(arg) => args.push(arg),
+            Err(arg) if args.last().is_some_and(|flag| PATH_FLAGS.contains(&flag.as_str())) => {
+                args.push(format!("{OS_ARG}{}", os_args.len()));
+                os_args.push(arg);
+            }
+            Err(arg) => {
+                eprintln!(
+                    "Argument {arg:?} is not valid UTF-8; only the values of {} can be",
+                    PATH_FLAGS.join(", ")
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+    let _ = OS_ARGS.set(os_args);
+    let mut args = args.into_iter();
+    let Some(cmd) = args.next() else {
+        print_usage();
+        return;
+    };
+
+    let rest: Vec<String> = args.collect();
+    let result = match cmd.as_str() {
+        "add" => cmd_add(&rest),
+        "search" => cmd_search(&rest),
+        "remember" => remember::cmd_remember(&rest),
+        "recall" => remember::cmd_recall(&rest),
+        "ask" => remember::cmd_ask(&rest),
+        "grep" => cmd_grep(&rest),
+        "get" => cmd_get(&rest),
+        "neighbors" => cmd_neighbors(&rest),
+        "explore" => explore::cmd_explore(&rest),
+        "centroids" => centroids::cmd_centroids(&rest),
+        "cluster" => cluster::cmd_cluster(&rest),
+        "recent" => cmd_recent(&rest),
+        "random" => random::cmd_random(&rest),
+        "review" => review::cmd_review(&rest),
+        "compact" => cmd_compact(&rest),
+        "shard" => shards::cmd_shard(&rest),
+        "archive" => archive::cmd_archive(&rest),
+        "maintain" => maintain::cmd_maintain(&rest),
+        "warm" => warm::cmd_warm(&rest),
+        "redact" => redact::cmd_redact(&rest),
+        "subject" => subject::cmd_subject(&rest),
+        "forget" | "delete" => forget::cmd_forget(&rest),
+        "count" => cmd_count(&rest),
+        "aggregate" => reports::cmd_aggregate(&rest),
+        "timeline" => reports::cmd_timeline(&rest),
+        "digest" => reports::cmd_digest(&rest),
+        "du" => reports::cmd_du(&rest),
+        "export" => export::cmd_export(&rest),
+        "import" => export::cmd_import(&rest),
+        "export-vectors" => export::cmd_export_vectors(&rest),
+        "init" => cmd_init(&rest),
+        "meta" => cmd_meta(&rest),
+        "saved" => cmd_saved(&rest),
+        "kinds" => kinds::cmd_kinds(&rest),
+        "kind" => kinds::cmd_kind(&rest),
+        "tag" => tags::cmd_tag(&rest),
+        "tx" => tx::cmd_tx(&rest),
+        "index" => vector_index::cmd_index(&rest),
+        "stats" => cmd_stats(&rest),
+        "upgrade" => cmd_upgrade(&rest),
+        "verify" => cmd_verify(&rest),
+        "drift" => drift::cmd_drift(&rest),
+        "selftest" => selftest::cmd_selftest(&rest),
+        "repair" => repair::cmd_repair(&rest),
+        "rekey" => cmd_rekey(&rest),
+        "serve" => server::cmd_serve(&rest),
+        "rpc" => rpc::cmd_rpc(&rest),
+        "schema" => schema::cmd_schema(&rest),
+        "help" | "-h" | "--help" => {
+            print_usage();
+            Ok(())
+        }
+        _ => {
+            eprintln!("Unknown command: {cmd}");
+            print_usage();
+            Err("unknown command")
+        }
+    };
+
+    if result.is_err() {
+        std::process::exit(1);
+    }
+}
+
+fn print_usage() {
+    eprintln!(
+        "memstore - simple local memory store\n\n")
+    ;
+    eprintln!("Commands:");
+    eprintln!("  add     (--text <text> | --template <name> [--field <key=value>]...) [--kind <kind>]");
+    eprintln!("          [--classify] [--weight <w> | --auto-weight] [--tag <tag>]...");
+    eprintln!("          [--report-novelty] [--expected-generation <n>] [--path <file>] [--profile <name>]");
+    eprintln!("  search  --query <text>... [--combine avg|max] [--limit <n>] [--must <term>]... [--must-not <term>]...");
+    eprintln!("          [--lang <code>] [--per-kind <n>] [--snippet <chars>] [--collapse <sim>]");
+    eprintln!("          [--nearest-kind] [--index hnsw|ivf|pq] [--rerank] [--window <months>]");
+    eprintln!("          [--include-archive] [--all-time] [--no-cautions] [--decay <curve>] [--as-of <date>]");
+    eprintln!("          [--exclude-similar-to-file <file> [--exclude-threshold <sim>]]");
+    eprintln!("          [--max-memory <size>] [--timing] [--cite] [--explain] [--min-score <0-1>]");
+    eprintln!("          [--boost-term <word>=<weight>]...   (or word^weight in the query)");
+    eprintln!("          [--path <file>]... [--profile <name>] [--all-profiles]");
+    eprintln!("  remember \"<text>\" [--kind <kind>] [--tag <tag>]... [--force]   (guessed kind, skips duplicates)");
+    eprintln!("  recall  \"<question>\" [--budget <tokens>] [--cite]   (prompt-ready list within a token budget)");
+    eprintln!("  ask     \"<question>\" [--sentences <n>]   (extractive answer citing record ids)");
+    eprintln!("  saved   add <name> <query> | run <name> [search options] | list | rm <name>");
+    eprintln!("  kinds   list | add <kind>... | rm <kind>... | policy warn|reject   (registry of kinds add expects)");
+    eprintln!("  kind    rename --from <kind> --to <kind> | merge --into <kind> <kind>... [--path <file>]");
+    eprintln!("  tag     add|remove --id <id>... <tag>... | list | rename --from <tag> --to <tag> [--path <file>]");
+    eprintln!("          add|remove --query <text> [--threshold <sim>] [--dry-run] <tag>...   (by similarity)");
+    eprintln!("  tx      [<file> | -] [--expected-generation <n>] [--path <file>] [--profile <name>]");
+    eprintln!("          (JSON Lines of add/edit/delete operations, applied all or nothing)");
+    eprintln!("  index   add-vector (--id <id> --vector <v> | --from <file.jsonl>) | query --vector <v> [-k <n>] | rm --id <id>...");
+    eprintln!("          [--path <file>]   (ANN index over external vectors; default path <store>.vectors)");
+    eprintln!("  grep    --pattern <regex> [--ignore-case] [--kind <kind>] [--limit <n>] [--path <file>]");
+    eprintln!("  get     <id> [--path <file>] [--profile <name>]");
+    eprintln!("  neighbors --id <id> [-k <n>] [--show-distance] [--path <file>] [--profile <name>]");
+    eprintln!("  explore --plus <id>... [--minus <id>]... [-k <n>]   (neighbours of combined record vectors)");
+    eprintln!("  centroids [--path <file>] [--profile <name>]");
+    eprintln!("  cluster [-k <n>] [--algorithm kmeans] [--tag-records] [--path <file>] [--profile <name>]");
+    eprintln!("  recent  [--limit <n>] [--follow [--interval <secs>]] [--path <file>] [--profile <name>]");
+    eprintln!("  random  [-n <n>] [--kind <kind>] [--tag <tag>]... [--since <age>] [--recency-weighted] [--seed <n>]");
+    eprintln!("  review  [-n <n>] [--kind <kind>] [--tag <tag>]... [--interactive] | --id <id> --grade <0-5>");
+    eprintln!("  compact [--keep <n>] [--dry-run | --confirm]");
+    eprintln!("          [--consolidate-cmd <cmd> [--older-than <age>] [--similarity <s>]]");
+    eprintln!("          [--path <file>] [--profile <name>]");
+    eprintln!("  shard   [--path <file>] [--profile <name>]");
+    eprintln!("  archive --before <age> [--path <file>] [--profile <name>]");
+    eprintln!("  maintain [--index hnsw|ivf|pq] [--path <file>] [--profile <name>]   (for cron)");
+    eprintln!("  warm    [--index hnsw|ivf|pq] [--path <file>] [--profile <name>]   (load store and index ahead of searches)");
+    eprintln!("  redact  (--id <id>... | --all) [--patterns emails,phones,cards] [--dry-run] [--path <file>]");
+    eprintln!("  subject export|erase --tag <tag> [--yes] [--path <file>] [--profile <name>]");
+    eprintln!("  forget  --id <id>... [--shred] [--path <file>] [--profile <name>]   (alias: delete)");
+    eprintln!("  count   [--kind <kind>] [--since <age>] [--tag <tag>]... [--path <file>] [--profile <name>]");
+    eprintln!("  aggregate [--by day|week] [--group kind] [--since <age>] [--format table|json] [--path <file>]");
+    eprintln!("  timeline [--since <age>] [--kind <kind>] [--tag <tag>]... [--path <file>] [--profile <name>]");
+    eprintln!("  digest  [--since <age>] [--format text|markdown] [--kind <kind>] [--tag <tag>]...   (new records by kind)");
+    eprintln!("  du      [--path <file>] [--profile <name>]");
+    eprintln!("  export  [--format jsonl|qdrant-jsonl|chroma-jsonl|parquet|markdown [--group-by kind|day]] [--out <file[.gz|.zst]>] [--compress gz|zst] [--with-vectors] [--kind <kind>] [--tag <tag>]...");
+    eprintln!("          [--since <age>] [--path <file>] [--profile <name>]");
+    eprintln!("  import  <file | -> [--format jsonl|mem0|langchain|chatgpt|parquet] [--path <file>] [--profile <name>]");
+    eprintln!("  export-vectors --out <file.npy|file.arrow> [--format npy|arrow] [--with-ids <ids.json>] [--kind <kind>]");
+    eprintln!("          [--tag <tag>]... [--since <age>] [--path <file>] [--profile <name>]");
+    eprintln!("  init    [--name <name>] [--description <text>] [--path <file>] [--profile <name>]");
+    eprintln!("  meta set [--name <name>] [--description <text>] [--path <file>] [--profile <name>]");
+    eprintln!("  stats   [--path <file>] [--profile <name>]");
+    eprintln!("  upgrade [--path <file>] [--profile <name>]");
+    eprintln!("  verify  [--strict] [--path <file>] [--profile <name>]");
+    eprintln!("  drift   [--sample <n>] [--threshold <sim>] [--path <file>]   (stored vectors vs. the current embedder)");
+    eprintln!("  selftest [--keep]   (add/search/index/delete/compact/verify in a temp store)");
+    eprintln!("  repair  --reassign-dup-ids [--path <file>] [--profile <name>]");
+    eprintln!("  rekey   --new-key-file <file> [--path <file>] [--profile <name>]");
+    eprintln!("  serve   [--addr <host:port>] [--dir <dir>] [--tokens <file>] [--preload] [--threads <n>]");
+    eprintln!("  rpc     [--path <file>] [--profile <name>]   (JSON-RPC on stdin/stdout)");
+    eprintln!("  schema  [--format openai|anthropic]   (tool definitions for function calling)");
+    eprintln!("\nDefaults:");
+    eprintln!("  kind=summary, weight=1.0, limit=3, keep=5000");
+    eprintln!("  addr=127.0.0.1:7700, dir=stores/ in the user data directory");
+    eprintln!("\nsearch accepts several --path flags and merges the results by normalized score.");
+    eprintln!("Without --path, the nearest .memstore/ directory above the cwd is used if present,");
+    eprintln!("else memories.hnsw in the user data directory.");
+    eprintln!("Path values (--path, --out, --dir, ...) may be any OS string; other arguments must be UTF-8.");
+    eprintln!("--profile selects a [profiles.<name>] section of $MEMSTORE_CONFIG (~/.config/memstore/config.toml).");
+    eprintln!("--path also accepts http(s)://host:port/stores/<name> to use a memstore server.");
+    eprintln!("--query understands kind:, tag:, after:, before:, must: and not: terms, e.g.");
+    eprintln!("  kind:decision tag:infra after:2024-06-01 \"rollback plan\"");
+}
+
+fn cmd_add(args: &[String]) -> Result<(), &'static str> {
+    let mut text: Option<String> = None;
+    let mut template: Option<String> = None;
+    let mut fields: Vec<String> = Vec::new();
+    let mut kind: Option<String> = None;
+    let mut weight: Option<f32> = None;
+    let mut auto_weight = false;
+    let mut auto_kind = false;
+    let mut report_novelty = false;
+    let mut tags: Vec<String> = Vec::new();
+    let mut expected_generation: Option<u64> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--text" => {
+                i += 1;
+                text = args.get(i).cloned();
+            }
+            "--template" => {
+                i += 1;
+                template = args.get(i).cloned();
+            }
+            "--field" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    fields.push(v.clone());
+                }
+            }
+            "--kind" => {
+                i += 1;
+                kind = args.get(i).cloned();
+            }
+            "--weight" => {
+                i += 1;
+                weight = Some(parse_float("--weight", args.get(i))?);
+            }
+            "--auto-weight" => auto_weight = true,
+            "--classify" => auto_kind = true,
+            "--report-novelty" => report_novelty = true,
+            "--expected-generation" => {
+                i += 1;
+                expected_generation = Some(parse_flag("--expected-generation", args.get(i))?);
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    tags.push(v.clone());
+                }
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let mut attrs = BTreeMap::new();
+    let (text, kind) = match (text, template) {
+        (Some(_), Some(_)) => {
+            eprintln!("--text and --template cannot be combined");
+            return Err("conflicting arguments");
+        }
+        (Some(text), None) => (text, kind),
+        (None, Some(name)) => {
+            let rendered = templates::render_named(&name, &fields)?;
+            for tag in rendered.tags {
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+            }
+            attrs = rendered.attrs;
+            (rendered.text, Some(kind.unwrap_or(rendered.kind)))
+        }
+        (None, None) => {
+            eprintln!("Missing --text or --template");
+            return Err("missing text");
+        }
+    };
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    // Without --kind, a classifier picks one if asked to; else it is a summary.
+    let kind = kind.unwrap_or_else(|| match (&profile.classify, auto_kind) {
+        (Some(classifier), _) => classifier.kind(&text),
+        (None, true) => classify::guess_kind(&text).to_string(),
+        (None, false) => "summary".to_string(),
+    });
+    // An explicit --weight always wins; `None` asks the store to estimate one.
+    let weight = match weight {
+        Some(w) => Some(w),
+        None if auto_weight || profile.auto_weight == Some(true) => None,
+        None => Some(1.0),
+    };
+
+    let (record, nearest) =
+        add_text(&path, &profile, text, kind, weight, tags, attrs, expected_generation)?;
+    // Lets a calling agent judge whether the note said anything new.
+    if report_novelty {
+        println!("id\t{}", record.id);
+        match nearest {
+            Some(n) => {
+                println!("max_similarity\t{:.3}", n.similarity);
+                println!("nearest\t{}", n.id);
+            }
+            None => {
+                println!("max_similarity\t0.000");
+                println!("nearest\t-");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `add` after argument parsing: checks the profile's limits, writes locally
+/// or to a remote store, and runs auto-compaction. Also used by `remember`.
+/// With `expected_generation`, the write fails unless the store is still at it.
+#[allow(clippy::too_many_arguments)]
+fn add_text(
+    path: &Path,
+    profile: &Profile,
+    text: String,
+    kind: String,
+    weight: Option<f32>,
+    tags: Vec<String>,
+    attrs: BTreeMap<String, String>,
+    expected_generation: Option<u64>,
+) -> Result<(Record, Option<Nearest>), &'static str> {
+    // Read before adding, so a bad setting does not surface after the write.
+    let limits = config::auto_compact(profile)?;
+    let add_limits = config::add_limits(profile)?;
+    if let Some(w) = weight {
+        add_limits.check_weight(w).map_err(|e| {
+            eprintln!("Not added: {e}");
+            "invalid weight"
+        })?;
+    }
+    match add_limits.check_kind(&kind) {
+        Ok(None) => {}
+        Ok(Some(warning)) => eprintln!("Warning: {warning}"),
+        Err(e) => {
+            eprintln!("Not added: {e}");
+            return Err("unknown kind");
+        }
+    }
+    let added = match remote::store_url(path) {
+        // The server applies its own max_text.
+        Some(url) => {
+            remote::add(url, text, kind, weight, tags, attrs, expected_generation).map_err(
+                |e| {
+                    eprintln!("add failed: {e}");
+                    "remote failed"
+                },
+            )?
+        }
+        None => {
+            let fitted = oversize::fit(text, add_limits.max_text.as_ref()).map_err(|e| {
+                eprintln!("Not added: {e}");
+                "text too large"
+            })?;
+            if fitted.truncated {
+                eprintln!(
+                    "Truncated text from {} to {} bytes (max_text)",
+                    fitted.original_len,
+                    fitted.pieces[0].len()
+                );
+            } else if fitted.pieces.len() > 1 {
+                eprintln!(
+                    "Split {} bytes of text into {} records (max_text)",
+                    fitted.original_len,
+                    fitted.pieces.len()
+                );
+            }
+            ensure_parent_dir(path).map_err(|_| "mkdir failed")?;
+            let (rec, nearest, _) =
+                add_fitted(path, fitted, kind, weight, tags, attrs, expected_generation)
+                    .map_err(store_error(path, "write failed"))?;
+            if let Some(limits) = limits {
+                let removed =
+                    auto_compact(path, &limits).map_err(store_error(path, "compact failed"))?;
+                if !removed.is_empty() {
+                    eprintln!("Auto-compact removed {} old records", removed.len());
+                }
+            }
+            (rec, nearest)
+        }
+    };
+    Ok(added)
+}
+
+fn cmd_search(args: &[String]) -> Result<(), &'static str> {
+    let mut queries: Vec<String> = Vec::new();
+    let mut combine = multi_query::Combine::default();
+    let mut limit: Option<usize> = None;
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut profile: Option<String> = None;
+    let mut all_profiles = false;
+    let mut filter = RecordFilter::default();
+    let mut per_kind: Option<usize> = None;
+    let mut snippet_width: Option<usize> = None;
+    let mut collapse: Option<f32> = None;
+    let mut nearest_kind = false;
+    let mut index: Option<IndexKind> = None;
+    let mut rerank = false;
+    let mut window: Option<usize> = None;
+    let mut include_archive = false;
+    let mut all_time = false;
+    let mut exclude_file: Option<PathBuf> = None;
+    let mut exclude_threshold = context::DEFAULT_THRESHOLD;
+    let mut timing = false;
+    let mut show_cautions = true;
+    let mut cite = false;
+    let mut explain = false;
+    let mut min_score: Option<f32> = None;
+    let mut boost_terms: Vec<String> = Vec::new();
+    let mut decay: Option<Decay> = None;
+    let mut max_memory: Option<u64> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--query" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    queries.push(v.clone());
+                }
+            }
+            "--combine" => {
+                i += 1;
+                combine = match args.get(i).and_then(|v| multi_query::Combine::parse(v)) {
+                    Some(combine) => combine,
+                    None => {
+                        eprintln!("--combine must be avg or max");
+                        return Err("invalid combine");
+                    }
+                };
+            }
+            "--limit" => {
+                i += 1;
+                limit = Some(parse_flag("--limit", args.get(i))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    paths.push(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            "--all-profiles" => all_profiles = true,
+            "--must" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.must.push(v.clone());
+                }
+            }
+            "--must-not" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.must_not.push(v.clone());
+                }
+            }
+            "--lang" => {
+                i += 1;
+                filter.lang = args.get(i).map(|v| v.to_lowercase());
+            }
+            "--per-kind" => {
+                i += 1;
+                per_kind = Some(parse_flag("--per-kind", args.get(i))?);
+            }
+            "--nearest-kind" => nearest_kind = true,
+            "--index" => {
+                i += 1;
+                index = match args.get(i).map(String::as_str) {
+                    Some("hnsw") => Some(IndexKind::Hnsw),
+                    Some("ivf") => Some(IndexKind::Ivf),
+                    Some("pq") => Some(IndexKind::Pq),
+                    _ => {
+                        eprintln!("--index must be hnsw, ivf or pq");
+                        return Err("invalid index");
+                    }
+                };
+            }
+            "--rerank" => rerank = true,
+            "--include-archive" => include_archive = true,
+            "--all-time" => all_time = true,
+            "--exclude-similar-to-file" => {
+                i += 1;
+                exclude_file = args.get(i).map(|v| path_arg(v));
+            }
+            "--exclude-threshold" => {
+                i += 1;
+                exclude_threshold = parse_float("--exclude-threshold", args.get(i))?;
+            }
+            "--window" => {
+                i += 1;
+                window = args.get(i).and_then(|v| v.parse().ok()).filter(|n| *n > 0);
+                if window.is_none() {
+                    eprintln!("--window takes a number of months");
+                    return Err("invalid window");
+                }
+            }
+            "--timing" => timing = true,
+            "--no-cautions" => show_cautions = false,
+            "--cite" => cite = true,
+            "--explain" => explain = true,
+            "--min-score" => {
+                i += 1;
+                min_score = Some(parse_float("--min-score", args.get(i))?);
+            }
+            "--max-memory" => {
+                i += 1;
+                max_memory = args.get(i).and_then(|v| config::parse_size(v));
+                if max_memory.is_none() {
+                    eprintln!("--max-memory takes a size such as 512MB");
+                    return Err("invalid size");
+                }
+            }
+            // Same as `term^w` in the query, which is how it reaches the stores.
+            "--boost-term" => {
+                i += 1;
+                let boost = args
+                    .get(i)
+                    .and_then(|v| v.split_once('='))
+                    .filter(|(term, weight)| {
+                        let word = !term.is_empty()
+                            && !term.contains(|c: char| c.is_whitespace() || c == ':' || c == '"');
+                        let positive = weight
+                            .parse::<f32>()
+                            .is_ok_and(|w| w.is_finite() && w > 0.0);
+                        word && positive
+                    });
+                let Some((term, weight)) = boost else {
+                    eprintln!("--boost-term takes <word>=<weight>, e.g. rollback=2");
+                    return Err("invalid boost");
+                };
+                boost_terms.push(format!("{term}^{weight}"));
+            }
+            "--as-of" => {
+                i += 1;
+                filter.as_of = Some(parse_as_of(args.get(i))?);
+            }
+            "--decay" => {
+                i += 1;
+                let value = args.get(i).map(String::as_str).unwrap_or("");
+                decay = Some(Decay::parse(value).map_err(|e| {
+                    eprintln!("{e}");
+                    "invalid decay"
+                })?);
+            }
+            // Both take an optional value, so a following flag is not swallowed.
+            "--collapse" => {
+                collapse = Some(0.97);
+                let value = args.get(i + 1).and_then(|v| v.parse::<f32>().ok());
+                if let Some(v) = value.filter(|v| v.is_finite()) {
+                    collapse = Some(v);
+                    i += 1;
+                }
+            }
+            "--snippet" => {
+                snippet_width = Some(160);
+                if let Some(v) = args.get(i + 1).and_then(|v| v.parse().ok()) {
+                    snippet_width = Some(v);
+                    i += 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if queries.is_empty() {
+        eprintln!("Missing --query");
+        return Err("missing query");
+    }
+    for query in &mut queries {
+        for boost in &boost_terms {
+            query.push(' ');
+            query.push_str(boost);
+        }
+    }
+    // Stores parse the query themselves (remote ones included); this only reports syntax errors
+    // early and picks out the words to highlight.
+    let parsed = queries
+        .iter()
+        .map(|q| query::parse_query(q))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| {
+            eprintln!("{e}");
+            "invalid query"
+        })?;
+    let text = parsed
+        .iter()
+        .map(|p| p.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+    let terms: HashSet<String> = tokenize(&text).into_iter().collect();
+    let marks = if io::stdout().is_terminal() {
+        ("\x1b[1m", "\x1b[0m")
+    } else {
+        ("**", "**")
+    };
+    let render = |text: &str| match snippet_width {
+        Some(width) => snippet::snippet(text, &terms, width, marks),
+        None => tsv_field(text),
+    };
+
+    // Each target is (label, path, scoring, index); the label is only printed when merging.
+    let mut targets: Vec<(String, PathBuf, Scoring, IndexKind)> = Vec::new();
+    let selected = config::select_profile(profile.as_deref())?;
+    let limit = limit.or(selected.limit).unwrap_or(3);
+    let rerank = rerank || selected.rerank.unwrap_or(false);
+    let window = window.or(selected.shard_window);
+    let max_memory = max_memory.or(selected.memory_limit());
+    let max_age = if all_time { None } else { selected.max_age() };
+    if all_profiles {
+        for (name, p) in config::all_profiles()? {
+            let path = resolve_path(None, &p);
+            let kind = index.or(p.index).unwrap_or_default();
+            targets.push((name, path, p.scoring, kind));
+        }
+    }
+    let selected_index = index.or(selected.index).unwrap_or_default();
+    for path in paths {
+        targets.push((path.display().to_string(), path, selected.scoring.clone(), selected_index));
+    }
+    if targets.is_empty() {
+        let path = resolve_path(None, &selected);
+        targets.push((path.display().to_string(), path, selected.scoring.clone(), selected_index));
+    }
+
+    // Ranks kinds instead of records, so an agent can pick one before a full search.
+    if nearest_kind {
+        let [(_, path, _, _)] = targets.as_slice() else {
+            eprintln!("--nearest-kind works on a single store");
+            return Err("multiple stores");
+        };
+        local_only(path)?;
+        let ranked = centroids::nearest_kinds(path, &embed_text(&text))
+            .map_err(store_error(path, "read failed"))?;
+        for (sim, c) in ranked.into_iter().take(limit) {
+            println!("{sim:.3}\t{}\t{}", c.kind, c.count);
+        }
+        return Ok(());
+    }
+
+    // Hits already in the caller's prompt are dropped (see `context.rs`).
+    let context = match &exclude_file {
+        Some(file) => {
+            Some(context::Context::load(file).map_err(store_error(file, "read failed"))?)
+        }
+        None => None,
+    };
+    let fresh = |rec: &Record| {
+        !context
+            .as_ref()
+            .is_some_and(|c| c.contains(rec, exclude_threshold))
+    };
+
+    // Collapsing and excluding eat into the result count, so fetch extra to fill `limit`.
+    let fetch = if collapse.is_some() || context.is_some() {
+        limit.saturating_mul(4)
+    } else {
+        limit
+    };
+    // --decay replaces the profile's curves, per-kind ones included.
+    let options_for = |scoring: &Scoring, index: IndexKind| SearchOptions {
+        limit: fetch,
+        scoring: match &decay {
+            Some(decay) => Scoring {
+                decay: decay.clone(),
+                kind_decay: BTreeMap::new(),
+                ..scoring.clone()
+            },
+            None => scoring.clone(),
+        },
+        filter: filter.clone(),
+        per_kind,
+        index,
+        rerank,
+        window,
+        include_archive,
+        max_memory,
+        max_age,
+        timing,
+    };
+    // With --collapse, a trailing column says how many near-duplicates each hit stands for.
+    let collapsed_column = |n: usize| match collapse {
+        Some(_) => format!("\t+{n}"),
+        None => String::new(),
+    };
+    let now = parsed
+        .iter()
+        .filter_map(|p| p.filter.as_of)
+        .chain(filter.as_of)
+        .min()
+        .unwrap_or_else(now_secs);
+    // With --explain, a last column breaks the score down (see `score_parts`); with several
+    // queries, similarity and raw score are listed per query, separated by `/`.
+    let vectors: Vec<Vec<f32>> = parsed.iter().map(query::ParsedQuery::vector).collect();
+    let explained_column = |rec: &Record, scoring: &Scoring| {
+        if !explain {
+            return String::new();
+        }
+        let parts: Vec<ScoreParts> = vectors
+            .iter()
+            .map(|v| score_parts(v, rec, scoring, now))
+            .collect();
+        let per_query = |value: &dyn Fn(&ScoreParts) -> f32| {
+            let values: Vec<String> = parts.iter().map(|p| format!("{:.3}", value(p))).collect();
+            values.join("/")
+        };
+        format!(
+            "\tsim={} weight={:.2} recency={:.3} boost={:.2} raw={}",
+            per_query(&|p| p.cosine),
+            parts[0].weight,
+            parts[0].recency,
+            parts[0].boost,
+            per_query(&|p| p.raw(scoring))
+        )
+    };
+
+    if targets.len() == 1 {
+        let (_, path, scoring, index) = &targets[0];
+        let options = options_for(scoring, *index);
+        let found = multi_query::search(path, &queries, combine, &options, now)?;
+        let mut scored: Vec<(f32, Record)> = found
+            .into_iter()
+            .map(|(score, rec)| (calibrate(score, &options.scoring), rec))
+            .collect();
+        scored.retain(|(score, rec)| min_score.is_none_or(|min| *score >= min) && fresh(rec));
+        let hits = collapse_hits(scored, collapse, |(_, rec)| rec);
+        let mut cited = Vec::new();
+        for ((score, rec), n) in hits.into_iter().take(limit) {
+            println!(
+                "{score:.3}\t{}\t{}\t{}\t{}{}{}",
+                rec.kind,
+                rec.id,
+                rec.ts,
+                render(&rec.text),
+                collapsed_column(n),
+                explained_column(&rec, &options.scoring)
+            );
+            cited.push((rec.id, rec.ts, targets[0].0.as_str()));
+        }
+        if cite {
+            print_citations(&cited);
+        }
+        if show_cautions {
+            print_cautions(&targets, &text, render)?;
+        }
+        return Ok(());
+    }
+
+    // Raw scores are not comparable across stores (each profile has its own
+    // multipliers), so hits are merged on the calibrated score, which means the
+    // same in every store and does not depend on the other hits.
+    let mut merged: Vec<(f32, usize, Record)> = Vec::new();
+    let mut target_scoring = Vec::new();
+    for (source, (_, path, scoring, index)) in targets.iter().enumerate() {
+        let options = options_for(scoring, *index);
+        let scored = multi_query::search(path, &queries, combine, &options, now)?;
+        for (score, rec) in scored {
+            let score = calibrate(score, &options.scoring);
+            if min_score.is_some_and(|min| score < min) {
+                continue;
+            }
+            merged.push((score, source, rec));
+        }
+        target_scoring.push(options.scoring);
+    }
+    merged.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    merged.retain(|(_, _, rec)| fresh(rec));
+    if let Some(n) = per_kind {
+        cap_per_kind(&mut merged, n, |(_, _, rec)| &rec.kind);
+    }
+    let hits = collapse_hits(merged, collapse, |(_, _, rec)| rec);
+    let mut cited = Vec::new();
+    for ((score, source, rec), n) in hits.into_iter().take(limit) {
+        println!(
+            "{score:.3}\t{}\t{}\t{}\t{}\t{}{}{}",
+            targets[source].0,
+            rec.kind,
+            rec.id,
+            rec.ts,
+            render(&rec.text),
+            collapsed_column(n),
+            explained_column(&rec, &target_scoring[source])
+        );
+        cited.push((rec.id, rec.ts, targets[source].0.as_str()));
+    }
+    if cite {
+        print_citations(&cited);
+    }
+    if show_cautions {
+        print_cautions(&targets, &text, render)?;
+    }
+    Ok(())
+}
+
+/// With `--cite`, maps each result to where it came from, so an agent can
+/// attribute what it says to specific memories: a `Citations:` line, then
+/// `index id ts source` per result, where `index` counts results from 1 in
+/// the order printed and `source` is the store.
+fn print_citations(cited: &[(u128, i64, &str)]) {
+    if cited.is_empty() {
+        return;
+    }
+    println!();
+    println!("Citations:");
+    for (n, (id, ts, source)) in cited.iter().enumerate() {
+        println!("{}\t{id}\t{ts}\t{source}", n + 1);
+    }
+}
+
+/// Lists the cautions relevant to the query after the hits, under a
+/// `Cautions:` line, as `similarity kind id ts text`. Remote stores are
+/// skipped; their servers leave cautions out of the hits as well.
+fn print_cautions(
+    targets: &[(String, PathBuf, Scoring, IndexKind)],
+    query_text: &str,
+    render: impl Fn(&str) -> String,
+) -> Result<(), &'static str> {
+    let mut found = Vec::new();
+    for (_, path, _, _) in targets {
+        if remote::store_url(path).is_none() {
+            found.extend(cautions::find(path, query_text).map_err(store_error(path, "read failed"))?);
+        }
+    }
+    if found.is_empty() {
+        return Ok(());
+    }
+    found.sort_by(|a, b| b.0.total_cmp(&a.0));
+    println!();
+    println!("Cautions:");
+    for (sim, rec) in found {
+        println!("{sim:.3}\t{}\t{}\t{}\t{}", rec.kind, rec.id, rec.ts, render(&rec.text));
+    }
+    Ok(())
+}
+
+/// Greedy clustering of ranked hits: each hit joins the first kept hit whose cosine
+/// similarity reaches `threshold`, otherwise it is kept. Returns the kept hits with the
+/// number of hits folded into each. Remote hits carry no vectors, so their text is re-embedded.
+fn collapse_hits<T>(
+    items: Vec<T>,
+    threshold: Option<f32>,
+    record: impl Fn(&T) -> &Record,
+) -> Vec<(T, usize)> {
+    let Some(threshold) = threshold else {
+        return items.into_iter().map(|item| (item, 0)).collect();
+    };
+    let mut kept: Vec<(T, usize, Vec<f32>)> = Vec::new();
+    for item in items {
+        let rec = record(&item);
+        let vector = if rec.vector.is_empty() {
+            embed_text(&rec.text)
+        } else {
+            rec.vector.clone()
+        };
+        match kept
+            .iter_mut()
+            .find(|(_, _, v)| cosine_sim(v, &vector) >= threshold)
+        {
+            Some(cluster) => cluster.1 += 1,
+            None => kept.push((item, 0, vector)),
+        }
+    }
+    kept.into_iter().map(|(item, n, _)| (item, n)).collect()
+}
+
+/// Named queries kept in the `[saved]` table of the config file.
+fn cmd_saved(args: &[String]) -> Result<(), &'static str> {
+    let config_error = |e: io::Error| {
+        eprintln!("{}: {e}", config::config_path().display());
+        "config failed"
+    };
+    let mut cfg = config::load_config().map_err(config_error)?;
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("add"), Some(name)) => {
+            let Some(query) = args.get(2) else {
+                eprintln!("Usage: saved add <name> <query>");
+                return Err("missing query");
+            };
+            if let Err(e) = query::parse_query(query) {
+                eprintln!("{e}");
+                return Err("invalid query");
+            }
+            cfg.saved.insert(name.clone(), query.clone());
+            config::save_config(&cfg).map_err(config_error)
+        }
+        (Some("run"), Some(name)) => {
+            let Some(query) = cfg.saved.remove(name.as_str()) else {
+                eprintln!("Unknown saved search: {name}");
+                return Err("unknown saved search");
+            };
+            let mut search_args = vec!["--query".to_string(), query];
+            search_args.extend_from_slice(&args[2..]);
+            cmd_search(&search_args)
+        }
+        (Some("rm"), Some(name)) => {
+            if cfg.saved.remove(name.as_str()).is_none() {
+                eprintln!("Unknown saved search: {name}");
+                return Err("unknown saved search");
+            }
+            config::save_config(&cfg).map_err(config_error)
+        }
+        (Some("list"), _) => {
+            for (name, query) in &cfg.saved {
+                println!("{name}\t{query}");
+            }
+            Ok(())
+        }
+        _ => {
+            eprintln!("Usage: saved add <name> <query> | run <name> [search options] | list | rm <name>");
+            Err("unknown saved command")
+        }
+    }
+}
+
+/// Literal/regex scan over record text; no embedding or index involved.
+fn cmd_grep(args: &[String]) -> Result<(), &'static str> {
+    let mut pattern: Option<String> = None;
+    let mut ignore_case = false;
+    let mut limit: Option<usize> = None;
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--pattern" => {
+                i += 1;
+                pattern = args.get(i).cloned();
+            }
+            "--ignore-case" | "-i" => ignore_case = true,
+            "--limit" => {
+                i += 1;
+                limit = Some(parse_flag("--limit", args.get(i))?);
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let Some(pattern) = pattern else {
+        eprintln!("Missing --pattern");
+        return Err("missing pattern");
+    };
+    let re = regex::RegexBuilder::new(&pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| {
+            eprintln!("Invalid pattern: {e}");
+            "invalid pattern"
+        })?;
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let aliases = config::load_config()
+        .map_err(store_error(&config::config_path(), "read failed"))?
+        .kind_aliases;
+    kinds::resolve_streamed(&mut filter, &path, &aliases)
+        .map_err(store_error(&path, "read failed"))?;
+    // Streamed, so a store larger than memory can be searched; a checksum
+    // error surfaces after the matches before it were printed.
+    let records = stream_records(&path).map_err(store_error(&path, "read failed"))?;
+    let mut shown = 0;
+    for rec in records {
+        let rec = rec.map_err(store_error(&path, "read failed"))?;
+        if shown == limit.unwrap_or(usize::MAX) {
+            break;
+        }
+        if filter.matches(&rec) && re.is_match(&rec.text) {
+            println!(
+                "{}\t{}\t{}\t{}",
+                rec.kind,
+                rec.id,
+                rec.ts,
+                tsv_field(&rec.text)
+            );
+            shown += 1;
+        }
+    }
+    Ok(())
+}
+
+fn search_any(
+    path: &Path,
+    query: &str,
+    options: &SearchOptions,
+) -> Result<Vec<(f32, Record)>, &'static str> {
+    match remote::store_url(path) {
+        Some(url) => remote::search(url, query, options).map_err(|e| {
+            eprintln!("search failed: {e}");
+            "remote failed"
+        }),
+        None => search_store(path, query, options).map_err(store_error(path, "read failed")),
+    }
+}
+
+/// Prints one record in full, e.g. after `search --snippet` cut it short.
+fn cmd_get(args: &[String]) -> Result<(), &'static str> {
+    let mut id: Option<u128> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => {
+                let Ok(v) = other.parse() else {
+                    eprintln!("Invalid record id: {other}");
+                    return Err("invalid id");
+                };
+                id = Some(v);
+            }
+        }
+        i += 1;
+    }
+
+    let Some(id) = id else {
+        eprintln!("Missing record id");
+        return Err("missing id");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+
+    let rec = match remote::store_url(&path) {
+        Some(url) => remote::get(url, id).map_err(|e| {
+            eprintln!("get failed: {e}");
+            "remote failed"
+        })?,
+        None => get_record(&path, id).map_err(store_error(&path, "read failed"))?,
+    };
+    println!("id\t{}", rec.id);
+    println!("kind\t{}", rec.kind);
+    println!("ts\t{}", rec.ts);
+    println!("weight\t{}", rec.weight);
+    println!("tags\t{}", rec.tags.join(","));
+    for (key, value) in &rec.attrs {
+        println!("{key}\t{}", tsv_field(value));
+    }
+    println!();
+    println!("{}", rec.text);
+    Ok(())
+}
+
+/// Raw ANN neighbourhood of a stored record, without weight/recency blending,
+/// to tell embedder problems apart from scorer problems.
+fn cmd_neighbors(args: &[String]) -> Result<(), &'static str> {
+    let mut id: Option<u128> = None;
+    let mut k: usize = 10;
+    let mut show_distance = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--id" => {
+                i += 1;
+                id = Some(parse_flag("--id", args.get(i))?);
+            }
+            "-k" | "--k" => {
+                i += 1;
+                k = parse_flag("-k", args.get(i))?;
+            }
+            "--show-distance" => show_distance = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let Some(id) = id else {
+        eprintln!("Missing or invalid --id");
+        return Err("missing id");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let fingerprint = index_cache::Fingerprint::of(&path);
+    let records = load_store(&path).map_err(store_error(&path, "read failed"))?.records;
+    let Some(target) = records.iter().position(|r| r.id == id) else {
+        eprintln!("No record with id {id}");
+        return Err("unknown id");
+    };
+
+    let vecs = collect_vectors(&records);
+    let want = (k + 1).min(vecs.len());
+    let neighbours = index_cache::with_index(&path, fingerprint, &vecs, false, |hnsw| {
+        hnsw.search(&vecs[target], want, HNSW_EF_SEARCH.max(want))
+    });
+    for n in neighbours.into_iter().filter(|n| n.d_id != target).take(k) {
+        let rec = &records[n.d_id];
+        let text = tsv_field(&rec.text);
+        if show_distance {
+            println!("{:.4}\t{}\t{}\t{text}", n.distance, rec.kind, rec.id);
+        } else {
+            println!("{}\t{}\t{text}", rec.kind, rec.id);
+        }
+    }
+    Ok(())
+}
+
+fn cmd_recent(args: &[String]) -> Result<(), &'static str> {
+    let mut limit: usize = 20;
+    let mut follow = false;
+    let mut interval: f32 = 1.0;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--limit" => {
+                i += 1;
+                limit = parse_flag("--limit", args.get(i))?;
+            }
+            "--follow" | "-f" => follow = true,
+            "--interval" => {
+                i += 1;
+                interval = parse_float("--interval", args.get(i))?;
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    if follow {
+        if !(interval > 0.0 && interval.is_finite()) {
+            eprintln!("--interval must be a positive number of seconds");
+            return Err("invalid interval");
+        }
+        return follow::follow(&path, limit, Duration::from_secs_f32(interval));
+    }
+
+    let records = match remote::store_url(&path) {
+        Some(url) => remote::recent(url, limit).map_err(|e| {
+            eprintln!("recent failed: {e}");
+            "remote failed"
+        })?,
+        None => recent_records(&path, limit).map_err(store_error(&path, "read failed"))?,
+    };
+    for rec in records {
+        println!("{}\t{}\t{}\t{}", rec.kind, rec.id, rec.ts, tsv_field(&rec.text));
+    }
+    Ok(())
+}
+
+fn cmd_compact(args: &[String]) -> Result<(), &'static str> {
+    let mut keep: usize = 5000;
+    let mut consolidate_cmd: Option<String> = None;
+    let mut older_than: Option<String> = None;
+    let mut similarity: f32 = 0.85;
+    let mut dry_run = false;
+    let mut confirm = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--keep" => {
+                i += 1;
+                keep = parse_flag("--keep", args.get(i))?;
+            }
+            "--dry-run" => dry_run = true,
+            "--confirm" => confirm = true,
+            "--consolidate-cmd" => {
+                i += 1;
+                consolidate_cmd = args.get(i).cloned();
+            }
+            "--older-than" => {
+                i += 1;
+                older_than = args.get(i).cloned();
+            }
+            "--similarity" => {
+                i += 1;
+                similarity = parse_float("--similarity", args.get(i))?;
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+
+    if let Some(url) = remote::store_url(&path) {
+        if dry_run || confirm {
+            eprintln!("--dry-run and --confirm only work on local stores");
+            return Err("remote unsupported");
+        }
+        remote::compact(url, keep).map_err(|e| {
+            eprintln!("compact failed: {e}");
+            "remote failed"
+        })?;
+        return Ok(());
+    }
+
+    if dry_run {
+        if consolidate_cmd.is_some() {
+            eprintln!("Note: --consolidate-cmd is not run under --dry-run");
+        }
+        let doomed = compaction_preview(&path, keep).map_err(store_error(&path, "read failed"))?;
+        print_removal_report(&doomed);
+        return Ok(());
+    }
+
+    // Like compaction, consolidation stays within the newest shard.
+    let target = if shards::is_sharded(&path) {
+        let newest = shards::newest(&path).map_err(store_error(&path, "read failed"))?;
+        newest.unwrap_or_else(|| path.clone())
+    } else {
+        path.clone()
+    };
+
+    // Under --confirm nothing changes, consolidation included, until the answer
+    // is yes; then exactly the records listed are removed.
+    let confirmed: Option<HashSet<u128>> = if confirm {
+        let doomed = compaction_preview(&path, keep).map_err(store_error(&path, "read failed"))?;
+        if doomed.is_empty() && consolidate_cmd.is_none() {
+            eprintln!("Nothing to remove");
+            return Ok(());
+        }
+        print_removal_report(&doomed);
+        if consolidate_cmd.is_some() {
+            eprint!("Run --consolidate-cmd, then remove these {} records? [y/N] ", doomed.len());
+        } else {
+            eprint!("Remove these {} records? [y/N] ", doomed.len());
+        }
+        let mut answer = String::new();
+        io::stdin().read_line(&mut answer).map_err(|_| "read failed")?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            eprintln!("Nothing was removed");
+            return Err("not confirmed");
+        }
+        Some(doomed.into_iter().map(|r| r.id).collect())
+    } else {
+        None
+    };
+
+    if let Some(cmd) = consolidate_cmd {
+        let min_age = match older_than.as_deref() {
+            Some(s) => filter::parse_duration(s).ok_or_else(|| {
+                eprintln!("Invalid --older-than: {s} (use e.g. 30d, 12h)");
+                "invalid duration"
+            })?,
+            None => 30 * 86400,
+        };
+        let done = consolidate::consolidate(&target, &cmd, min_age, similarity)
+            .map_err(store_error(&target, "consolidation failed"))?;
+        eprintln!(
+            "Consolidated {} records into {} summaries",
+            done.replaced, done.groups
+        );
+    }
+
+    match confirmed {
+        // Records consolidation already replaced are simply no longer there.
+        Some(ids) if !ids.is_empty() => {
+            update_store(&target, |store| {
+                store.records.retain(|r| !ids.contains(&r.id));
+                Ok(())
+            })
+            .map_err(store_error(&target, "write failed"))?;
+        }
+        Some(_) => {}
+        None => {
+            compact_store(&path, keep).map_err(store_error(&path, "write failed"))?;
+        }
+    }
+    Ok(())
+}
+
+/// The records `compact_store(path, keep)` would drop, chosen the same way.
+fn compaction_preview(path: &Path, keep: usize) -> io::Result<Vec<Record>> {
+    if shards::is_sharded(path) {
+        return match shards::newest(path)? {
+            Some(shard) => compaction_preview(&shard, keep),
+            None => Ok(Vec::new()),
+        };
+    }
+    let mut records = load_store(path)?.records;
+    records.sort_by_key(|r| std::cmp::Reverse(r.ts));
+    Ok(records.split_off(keep.min(records.len())))
+}
+
+/// One `id kind ts` line per record on stdout, then counts by kind and by age
+/// on stderr.
+fn print_removal_report(records: &[Record]) {
+    for rec in records {
+        println!("{}\t{}\t{}", rec.id, rec.kind, rec.ts);
+    }
+    let mut by_kind: BTreeMap<&str, usize> = BTreeMap::new();
+    for rec in records {
+        *by_kind.entry(rec.kind.as_str()).or_default() += 1;
+    }
+    const AGES: [(i64, &str); 5] = [
+        (86400, "< 1d"),
+        (7 * 86400, "1d-7d"),
+        (30 * 86400, "7d-30d"),
+        (365 * 86400, "30d-1y"),
+        (i64::MAX, ">= 1y"),
+    ];
+    let now = now_secs();
+    let mut by_age = [0usize; AGES.len()];
+    for rec in records {
+        let age = now - rec.ts;
+        let bucket = AGES.iter().position(|(max, _)| age < *max).unwrap_or(AGES.len() - 1);
+        by_age[bucket] += 1;
+    }
+    eprintln!("Would remove {} records", records.len());
+    for (kind, n) in by_kind {
+        eprintln!("  kind {kind}\t{n}");
+    }
+    for ((_, label), n) in AGES.iter().zip(by_age) {
+        if n > 0 {
+            eprintln!("  age {label}\t{n}");
+        }
+    }
+}
+
+/// The existing record closest to a newly added one.
+#[derive(Clone, Copy, Debug)]
+struct Nearest {
+    id: u128,
+    similarity: f32,
+}
+
+/// Adds text cut to `max_text`. A truncated record notes its original length in
+/// `truncated_from`; chunks are numbered in `chunk` (`2/5`) and point at the
+/// first one with `chunk_of`; every chunk gets `attrs`. Returns the first
+/// record and its nearest neighbour.
+fn add_fitted(
+    path: &Path,
+    fitted: oversize::Fitted,
+    kind: String,
+    weight: Option<f32>,
+    tags: Vec<String>,
+    attrs: BTreeMap<String, String>,
+    expected_generation: Option<u64>,
+) -> io::Result<(Record, Option<Nearest>, u64)> {
+    let n = fitted.pieces.len();
+    let mut first: Option<(Record, Option<Nearest>)> = None;
+    let mut generation = 0;
+    for (i, text) in fitted.pieces.into_iter().enumerate() {
+        let mut attrs = attrs.clone();
+        if fitted.truncated {
+            attrs.insert("truncated_from".to_string(), fitted.original_len.to_string());
+        }
+        if n > 1 {
+            attrs.insert("chunk".to_string(), format!("{}/{n}", i + 1));
+        }
+        if let Some((head, _)) = &first {
+            attrs.insert("chunk_of".to_string(), head.id.to_string());
+        }
+        // Only the first chunk is checked; the rest follow the write it made.
+        let expected = if first.is_none() { expected_generation } else { None };
+        let (rec, nearest, written) =
+            add_memory(path, text, kind.clone(), weight, tags.clone(), attrs, expected)?;
+        generation = written;
+        first.get_or_insert((rec, nearest));
+    }
+    let (rec, nearest) =
+        first.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty text"))?;
+    Ok((rec, nearest, generation))
+}
+
+/// Appends a record, with its language in the `lang` attribute when it can be
+/// told (see `lang.rs`). Without a `weight`, one is estimated from the text and
+/// the existing records (see `importance.rs`). Also returns the closest record
+/// that was already stored, if any, and the store's new generation.
+fn add_memory(
+    path: &Path,
+    text: String,
+    kind: String,
+    weight: Option<f32>,
+    tags: Vec<String>,
+    mut attrs: BTreeMap<String, String>,
+    expected_generation: Option<u64>,
+) -> io::Result<(Record, Option<Nearest>, u64)> {
+    if shards::is_sharded(path) {
+        let shard = shards::current(path)?;
+        return add_memory(&shard, text, kind, weight, tags, attrs, expected_generation);
+    }
+    if let Some(lang) = lang::detect(&text) {
+        attrs.entry(lang::ATTR.to_string()).or_insert_with(|| lang.to_string());
+    }
+    let mut record = Record {
+        id: now_millis(),
+        ts: now_secs(),
+        kind,
+        weight: weight.unwrap_or(1.0),
+        vector: embed_text(&text),
+        text,
+        tags,
+        attrs,
+    };
+    let ((record, nearest), generation) = update_store_if(path, expected_generation, |store| {
+        // Ids are wall-clock millis; adds within the same millisecond take the next free one.
+        while store.records.iter().any(|r| r.id == record.id) {
+            record.id += 1;
+        }
+        if weight.is_none() {
+            record.weight =
+                importance::estimate(&record.text, &record.kind, &record.vector, &store.records);
+        }
+        let nearest = store
+            .records
+            .iter()
+            .map(|r| Nearest {
+                id: r.id,
+                similarity: cosine_sim(&record.vector, &r.vector),
+            })
+            .max_by(|a, b| a.similarity.total_cmp(&b.similarity));
+        store.records.push(record.clone());
+        Ok((record, nearest))
+    })?;
+    Ok((record, nearest, generation))
+}
+
+/// The generation of the store at `path`; for a sharded store, of the shard
+/// that takes new records.
+fn store_generation(path: &Path) -> io::Result<u64> {
+    if shards::is_sharded(path) {
+        return store_generation(&shards::current(path)?);
+    }
+    Ok(load_store(path)?.generation)
+}
+
+/// Filters run before vector ranking, so excluded records never take a
+/// candidate slot from the ANN search.
+/// `query` may use the filter syntax from `query.rs`; its filters narrow `options.filter`.
+fn search_store(path: &Path, query: &str, options: &SearchOptions) -> io::Result<Vec<(f32, Record)>> {
+    if shards::is_sharded(path) {
+        return shards::search(path, query, options);
+    }
+    let parsed = query::parse_query(query)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let query_vec = parsed.vector();
+    let mut filter = options.filter.clone();
+    filter.merge(parsed.filter);
+    if let Some(age) = options.max_age {
+        if filter.since.is_none() && filter.until.is_none() && !options.include_archive {
+            filter.since = Some(filter.as_of.unwrap_or_else(now_secs) - age);
+        }
+    }
+    // Over the memory budget, scan the file record by record (see `spill.rs`).
+    let over_budget = options
+        .max_memory
+        .is_some_and(|max| fs::metadata(path).is_ok_and(|m| m.len() > max));
+    let mut scored = if over_budget {
+        spill::search(path, &query_vec, &filter, options)?
+    } else {
+        search_loaded(path, &query_vec, filter, options)?
+    };
+    if options.include_archive {
+        let started = Instant::now();
+        scored.extend(archive::search(path, query, options)?);
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        if options.timing {
+            print_timing("archive", started);
+        }
+    }
+    if let Some(n) = options.per_kind {
+        cap_per_kind(&mut scored, n, |(_, rec)| &rec.kind);
+    }
+    scored.truncate(options.limit);
+    Ok(scored)
+}
+
+/// Loads the store whole and ranks through its index.
+fn search_loaded(
+    path: &Path,
+    query_vec: &[f32],
+    mut filter: RecordFilter,
+    options: &SearchOptions,
+) -> io::Result<Vec<(f32, Record)>> {
+    let started = Instant::now();
+    let fingerprint = index_cache::Fingerprint::of(path);
+    let records = load_store(path)?.records;
+    if options.timing {
+        print_timing("load", started);
+    }
+    kinds::resolve(&mut filter, &records, &config::load_config()?.kind_aliases);
+    // Cautions are listed beside the hits (see `cautions.rs`) unless asked for by kind.
+    let with_cautions = filter.kind.as_deref() == Some(cautions::CAUTION_KIND);
+    let matching: Vec<usize> = (0..records.len())
+        .filter(|&i| filter.matches(&records[i]))
+        .filter(|&i| with_cautions || records[i].kind != cautions::CAUTION_KIND)
+        .collect();
+    let candidates = candidate_indices(
+        path,
+        fingerprint,
+        query_vec,
+        &records,
+        &matching,
+        options,
+    );
+    let started = Instant::now();
+    // Recency is measured from --as-of, so old searches rank as they would have then.
+    let now = filter.as_of.unwrap_or_else(now_secs);
+    let scored = score_records(query_vec, &records, &candidates, &options.scoring, now);
+    if options.timing {
+        print_timing("score", started);
+    }
+    Ok(scored)
+}
+
+/// Keeps the first `n` items of each kind, preserving order.
+fn cap_per_kind<T>(items: &mut Vec<T>, n: usize, kind: impl Fn(&T) -> &String) {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    items.retain(|item| {
+        let count = seen.entry(kind(item).clone()).or_insert(0);
+        *count += 1;
+        *count <= n
+    });
+}
+
+fn get_record(path: &Path, id: u128) -> io::Result<Record> {
+    if shards::is_sharded(path) {
+        for shard in shards::in_window(path, None)? {
+            if let Some(rec) = load_store(&shard)?.records.into_iter().find(|r| r.id == id) {
+                return Ok(rec);
+            }
+        }
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no record with id {id}")));
+    }
+    load_store(path)?
+        .records
+        .into_iter()
+        .find(|r| r.id == id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no record with id {id}")))
+}
+
+fn recent_records(path: &Path, limit: usize) -> io::Result<Vec<Record>> {
+    let mut records = Vec::new();
+    if shards::is_sharded(path) {
+        // Shards are per month, so older ones only matter if newer ones run short.
+        for shard in shards::in_window(path, None)? {
+            if records.len() >= limit {
+                break;
+            }
+            records.extend(load_store(&shard)?.records);
+        }
+    } else {
+        records = load_store(path)?.records;
+    }
+    records.sort_by_key(|r| std::cmp::Reverse(r.ts));
+    records.truncate(limit);
+    Ok(records)
+}
+
+/// Keeps the `keep` newest records and returns the ids of the ones dropped.
+/// A sharded store only compacts its newest shard.
+fn compact_store(path: &Path, keep: usize) -> io::Result<Vec<u128>> {
+    if shards::is_sharded(path) {
+        return match shards::newest(path)? {
+            Some(shard) => compact_store(&shard, keep),
+            None => Ok(Vec::new()),
+        };
+    }
+    update_store(path, |store| {
+        store.records.sort_by_key(|r| std::cmp::Reverse(r.ts));
+        let removed = store.records.split_off(keep.min(store.records.len()));
+        Ok(removed.into_iter().map(|r| r.id).collect())
+    })
+}
+
+/// Compacts a store that outgrew `limits` down to 90% of them, so the next few
+/// adds do not compact again, and returns the removed ids. A sharded store
+/// is judged by its newest shard.
+fn auto_compact(path: &Path, limits: &config::AutoCompact) -> io::Result<Vec<u128>> {
+    let target = if shards::is_sharded(path) {
+        match shards::newest(path)? {
+            Some(shard) => shard,
+            None => return Ok(Vec::new()),
+        }
+    } else {
+        path.to_path_buf()
+    };
+    let bytes = fs::metadata(&target).map_or(0, |m| m.len());
+    let over_bytes = limits.byte_limit().is_some_and(|max| bytes > max);
+    // Counting records means loading the store; skip that when only size is limited.
+    if !over_bytes && limits.max_records.is_none() {
+        return Ok(Vec::new());
+    }
+    let records = load_store(&target)?.records.len();
+    let over_records = limits.max_records.is_some_and(|max| records > max);
+    if !over_bytes && !over_records {
+        return Ok(Vec::new());
+    }
+    // Compacting down to 90% of a limit leaves room before the next run; a tiny
+    // limit still keeps the newest record rather than emptying the store.
+    let mut keep = records;
+    if let Some(max) = limits.max_records {
+        keep = keep.min((max * 9 / 10).max(1));
+    }
+    if let Some(max) = limits.byte_limit() {
+        let per_record = (bytes / records.max(1) as u64).max(1);
+        keep = keep.min(((max / 10 * 9 / per_record) as usize).max(1));
+    }
+    compact_store(&target, keep)
+}
+
+fn cmd_count(args: &[String]) -> Result<(), &'static str> {
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let mut count = 0;
+    for rec in stream_records(&path).map_err(store_error(&path, "read failed"))? {
+        if filter.matches(&rec.map_err(store_error(&path, "read failed"))?) {
+            count += 1;
+        }
+    }
+    println!("{count}");
+    Ok(())
+}
+
+/// Parses an age such as `7d` into the cutoff timestamp `now - age`.
+fn parse_since(arg: Option<&String>) -> Result<i64, &'static str> {
+    let Some(arg) = arg else {
+        eprintln!("Missing value for --since");
+        return Err("missing value");
+    };
+    match filter::parse_duration(arg) {
+        Some(secs) => Ok(now_secs() - secs),
+        None => {
+            eprintln!("Invalid duration {arg:?} (expected e.g. 30m, 12h, 7d, 2w)");
+            Err("invalid duration")
+        }
+    }
+}
+
+/// `--as-of`: a date means the end of that day (UTC), a date and time is taken as given.
+fn parse_as_of(arg: Option<&String>) -> Result<i64, &'static str> {
+    let value = arg.map(String::as_str).unwrap_or("");
+    let as_of = match dates::parse_date(value) {
+        Some(day) => Some(day + 86400),
+        None => dates::parse_datetime(value),
+    };
+    as_of.ok_or_else(|| {
+        eprintln!("--as-of expects YYYY-MM-DD or YYYY-MM-DDTHH:MM[:SS][Z|±HH:MM]");
+        "invalid date"
+    })
+}
+
+/// Parses the value of a numeric flag. A missing or malformed value is an error
+/// instead of quietly becoming a default.
+fn parse_flag<T: FromStr>(flag: &str, value: Option<&String>) -> Result<T, &'static str> {
+    let Some(value) = value else {
+        eprintln!("Missing value for {flag}");
+        return Err("missing value");
+    };
+    value.trim().parse().map_err(|_| {
+        eprintln!("Invalid {flag} {value:?} (expected a number)");
+        "invalid number"
+    })
+}
+
+/// `parse_flag` for floats, which also rejects NaN and infinities.
+fn parse_float(flag: &str, value: Option<&String>) -> Result<f32, &'static str> {
+    let v: f32 = parse_flag(flag, value)?;
+    if !v.is_finite() {
+        eprintln!("Invalid {flag} {v} (expected a finite number)");
+        return Err("invalid number");
+    }
+    Ok(v)
+}
+
+fn cmd_init(args: &[String]) -> Result<(), &'static str> {
+    let mut name = String::new();
+    let mut description = String::new();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--name" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    name = v.clone();
+                }
+            }
+            "--description" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    description = v.clone();
+                }
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let _lock = lock_store(&path).map_err(store_error(&path, "lock failed"))?;
+    if path.exists() {
+        eprintln!("{} already exists; use `meta set` to change its metadata", path.display());
+        return Err("store exists");
+    }
+
+    let mut store = new_store();
+    store.meta.name = name;
+    store.meta.description = description;
+    save_store(&path, &store).map_err(|_| "write failed")?;
+    Ok(())
+}
+
+fn cmd_meta(args: &[String]) -> Result<(), &'static str> {
+    if args.first().map(String::as_str) != Some("set") {
+        eprintln!("Usage: meta set [--name <name>] [--description <text>] [--path <file>]");
+        return Err("unknown meta command");
+    }
+    let mut name: Option<String> = None;
+    let mut description: Option<String> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--name" => {
+                i += 1;
+                name = args.get(i).cloned();
+            }
+            "--description" => {
+                i += 1;
+                description = args.get(i).cloned();
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    update_store(&path, |store| {
+        if let Some(name) = name {
+            store.meta.name = name;
+        }
+        if let Some(description) = description {
+            store.meta.description = description;
+        }
+        Ok(())
+    })
+    .map_err(store_error(&path, "write failed"))?;
+    Ok(())
+}
+
+fn cmd_stats(args: &[String]) -> Result<(), &'static str> {
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    if !path.exists() {
+        eprintln!("No such file: {}", path.display());
+        return Err("missing store");
+    }
+    let (store, version) =
+        load_store_versioned(&path).map_err(store_error(&path, "read failed"))?;
+    let bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    let kinds: HashSet<&str> = store.records.iter().map(|r| r.kind.as_str()).collect();
+    let oldest = store.records.iter().map(|r| r.ts).min();
+    let newest = store.records.iter().map(|r| r.ts).max();
+    let created = match store.meta.created_at {
+        0 => "-".to_string(),
+        ts => ts.to_string(),
+    };
+
+    println!("path\t{}", path.display());
+    println!("name\t{}", store.meta.name);
+    println!("description\t{}", store.meta.description);
+    println!("created_at\t{created}");
+    println!("version\t{version}");
+    println!("generation\t{}", store.generation);
+    println!("vector_dim\t{}", store.vector_dim);
+    println!("records\t{}", store.records.len());
+    println!("kinds\t{}", kinds.len());
+    println!("bytes\t{bytes}");
+    println!("oldest\t{}", oldest.map(|t| t.to_string()).unwrap_or_else(|| "-".into()));
+    println!("newest\t{}", newest.map(|t| t.to_string()).unwrap_or_else(|| "-".into()));
+    Ok(())
+}
+
+fn cmd_upgrade(args: &[String]) -> Result<(), &'static str> {
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    if !path.exists() {
+        eprintln!("No such file: {}", path.display());
+        return Err("missing store");
+    }
+    let _lock = lock_store(&path).map_err(store_error(&path, "lock failed"))?;
+    let (store, version) =
+        load_store_versioned(&path).map_err(store_error(&path, "read failed"))?;
+    if version == STORE_VERSION {
+        println!("{} is already at version {STORE_VERSION}", path.display());
+        return Ok(());
+    }
+
+    // Keep the original bytes until the user is happy with the migrated file.
+    let backup = sibling_path(&path, &format!(".v{version}.bak"));
+    fs::copy(&path, &backup).map_err(|_| "backup failed")?;
+    save_store(&path, &store).map_err(|_| "write failed")?;
+    println!(
+        "upgraded {} from version {version} to {STORE_VERSION} (backup: {})",
+        path.display(),
+        backup.display()
+    );
+    Ok(())
+}
+
+fn cmd_rekey(args: &[String]) -> Result<(), &'static str> {
+    let mut key_file: Option<PathBuf> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--new-key-file" => {
+                i += 1;
+                key_file = args.get(i).map(|v| path_arg(v));
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let Some(key_file) = key_file else {
+        eprintln!("Missing --new-key-file");
+        return Err("missing key file");
+    };
+    let mut new_key = fs::read(&key_file).map_err(store_error(&key_file, "read failed"))?;
+    while new_key.last().is_some_and(|b| b.is_ascii_whitespace()) {
+        new_key.pop();
+    }
+    if new_key.is_empty() {
+        eprintln!("{} is empty", key_file.display());
+        return Err("empty key");
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    if !path.exists() {
+        eprintln!("No such file: {}", path.display());
+        return Err("missing store");
+    }
+
+    // Everything is checked against the current key before anything is rewritten,
+    // so a wrong MEMSTORE_SIGNING_KEY leaves all files untouched.
+    let _lock = lock_store(&path).map_err(store_error(&path, "lock failed"))?;
+    let mut files = vec![path.clone()];
+    for version in 1..STORE_VERSION {
+        let backup = sibling_path(&path, &format!(".v{version}.bak"));
+        if backup.exists() {
+            files.push(backup);
+        }
+    }
+    for file in &files {
+        let report = verify_store(file).map_err(store_error(file, "read failed"))?;
+        if !report.problems.is_empty() || report.signature == Signature::NoKey {
+            eprintln!(
+                "{}: does not verify with the current MEMSTORE_SIGNING_KEY; nothing was changed",
+                file.display()
+            );
+            return Err("verify failed");
+        }
+    }
+    for file in &files {
+        match resign_file(file, &new_key).map_err(store_error(file, "rekey failed"))? {
+            true => println!("signed\t{}", file.display()),
+            false => println!("skipped\t{} (legacy file without header)", file.display()),
+        }
+    }
+    eprintln!("Set MEMSTORE_SIGNING_KEY to the new key before the next write");
+    Ok(())
+}
+
+fn cmd_verify(args: &[String]) -> Result<(), &'static str> {
+    let mut strict = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--strict" => strict = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let mut report = verify_store(&path).map_err(store_error(&path, "read failed"))?;
+    // --strict only accepts a store whose signature checks out with the configured key.
+    match report.signature {
+        Signature::Valid => println!("note\tsignature valid"),
+        Signature::Unsigned if strict => report.problems.push("store is not signed".to_string()),
+        Signature::NoKey if strict => report
+            .problems
+            .push("store is signed but MEMSTORE_SIGNING_KEY is not set".to_string()),
+        Signature::NoKey => {
+            println!("note\tsigned; set MEMSTORE_SIGNING_KEY to check the signature")
+        }
+        Signature::Missing => report
+            .problems
+            .push("store is not signed but MEMSTORE_SIGNING_KEY is set; sign it with rekey".to_string()),
+        // A mismatch is already among the problems.
+        Signature::Unsigned | Signature::Invalid => {}
+    }
+    for problem in &report.problems {
+        println!("error\t{problem}");
+    }
+    if !report.checksummed {
+        println!("note\tno checksums stored; they are added on the next write");
+    }
+    if !report.problems.is_empty() {
+        eprintln!("{}: {} problem(s) found", path.display(), report.problems.len());
+        return Err("verify failed");
+    }
+    println!(
+        "ok\t{}: version {}, {} records",
+        path.display(),
+        report.version,
+        report.records
+    );
+    Ok(())
+}
+
+/// Prints an I/O error against the store path before mapping it to the command error.
+fn store_error<'a>(path: &'a Path, what: &'static str) -> impl FnOnce(io::Error) -> &'static str + 'a {
+    move |e| {
+        eprintln!("{}: {e}", path.display());
+        what
+    }
+}
+
+/// `text` as a single TSV field: tabs, carriage returns and newlines become
+/// spaces so they cannot split the row or the line.
+fn tsv_field(text: &str) -> String {
+    text.replace(['\t', '\r', '\n'], " ")
+}
+
+fn local_only(path: &Path) -> Result<(), &'static str> {
+    if remote::store_url(path).is_some() {
+        eprintln!("This command only works on local stores");
+        return Err("remote unsupported");
+    }
+    if shards::is_sharded(path) {
+        eprintln!("{} is sharded; pass one of its shards with --path", path.display());
+        return Err("sharded store");
+    }
+    Ok(())
+}
+
+/// `--path` wins over the profile's path, which wins over `MEMSTORE_PATH`.
+fn resolve_path(path: Option<PathBuf>, profile: &Profile) -> PathBuf {
+    path.or_else(|| profile.path.clone())
+        .unwrap_or_else(default_path)
+}
+
+/// `MEMSTORE_PATH`, a project's `.memstore/`, an existing `memory/memories.hnsw`
+/// in the cwd (the old default), then the per-user data directory.
+fn default_path() -> PathBuf {
+    if let Some(p) = env::var_os("MEMSTORE_PATH") {
+        return PathBuf::from(p);
+    }
+    if let Some(dir) = find_project_dir() {
+        return dir.join("memories.hnsw");
+    }
+    let legacy = Path::new("memory").join("memories.hnsw");
+    if legacy.exists() {
+        return legacy;
+    }
+    config::data_dir().unwrap_or_else(|| PathBuf::from("memory")).join("memories.hnsw")
+}
+
+/// Walks up from the cwd looking for a `.memstore/` directory, the way git finds `.git/`.
+fn find_project_dir() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .map(|dir| dir.join(".memstore"))
+        .find(|candidate| candidate.is_dir())
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut buf = String::new();
+    for ch in text.chars() {
+        if ch.is_alphanumeric() {
+            buf.push(ch.to_ascii_lowercase());
+        } else if !buf.is_empty() {
+            tokens.push(buf.clone());
+            buf.clear();
+        }
+    }
+    if !buf.is_empty() {
+        tokens.push(buf);
+    }
+    tokens
+}
+
+/// Scores the records at `candidates` (positions in `records`), best first,
+/// with ages measured from `now`.
+fn score_records(
+    query_vec: &[f32],
+    records: &[Record],
+    candidates: &HashSet<usize>,
+    scoring: &Scoring,
+    now: i64,
+) -> Vec<(f32, Record)> {
+    let mut scored: Vec<(f32, Record)> = records
+        .iter()
+        .enumerate()
+        .filter(|(idx, _)| candidates.contains(idx))
+        .map(|(_, rec)| (score_record(query_vec, rec, scoring, now), rec.clone()))
+        .collect();
+
+    scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    scored
+}
+
+/// Similarity, weight and recency of one record, times its kind's boost.
+fn score_record(query_vec: &[f32], rec: &Record, scoring: &Scoring, now: i64) -> f32 {
+    score_parts(query_vec, rec, scoring, now).raw(scoring)
+}
+
+/// The inputs `score_record` combines, shown by `search --explain`.
+struct ScoreParts {
+    cosine: f32,
+    weight: f32,
+    recency: f32,
+    boost: f32,
+}
+
+impl ScoreParts {
+    fn raw(&self, scoring: &Scoring) -> f32 {
+        let score = self.cosine * scoring.similarity
+            + self.weight * scoring.weight
+            + self.recency * scoring.recency;
+        score * self.boost
+    }
+}
+
+fn score_parts(query_vec: &[f32], rec: &Record, scoring: &Scoring, now: i64) -> ScoreParts {
+    // Remote hits come without vectors.
+    let cosine = if rec.vector.is_empty() {
+        cosine_sim(query_vec, &embed_text(&rec.text))
+    } else {
+        cosine_sim(query_vec, &rec.vector)
+    };
+    let age_days = ((now - rec.ts).max(0) as f32) / 86400.0;
+    let decay = scoring.kind_decay.get(&rec.kind).unwrap_or(&scoring.decay);
+    ScoreParts {
+        cosine,
+        weight: rec.weight,
+        recency: decay.factor(age_days),
+        boost: scoring.kind_boost.get(&rec.kind).copied().unwrap_or(1.0),
+    }
+}
+
+/// A raw score as relevance from 0 to 1: the fraction of what a record of
+/// weight 1.0, identical to the query and added just now would score under
+/// the same multipliers, clamped. Linear in the raw score, so it ranks the
+/// same; heavier or kind-boosted records can reach 1.0 short of a perfect
+/// match.
+fn calibrate(raw: f32, scoring: &Scoring) -> f32 {
+    let recency = if scoring.decay == Decay::Off {
+        0.0
+    } else {
+        scoring.recency
+    };
+    let best = scoring.similarity + scoring.weight + recency;
+    if best <= 0.0 {
+        return 0.0;
+    }
+    (raw / best).clamp(0.0, 1.0)
+}
+
+fn collect_vectors(records: &[Record]) -> Vec<Vec<f32>> {
+    records.iter().map(|rec| rec.vector.clone()).collect()
+}
+
+/// Positions in `records` worth scoring, drawn from `matching` (sorted positions
+/// that pass the filters). Large stores go through the cached index over all
+/// records, restricted to `matching`.
+fn candidate_indices(
+    path: &Path,
+    fingerprint: Option<index_cache::Fingerprint>,
+    query_vec: &[f32],
+    records: &[Record],
+    matching: &[usize],
+    options: &SearchOptions,
+) -> HashSet<usize> {
+    let k = (options.limit.saturating_mul(10)).max(10).min(matching.len());
+    // A filter-only query embeds to the zero vector, which has no neighbours; rank everything.
+    if matching.len() <= k || query_vec.iter().all(|v| *v == 0.0) {
+        return matching.iter().copied().collect();
+    }
+    match options.index {
+        IndexKind::Hnsw => {}
+        IndexKind::Ivf => {
+            return ivf::search(path, fingerprint, records, query_vec, k, matching, options.timing)
+                .into_iter()
+                .collect();
+        }
+        IndexKind::Pq => {
+            let (rerank, timing) = (options.rerank, options.timing);
+            return pq::search(path, fingerprint, records, query_vec, k, matching, rerank, timing)
+                .into_iter()
+                .collect();
+        }
+    }
+
+    let vecs = collect_vectors(records);
+    let ef = HNSW_EF_SEARCH.max(k);
+    let search = |hnsw: &Hnsw<'_, f32, DistCosine>| {
+        if matching.len() == records.len() {
+            hnsw.search(query_vec, k, ef)
+        } else {
+            let allowed: Vec<usize> = matching.to_vec();
+            hnsw.search_filter(query_vec, k, ef, Some(&allowed))
+        }
+    };
+    let neighbours: Vec<Neighbour> =
+        index_cache::with_index(path, fingerprint, &vecs, options.timing, search);
+    neighbours.into_iter().map(|n| n.d_id).collect()
+}
+
+/// HNSW over `vecs`; neighbour `d_id`s are positions in `vecs`. Insertion is
+/// spread over all cores by `hnsw_rs`, in batches so progress can be shown.
+fn build_index(vecs: &[Vec<f32>]) -> Hnsw<'_, f32, DistCosine> {
+    let hnsw: Hnsw<f32, DistCosine> = Hnsw::new(
+        HNSW_M,
+        vecs.len(),
+        HNSW_NB_LAYER,
+        HNSW_EF_CONSTRUCTION,
+        DistCosine,
+    );
+    let points: Vec<(&Vec<f32>, usize)> = vecs.iter().zip(0..).collect();
+    let progress = progress::Progress::new("index build", points.len());
+    for batch in points.chunks(INSERT_BATCH) {
+        hnsw.parallel_insert(batch);
+        progress.inc(batch.len());
+    }
+    progress.finish();
+    hnsw
+}
+
+/// `search --timing` line: phase and elapsed milliseconds, on stderr.
+fn print_timing(phase: &str, started: Instant) {
+    eprintln!("timing\t{phase}\t{:.1} ms", started.elapsed().as_secs_f64() * 1000.0);
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+fn embed_text(text: &str) -> Vec<f32> {
+    let mut vec = vec![0.0f32; VECTOR_DIM];
+    let tokens = tokenize(text);
+    if tokens.is_empty() {
+        return vec;
+    }
+    for token in tokens.iter() {
+        let idx = (fnv1a_hash(token) % VECTOR_DIM as u64) as usize;
+        vec[idx] += 1.0;
+    }
+    normalize(&mut vec);
+    vec
+}
+
+/// `embed_text` with the tokens in `boosts` counted that many times, instead of
+/// once per occurrence (`word^2` in a query, see `query.rs`).
+fn embed_weighted(text: &str, boosts: &BTreeMap<String, f32>) -> Vec<f32> {
+    if boosts.is_empty() {
+        return embed_text(text);
+    }
+    let mut vec = vec![0.0f32; VECTOR_DIM];
+    for token in tokenize(text) {
+        if !boosts.contains_key(&token) {
+            vec[(fnv1a_hash(&token) % VECTOR_DIM as u64) as usize] += 1.0;
+        }
+    }
+    for (token, weight) in boosts {
+        vec[(fnv1a_hash(token) % VECTOR_DIM as u64) as usize] += weight;
+    }
+    normalize(&mut vec);
+    vec
+}
+
+fn normalize(vec: &mut [f32]) {
+    let mut sum = 0.0f32;
+    for v in vec.iter() {
+        sum += v * v;
+    }
+    let norm = sum.sqrt();
+    if norm > 0.0 {
+        for v in vec.iter_mut() {
+            *v /= norm;
+        }
+    }
+}
+
+fn cosine_sim(a: &[f32], b: &[f32]) -> f32 {
+    let mut dot = 0.0f32;
+    let mut a_norm = 0.0f32;
+    let mut b_norm = 0.0f32;
+    for i in 0..a.len().min(b.len()) {
+        dot += a[i] * b[i];
+        a_norm += a[i] * a[i];
+        b_norm += b[i] * b[i];
+    }
+    if a_norm == 0.0 || b_norm == 0.0 {
+        0.0
+    } else {
+        dot / (a_norm.sqrt() * b_norm.sqrt())
+    }
+}
+
+fn fnv1a_hash(input: &str) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in input.as_bytes() {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
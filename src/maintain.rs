@@ -0,0 +1,135 @@
+//! `maintain`: the housekeeping steps in one pass, meant to run from cron.
+//!
+//! 1. compact per the `auto_compact` policy, if one is configured;
+//! 2. vacuum: remove `.tmp` files left behind by interrupted writes;
+//! 3. rebuild the search index for the profile's index kind if it is stale.
+//!
+//! Each step prints one `step\tresult` line. A sharded store is maintained
+//! through its newest shard, the only one that still changes.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::store::{load_store, lock_store, sibling_path};
+use crate::{
+    archive, auto_compact, collect_vectors, config, index_cache, ivf, path_arg, pq, remote,
+    resolve_path, shards, store_error, IndexKind,
+};
+
+pub fn cmd_maintain(args: &[String]) -> Result<(), &'static str> {
+    let mut index: Option<IndexKind> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--index" => {
+                i += 1;
+                index = match args.get(i).map(String::as_str) {
+                    Some("hnsw") => Some(IndexKind::Hnsw),
+                    Some("ivf") => Some(IndexKind::Ivf),
+                    Some("pq") => Some(IndexKind::Pq),
+                    _ => {
+                        eprintln!("--index must be hnsw, ivf or pq");
+                        return Err("invalid index");
+                    }
+                };
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    if remote::store_url(&path).is_some() {
+        eprintln!("This command only works on local stores");
+        return Err("remote unsupported");
+    }
+    if !path.exists() && !shards::is_sharded(&path) {
+        eprintln!("No such file: {}", path.display());
+        return Err("missing store");
+    }
+    let limits = config::auto_compact(&profile)?;
+    let index = index.or(profile.index).unwrap_or_default();
+
+    match limits {
+        Some(limits) => {
+            let removed = auto_compact(&path, &limits).map_err(store_error(&path, "compact failed"))?;
+            println!("compact\tremoved {} records", removed.len());
+        }
+        None => println!("compact\tskipped (no auto_compact policy)"),
+    }
+
+    let target = if shards::is_sharded(&path) {
+        match shards::newest(&path).map_err(store_error(&path, "read failed"))? {
+            Some(shard) => shard,
+            None => {
+                println!("index\tskipped (no shards)");
+                return Ok(());
+            }
+        }
+    } else {
+        path.clone()
+    };
+
+    let removed = vacuum(&target).map_err(store_error(&target, "vacuum failed"))?;
+    println!("vacuum\tremoved {removed} leftover files");
+
+    // The fingerprint is taken before loading, as in `search`.
+    let fingerprint = index_cache::Fingerprint::of(&target);
+    let records = load_store(&target)
+        .map_err(store_error(&target, "read failed"))?
+        .records;
+    if records.is_empty() {
+        println!("index\tskipped (empty store)");
+        return Ok(());
+    }
+    if fingerprint.is_none() {
+        println!("index\tskipped (store has no checksums; run `memstore upgrade`)");
+        return Ok(());
+    }
+    match index {
+        IndexKind::Hnsw => {
+            let vecs = collect_vectors(&records);
+            index_cache::with_index(&target, fingerprint, &vecs, false, |_| ());
+        }
+        IndexKind::Ivf => ivf::warm(&target, fingerprint, &records),
+        IndexKind::Pq => pq::warm(&target, fingerprint, &records),
+    }
+    let name = match index {
+        IndexKind::Hnsw => "hnsw",
+        IndexKind::Ivf => "ivf",
+        IndexKind::Pq => "pq",
+    };
+    println!("index\t{name} up to date ({} vectors)", records.len());
+    Ok(())
+}
+
+/// Removes the temp files `write_atomic` leaves when a write is interrupted.
+/// Writers hold the store lock while a temp file exists, so taking it here
+/// keeps a write in progress from losing its file.
+fn vacuum(path: &Path) -> io::Result<usize> {
+    let _lock = lock_store(path)?;
+    let mut removed = 0;
+    for file in [path.to_path_buf(), archive::archive_path(path)] {
+        let tmp = sibling_path(&file, ".tmp");
+        if tmp.exists() {
+            fs::remove_file(&tmp)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,78 @@
+//! `search --query A --query B [--combine avg|max]`: several phrasings of one
+//! question. The hash embedder only matches the words a phrasing uses, so each
+//! phrasing finds hits the others miss.
+//!
+//! Every query fetches its own candidates, with its own filters, and each
+//! candidate is then scored against every query: `avg` (the default) favours
+//! hits all phrasings agree on, `max` keeps whatever any one phrasing ranks
+//! highly. One query is a plain search.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::{cap_per_kind, query, score_record, search_any, Record, SearchOptions};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Combine {
+    #[default]
+    Avg,
+    Max,
+}
+
+impl Combine {
+    pub fn parse(s: &str) -> Option<Combine> {
+        match s {
+            "avg" => Some(Combine::Avg),
+            "max" => Some(Combine::Max),
+            _ => None,
+        }
+    }
+}
+
+/// Hits for `queries` at `path`, best first, with recency measured from `now`.
+pub fn search(
+    path: &Path,
+    queries: &[String],
+    combine: Combine,
+    options: &SearchOptions,
+    now: i64,
+) -> Result<Vec<(f32, Record)>, &'static str> {
+    if let [query] = queries {
+        return search_any(path, query, options);
+    }
+    let mut vectors = Vec::new();
+    let mut seen = HashSet::new();
+    let mut hits = Vec::new();
+    for query in queries {
+        let parsed = query::parse_query(query).map_err(|e| {
+            eprintln!("{e}");
+            "invalid query"
+        })?;
+        vectors.push(parsed.vector());
+        for (_, rec) in search_any(path, query, options)? {
+            if seen.insert(rec.id) {
+                hits.push(rec);
+            }
+        }
+    }
+
+    let mut scored: Vec<(f32, Record)> = hits
+        .into_iter()
+        .map(|rec| {
+            let scores = vectors
+                .iter()
+                .map(|v| score_record(v, &rec, &options.scoring, now));
+            let score = match combine {
+                Combine::Avg => scores.sum::<f32>() / vectors.len() as f32,
+                Combine::Max => scores.fold(f32::MIN, f32::max),
+            };
+            (score, rec)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    if let Some(n) = options.per_kind {
+        cap_per_kind(&mut scored, n, |(_, rec)| &rec.kind);
+    }
+    scored.truncate(options.limit);
+    Ok(scored)
+}
@@ -0,0 +1,115 @@
+//! Node.js binding, built into the `cdylib` with `--features node`: the
+//! surface of the C API in `ffi.rs` as a class, for TypeScript agents that
+//! would otherwise spawn `memstore` on every call. Copy the library to
+//! `memstore.node` and `require` it.
+//!
+//! Ids are `BigInt`s since they are 128-bit. Errors, panics included, throw
+//! a JavaScript `Error`. Calls are synchronous and each reads the store file,
+//! so handles in several processes see each other's writes, as with the C API.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use napi::bindgen_prelude::BigInt;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+use crate::server::{handle_add, handle_search, AddRequest, ApiHit, SearchRequest};
+use crate::store::load_store;
+
+/// A store file, opened with `new MemStore(path)`.
+#[napi]
+pub struct MemStore {
+    path: PathBuf,
+}
+
+/// A record as returned by `search`; the fields of the HTTP API's records.
+#[napi(object)]
+pub struct StoredRecord {
+    pub id: BigInt,
+    pub ts: i64,
+    pub kind: String,
+    pub weight: f64,
+    pub text: String,
+    pub tags: Vec<String>,
+    pub attrs: HashMap<String, String>,
+}
+
+/// One search hit, `{ score, record }` like `POST /stores/<name>/search`.
+#[napi(object)]
+pub struct Hit {
+    pub score: f64,
+    pub record: StoredRecord,
+}
+
+#[napi]
+impl MemStore {
+    /// Opens the store file at `path`, which is created on the first add.
+    /// Throws if an existing file cannot be read.
+    #[napi(constructor, catch_unwind)]
+    pub fn new(path: String) -> Result<Self> {
+        let path = PathBuf::from(path);
+        load_store(&path).map_err(|e| store_error(&path, e))?;
+        Ok(MemStore { path })
+    }
+
+    /// Adds a record and returns its id. `kind` defaults to `summary`; without
+    /// a `weight`, or with a negative one, memstore estimates it.
+    #[napi(catch_unwind)]
+    pub fn add(&self, text: String, kind: Option<String>, weight: Option<f64>) -> Result<BigInt> {
+        let req = AddRequest {
+            text,
+            kind: kind.unwrap_or_else(|| "summary".to_string()),
+            weight: weight.unwrap_or(1.0) as f32,
+            tags: Vec::new(),
+            attrs: BTreeMap::new(),
+            auto_weight: weight.is_none_or(|w| w < 0.0),
+            expected_generation: None,
+        };
+        let resp = handle_add(&self.path, req).map_err(|e| store_error(&self.path, e))?;
+        Ok(BigInt::from(resp.id))
+    }
+
+    /// The best `limit` hits (3 by default) for `query`, which may use the
+    /// query syntax.
+    #[napi(catch_unwind)]
+    pub fn search(&self, query: String, limit: Option<u32>) -> Result<Vec<Hit>> {
+        let req = SearchRequest {
+            query,
+            limit: limit.map_or(3, |n| n as usize),
+            must: Vec::new(),
+            must_not: Vec::new(),
+            per_kind: None,
+            kind: None,
+            tags: Vec::new(),
+            since: None,
+            until: None,
+            as_of: None,
+            lang: None,
+            max_age: None,
+        };
+        let hits = handle_search(&self.path, req).map_err(|e| store_error(&self.path, e))?;
+        Ok(hits.into_iter().map(hit).collect())
+    }
+}
+
+fn hit(hit: ApiHit) -> Hit {
+    let rec = hit.record;
+    Hit {
+        score: hit.score.into(),
+        record: StoredRecord {
+            id: BigInt::from(rec.id),
+            ts: rec.ts,
+            kind: rec.kind,
+            weight: rec.weight.into(),
+            text: rec.text,
+            tags: rec.tags,
+            attrs: rec.attrs.into_iter().collect(),
+        },
+    }
+}
+
+/// A JavaScript `Error` naming the store, like the CLI's messages.
+fn store_error(path: &std::path::Path, e: std::io::Error) -> Error {
+    Error::from_reason(format!("{}: {e}", path.display()))
+}
@@ -0,0 +1,82 @@
+//! Enforces `max_text` on added text: reject it, keep its head, or split it
+//! into chunks that are stored as separate, linked records.
+
+use crate::config::{OversizePolicy, TextLimit};
+
+/// Text cut to fit the limit, as one or more record texts.
+pub struct Fitted {
+    pub pieces: Vec<String>,
+    /// Length of the text as given, in bytes.
+    pub original_len: usize,
+    pub truncated: bool,
+}
+
+/// Applies `limit` to `text`. Under `reject`, an oversized text is an error
+/// describing the limit.
+pub fn fit(text: String, limit: Option<&TextLimit>) -> Result<Fitted, String> {
+    let original_len = text.len();
+    let Some(limit) = limit.filter(|l| text.len() > l.byte_limit()) else {
+        return Ok(Fitted {
+            pieces: vec![text],
+            original_len,
+            truncated: false,
+        });
+    };
+    let max = limit.byte_limit();
+    match limit.policy {
+        OversizePolicy::Reject => Err(format!(
+            "text is {original_len} bytes, over the max_text limit of {} ({max} bytes)",
+            limit.max_bytes
+        )),
+        OversizePolicy::Truncate => {
+            let mut text = text;
+            text.truncate(floor_char_boundary(&text, max));
+            Ok(Fitted {
+                pieces: vec![text],
+                original_len,
+                truncated: true,
+            })
+        }
+        OversizePolicy::Chunk => Ok(Fitted {
+            pieces: split(&text, max),
+            original_len,
+            truncated: false,
+        }),
+    }
+}
+
+/// Pieces of at most `max` bytes, cut after the last newline or else the last
+/// whitespace in each window, so words stay whole where possible.
+fn split(text: &str, max: usize) -> Vec<String> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while rest.len() > max {
+        let window = &rest[..floor_char_boundary(rest, max)];
+        let cut = window
+            .rfind('\n')
+            .or_else(|| window.rfind(char::is_whitespace))
+            .map(|at| at + window[at..].chars().next().map_or(1, char::len_utf8))
+            .unwrap_or(window.len());
+        // A single character wider than `max` still has to go somewhere.
+        let cut = if cut == 0 {
+            rest.chars().next().map_or(rest.len(), char::len_utf8)
+        } else {
+            cut
+        };
+        pieces.push(rest[..cut].trim_end().to_string());
+        rest = rest[cut..].trim_start();
+    }
+    if !rest.is_empty() {
+        pieces.push(rest.to_string());
+    }
+    pieces.retain(|p| !p.is_empty());
+    pieces
+}
+
+/// The largest char boundary in `s` at or below `at`.
+fn floor_char_boundary(s: &str, at: usize) -> usize {
+    if at >= s.len() {
+        return s.len();
+    }
+    (0..=at).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0)
+}
@@ -0,0 +1,255 @@
+//! Product quantization: each vector is split into `SUBSPACES` slices and every
+//! slice is replaced by the id of its nearest centroid in that subspace's
+//! codebook, so a 256-dim vector becomes 32 bytes. Queries are compared against
+//! the codes with a lookup table (asymmetric distance: the query stays exact),
+//! optionally followed by exact re-ranking of a wider shortlist.
+//!
+//! Cached as `<store>.pq` under the same fingerprint as the HNSW cache.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Instant;
+
+use serde::{Deserialize, Serialize};
+
+use crate::index_cache::Fingerprint;
+use crate::progress::Progress;
+use crate::store::{sibling_path, Record, VECTOR_DIM};
+use crate::{cosine_sim, print_timing};
+
+const SUBSPACES: usize = 32;
+const SUB_DIM: usize = VECTOR_DIM / SUBSPACES;
+/// Centroids per codebook, so a code fits in a byte.
+const CENTROIDS: usize = 256;
+const TRAIN_SAMPLE: usize = 4096;
+const TRAIN_ITERATIONS: usize = 8;
+/// With re-ranking, this many times `k` codes are re-scored from the full vectors.
+const RERANK_FACTOR: usize = 4;
+
+#[derive(Serialize, Deserialize)]
+struct Pq {
+    fingerprint: Option<Fingerprint>,
+    /// Per subspace, the centroids back to back (`SUB_DIM` floats each).
+    codebooks: Vec<Vec<f32>>,
+    /// `SUBSPACES` centroid ids per record, in store order.
+    codes: Vec<u8>,
+}
+
+/// Up to `k` positions from `allowed` nearest to `query` by their codes. With
+/// `rerank`, a shortlist of `RERANK_FACTOR * k` is re-ranked by exact cosine.
+#[allow(clippy::too_many_arguments)]
+pub fn search(
+    path: &Path,
+    fingerprint: Option<Fingerprint>,
+    records: &[Record],
+    query: &[f32],
+    k: usize,
+    allowed: &[usize],
+    rerank: bool,
+    timing: bool,
+) -> Vec<usize> {
+    let pq = load_or_build(path, fingerprint, records, timing);
+
+    // Inner products of each query slice with every centroid of its subspace.
+    let table: Vec<Vec<f32>> = pq
+        .codebooks
+        .iter()
+        .enumerate()
+        .map(|(j, book)| {
+            let q = slice(query, j);
+            book.chunks(SUB_DIM).map(|c| dot(&q, c)).collect()
+        })
+        .collect();
+    let mut hits: Vec<(f32, usize)> = allowed
+        .iter()
+        .map(|&pos| {
+            let code = &pq.codes[pos * SUBSPACES..(pos + 1) * SUBSPACES];
+            let score = code.iter().zip(&table).map(|(&c, t)| t[c as usize]).sum();
+            (score, pos)
+        })
+        .collect();
+
+    let shortlist = if rerank {
+        k.saturating_mul(RERANK_FACTOR)
+    } else {
+        k
+    };
+    if hits.len() > shortlist {
+        hits.select_nth_unstable_by(shortlist, |a, b| b.0.total_cmp(&a.0));
+        hits.truncate(shortlist);
+    }
+    if rerank {
+        for hit in hits.iter_mut() {
+            hit.0 = cosine_sim(query, &records[hit.1].vector);
+        }
+    }
+    hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+    hits.into_iter().take(k).map(|(_, pos)| pos).collect()
+}
+
+/// Every file this index may have written.
+pub fn cache_files(path: &Path) -> Vec<PathBuf> {
+    let file = sibling_path(path, ".pq");
+    if file.exists() {
+        vec![file]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Loads the cached index, building and caching it if it is stale; for `maintain`.
+pub fn warm(path: &Path, fingerprint: Option<Fingerprint>, records: &[Record]) {
+    load_or_build(path, fingerprint, records, false);
+}
+
+fn load_or_build(
+    path: &Path,
+    fingerprint: Option<Fingerprint>,
+    records: &[Record],
+    timing: bool,
+) -> Pq {
+    let started = Instant::now();
+    let cache_path = sibling_path(path, ".pq");
+    if fingerprint.is_some() {
+        let cached = fs::read(&cache_path)
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<Pq>(&bytes).ok())
+            .filter(|pq| {
+                pq.fingerprint == fingerprint && pq.codes.len() == records.len() * SUBSPACES
+            });
+        if let Some(pq) = cached {
+            if timing {
+                print_timing("pq load (cached)", started);
+            }
+            return pq;
+        }
+    }
+
+    let pq = build(records, fingerprint);
+    if timing {
+        print_timing(&format!("pq build ({} vectors)", records.len()), started);
+    }
+    if fingerprint.is_some() {
+        // The cache is an optimisation; a read-only directory just means rebuilding.
+        let _ = save(&cache_path, &pq);
+    }
+    pq
+}
+
+/// Trains the codebooks on an evenly spaced sample and encodes every record.
+/// Subspaces are trained, and records encoded, on all cores.
+fn build(records: &[Record], fingerprint: Option<Fingerprint>) -> Pq {
+    let stride = (records.len() / TRAIN_SAMPLE).max(1);
+    let sample: Vec<&[f32]> = records
+        .iter()
+        .step_by(stride)
+        .map(|r| r.vector.as_slice())
+        .collect();
+    let codebooks: Vec<Vec<f32>> = thread::scope(|s| {
+        let sample = &sample;
+        let workers: Vec<_> = (0..SUBSPACES)
+            .map(|j| s.spawn(move || train(sample, j)))
+            .collect();
+        workers
+            .into_iter()
+            .map(|w| w.join().expect("pq training panicked"))
+            .collect()
+    });
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk = records.len().div_ceil(threads).max(1);
+    let progress = Progress::new("pq build", records.len());
+    let codes: Vec<u8> = thread::scope(|s| {
+        let (codebooks, progress) = (&codebooks, &progress);
+        let workers: Vec<_> = records
+            .chunks(chunk)
+            .map(|part| s.spawn(move || encode(codebooks, part, progress)))
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|w| w.join().expect("pq encoding panicked"))
+            .collect()
+    });
+    progress.finish();
+    Pq {
+        fingerprint,
+        codebooks,
+        codes,
+    }
+}
+
+/// Lloyd's k-means on slice `j` of the sample, seeded with evenly spaced points.
+fn train(sample: &[&[f32]], j: usize) -> Vec<f32> {
+    let points: Vec<[f32; SUB_DIM]> = sample.iter().map(|v| slice(v, j)).collect();
+    let n = CENTROIDS.min(points.len());
+    let seed_stride = (points.len() / n.max(1)).max(1);
+    let mut centers: Vec<[f32; SUB_DIM]> = points
+        .iter()
+        .step_by(seed_stride)
+        .take(n)
+        .copied()
+        .collect();
+    for _ in 0..TRAIN_ITERATIONS {
+        let mut sums = vec![([0.0f32; SUB_DIM], 0usize); centers.len()];
+        for p in &points {
+            let (sum, count) = &mut sums[nearest(&centers, p)];
+            for (s, x) in sum.iter_mut().zip(p) {
+                *s += x;
+            }
+            *count += 1;
+        }
+        // An emptied centroid keeps its old position.
+        for (center, (sum, count)) in centers.iter_mut().zip(sums) {
+            if count > 0 {
+                *center = sum.map(|s| s / count as f32);
+            }
+        }
+    }
+    centers.concat()
+}
+
+fn encode(codebooks: &[Vec<f32>], records: &[Record], progress: &Progress) -> Vec<u8> {
+    let books: Vec<Vec<[f32; SUB_DIM]>> = codebooks
+        .iter()
+        .map(|book| book.chunks(SUB_DIM).map(|c| slice(c, 0)).collect())
+        .collect();
+    let mut codes = Vec::with_capacity(records.len() * SUBSPACES);
+    for rec in records {
+        for (j, centers) in books.iter().enumerate() {
+            codes.push(nearest(centers, &slice(&rec.vector, j)) as u8);
+        }
+        progress.inc(1);
+    }
+    codes
+}
+
+/// Slice `j` of `v`, zero-padded if `v` is short.
+fn slice(v: &[f32], j: usize) -> [f32; SUB_DIM] {
+    let mut out = [0.0; SUB_DIM];
+    for (o, x) in out.iter_mut().zip(v.iter().skip(j * SUB_DIM)) {
+        *o = *x;
+    }
+    out
+}
+
+fn nearest(centers: &[[f32; SUB_DIM]], p: &[f32; SUB_DIM]) -> usize {
+    let dist = |c: &[f32; SUB_DIM]| c.iter().zip(p).map(|(a, b)| (a - b) * (a - b)).sum::<f32>();
+    centers
+        .iter()
+        .map(dist)
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn save(cache_path: &Path, pq: &Pq) -> io::Result<()> {
+    let bytes = bincode::serialize(pq).map_err(|_| io::ErrorKind::InvalidData)?;
+    fs::write(cache_path, bytes)
+}
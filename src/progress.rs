@@ -0,0 +1,104 @@
+//! Progress on stderr for operations that can take minutes on large stores
+//! (index builds, re-embedding). Nothing is shown for the first second, so
+//! quick runs stay silent. On a terminal a single line is redrawn in place;
+//! otherwise (cron, log files) a plain line is written every ten seconds.
+
+use std::io::{self, IsTerminal, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const QUIET_FOR: Duration = Duration::from_secs(1);
+const TTY_EVERY: Duration = Duration::from_millis(200);
+const LOG_EVERY: Duration = Duration::from_secs(10);
+
+/// Counts finished items out of `total`. Shared by reference between worker
+/// threads; `inc` is cheap between redraws.
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: AtomicUsize,
+    started: Instant,
+    tty: bool,
+    /// When the last line was drawn, `None` until the first one.
+    shown: Mutex<Option<Instant>>,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: usize) -> Progress {
+        Progress {
+            label: label.to_string(),
+            total,
+            done: AtomicUsize::new(0),
+            started: Instant::now(),
+            tty: io::stderr().is_terminal(),
+            shown: Mutex::new(None),
+        }
+    }
+
+    pub fn inc(&self, n: usize) {
+        let done = self.done.fetch_add(n, Ordering::Relaxed) + n;
+        let now = Instant::now();
+        if now.duration_since(self.started) < QUIET_FOR {
+            return;
+        }
+        // Another thread is drawing; this update shows up in the next line.
+        let Ok(mut shown) = self.shown.try_lock() else {
+            return;
+        };
+        let every = if self.tty { TTY_EVERY } else { LOG_EVERY };
+        if shown.is_some_and(|last| now.duration_since(last) < every) {
+            return;
+        }
+        *shown = Some(now);
+        self.draw(done);
+    }
+
+    /// Ends the line on a terminal, or logs the total elsewhere, if anything
+    /// was shown at all.
+    pub fn finish(self) {
+        let shown = self.shown.lock().map(|s| s.is_some()).unwrap_or(false);
+        if shown {
+            self.draw(self.done.load(Ordering::Relaxed));
+            if self.tty {
+                eprintln!();
+            }
+        }
+    }
+
+    fn draw(&self, done: usize) {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let percent = (done.min(self.total) * 100)
+            .checked_div(self.total)
+            .unwrap_or(100);
+        let eta = if done > 0 && done < self.total {
+            let left = elapsed * (self.total - done) as f64 / done as f64;
+            format!(", ETA {}", format_secs(left))
+        } else {
+            String::new()
+        };
+        let line = format!(
+            "{}: {done}/{} ({percent}%), {} elapsed{eta}",
+            self.label,
+            self.total,
+            format_secs(elapsed)
+        );
+        let mut err = io::stderr().lock();
+        let _ = if self.tty {
+            write!(err, "\r\x1b[K{line}")
+        } else {
+            writeln!(err, "{line}")
+        };
+        let _ = err.flush();
+    }
+}
+
+/// `42s`, `3m05s` or `1h02m`.
+fn format_secs(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
@@ -0,0 +1,154 @@
+//! The `--query` mini language.
+//!
+//! ```text
+//! kind:decision tag:infra after:2024-06-01 "rollback plan"
+//! ```
+//!
+//! Recognised `key:value` terms become filters; everything else, with quotes
+//! removed, is the semantic query text. Keys:
+//!
+//! - `kind:<kind>`
+//! - `tag:<tag>` (repeatable, all must be present)
+//! - `after:<date|duration>` – `2024-06-01` (that day included) or `7d` (last 7 days)
+//! - `before:<date|duration>` – `2024-06-01` (that day excluded) or `7d` (older than 7 days)
+//! - `must:<term>` / `not:<term>` – same as `--must` / `--must-not`
+//! - `lang:<code>` – same as `--lang`
+//!
+//! A word in the text can carry a weight, `rollback^2 kubernetes`, for when
+//! one term is the point of the query: it counts that many times in the query
+//! vector, however often it appears, where other words count once per
+//! occurrence. Filters and `--must` terms are unaffected.
+//!
+//! Quoting a whole term (`"kind:x"`) keeps it literal, and unknown keys such
+//! as `http://…` are left in the text.
+
+use std::collections::BTreeMap;
+
+use crate::dates::parse_date;
+use crate::filter::{parse_duration, RecordFilter};
+use crate::{embed_weighted, now_secs, tokenize};
+
+const KEYS: [&str; 7] = ["kind", "tag", "after", "before", "must", "not", "lang"];
+
+#[derive(Debug, Default)]
+pub struct ParsedQuery {
+    pub text: String,
+    pub filter: RecordFilter,
+    /// Token weights from `word^w`, keyed like `tokenize` output.
+    pub boosts: BTreeMap<String, f32>,
+}
+
+impl ParsedQuery {
+    /// The text embedded with its term weights.
+    pub fn vector(&self) -> Vec<f32> {
+        embed_weighted(&self.text, &self.boosts)
+    }
+}
+
+pub fn parse_query(query: &str) -> Result<ParsedQuery, String> {
+    let mut parsed = ParsedQuery::default();
+    let mut words: Vec<String> = Vec::new();
+
+    for (term, quoted) in split_terms(query) {
+        let key_value = if quoted { None } else { term.split_once(':') };
+        let Some((key, value)) = key_value.filter(|(k, _)| KEYS.contains(k)) else {
+            match split_boost(&term, quoted)? {
+                Some((word, weight)) => {
+                    for token in tokenize(word) {
+                        parsed.boosts.insert(token, weight);
+                    }
+                    words.push(word.to_string());
+                }
+                None => words.push(term),
+            }
+            continue;
+        };
+        let value = value.trim_matches('"').to_string();
+        if value.is_empty() {
+            return Err(format!("{key}: needs a value"));
+        }
+        let filter = &mut parsed.filter;
+        match key {
+            "kind" => filter.kind = Some(value),
+            "tag" => filter.tags.push(value),
+            "after" => filter.since = filter.since.max(Some(parse_bound(key, &value)?)),
+            "before" => {
+                let bound = parse_bound(key, &value)?;
+                filter.until = Some(filter.until.map_or(bound, |u| u.min(bound)));
+            }
+            "must" => filter.must.push(value),
+            "lang" => filter.lang = Some(value.to_lowercase()),
+            _ => filter.must_not.push(value),
+        }
+    }
+
+    parsed.text = words.join(" ");
+    Ok(parsed)
+}
+
+/// Splits on whitespace outside double quotes. The flag is set when the whole
+/// term was quoted, which keeps `"kind:x"` out of the filter syntax.
+fn split_terms(query: &str) -> Vec<(String, bool)> {
+    let mut terms = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut started_quoted = false;
+    let mut started = false;
+    for c in query.chars() {
+        match c {
+            '"' => {
+                if !started {
+                    started = true;
+                    started_quoted = true;
+                }
+                in_quotes = !in_quotes;
+                // Keep quotes after a key so `must:"two words"` stays one term.
+                if !started_quoted {
+                    current.push(c);
+                }
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if started {
+                    terms.push((std::mem::take(&mut current), started_quoted));
+                }
+                started = false;
+                started_quoted = false;
+            }
+            c => {
+                started = true;
+                current.push(c);
+            }
+        }
+    }
+    if started {
+        terms.push((current, started_quoted));
+    }
+    terms
+}
+
+/// `word^2` as `("word", 2.0)`. Anything not ending in `^<number>` is an
+/// ordinary word.
+fn split_boost(term: &str, quoted: bool) -> Result<Option<(&str, f32)>, String> {
+    let split = if quoted { None } else { term.rsplit_once('^') };
+    let Some((word, weight)) = split.filter(|(word, _)| !word.is_empty()) else {
+        return Ok(None);
+    };
+    match weight.parse::<f32>() {
+        Ok(w) if w.is_finite() && w > 0.0 => Ok(Some((word, w))),
+        Ok(_) => Err(format!("{term}: a term weight must be a positive number")),
+        Err(_) => Ok(None),
+    }
+}
+
+/// A date is midnight UTC; a duration counts back from now.
+fn parse_bound(key: &str, value: &str) -> Result<i64, String> {
+    if let Some(ts) = parse_date(value) {
+        return Ok(ts);
+    }
+    if let Some(secs) = parse_duration(value) {
+        return Ok(now_secs() - secs);
+    }
+    Err(format!(
+        "{key}:{value}: expected a date (2024-06-01) or a duration (7d)"
+    ))
+}
@@ -0,0 +1,147 @@
+//! `random -n <n>`: a sample of records, for resurfacing ones no search brings
+//! up any more. Uniform by default. `--recency-weighted` gives each record odds
+//! proportional to its recency under the profile's decay curve (the recency
+//! part of the search score), so newer records come up more often; records the
+//! curve gives no recency at all only fill a sample that would be short.
+//!
+//! The store is streamed once, keeping `n` records in memory: each record
+//! draws the key `u^(1/w)` for a uniform `u` and its weight `w`, and the `n`
+//! largest keys are the sample (weighted reservoir sampling). `--seed` repeats
+//! a sample.
+
+use std::path::PathBuf;
+use std::process;
+
+use crate::filter::RecordFilter;
+use crate::store::stream_records;
+use crate::{
+    config, kinds, local_only, now_millis, now_secs, parse_flag, parse_since, path_arg,
+    resolve_path, store_error, tsv_field, Record,
+};
+
+pub fn cmd_random(args: &[String]) -> Result<(), &'static str> {
+    let mut n: usize = 10;
+    let mut filter = RecordFilter::default();
+    let mut recency_weighted = false;
+    let mut seed: Option<u64> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-n" => {
+                i += 1;
+                n = parse_flag("-n", args.get(i))?;
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--recency-weighted" => recency_weighted = true,
+            "--seed" => {
+                i += 1;
+                seed = Some(parse_flag("--seed", args.get(i))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if n == 0 {
+        return Ok(());
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let aliases = config::load_config()
+        .map_err(store_error(&config::config_path(), "read failed"))?
+        .kind_aliases;
+    kinds::resolve_streamed(&mut filter, &path, &aliases)
+        .map_err(store_error(&path, "read failed"))?;
+
+    let scoring = &profile.scoring;
+    let now = now_secs();
+    let weight = |rec: &Record| {
+        if !recency_weighted {
+            return 1.0;
+        }
+        let age_days = ((now - rec.ts).max(0) as f32) / 86400.0;
+        let decay = scoring.kind_decay.get(&rec.kind).unwrap_or(&scoring.decay);
+        decay.factor(age_days) as f64
+    };
+
+    let seed = seed.unwrap_or_else(|| (now_millis() as u64) ^ ((process::id() as u64) << 32));
+    let mut rng = SplitMix64(seed);
+    let mut sample: Vec<(f64, Record)> = Vec::with_capacity(n);
+    for rec in stream_records(&path).map_err(store_error(&path, "read failed"))? {
+        let rec = rec.map_err(store_error(&path, "read failed"))?;
+        if !filter.matches(&rec) {
+            continue;
+        }
+        let w = weight(&rec);
+        let key = if w > 0.0 {
+            rng.next_f64().powf(1.0 / w)
+        } else {
+            0.0
+        };
+        if sample.len() < n {
+            sample.push((key, rec));
+            continue;
+        }
+        let lowest = sample
+            .iter_mut()
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .expect("sample is full");
+        if key > lowest.0 {
+            *lowest = (key, rec);
+        }
+    }
+
+    sample.sort_by(|a, b| b.0.total_cmp(&a.0));
+    for (_, rec) in sample {
+        println!(
+            "{}\t{}\t{}\t{}",
+            rec.kind,
+            rec.id,
+            rec.ts,
+            tsv_field(&rec.text)
+        );
+    }
+    Ok(())
+}
+
+/// SplitMix64: small, fast and plenty for picking records.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Uniform in `[0, 1)`.
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
@@ -0,0 +1,88 @@
+//! The recency part of the search score, as a function of a record's age.
+//!
+//! The default `1/(1 + age_days)` halves after a day, which suits a working
+//! log but buries long-term knowledge. Profiles pick another curve with
+//! `scoring.decay` (and per kind with `scoring.kind_decay`); `search --decay`
+//! overrides both for one search.
+
+use serde::{Deserialize, Serialize};
+
+use crate::filter::parse_duration;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "lowercase")]
+pub enum Decay {
+    /// `1 / (1 + age_days)`.
+    #[default]
+    Hyperbolic,
+    /// Halves every `half_life_days`.
+    Exponential { half_life_days: f32 },
+    /// 1.0 inside the first window, one step lower for each window boundary
+    /// passed, 0 beyond the last: `[7, 30, 365]` gives 1, 2/3, 1/3, 0.
+    Step { days: Vec<f32> },
+    /// No recency term at all.
+    #[serde(rename = "none")]
+    Off,
+}
+
+impl Decay {
+    /// Recency in `[0, 1]` for a record `age_days` old.
+    pub fn factor(&self, age_days: f32) -> f32 {
+        match self {
+            Decay::Hyperbolic => 1.0 / (1.0 + age_days),
+            Decay::Exponential { half_life_days } => 0.5f32.powf(age_days / half_life_days),
+            Decay::Step { days } => {
+                let passed = days.iter().filter(|&&d| age_days >= d).count();
+                (days.len() - passed) as f32 / days.len().max(1) as f32
+            }
+            Decay::Off => 0.0,
+        }
+    }
+
+    /// `--decay` values: `hyperbolic`, `none`, `exp:<age>` (the half-life) or
+    /// `step:<age>,<age>,...`. Ages are days (`30`) or durations (`2w`, `12h`).
+    pub fn parse(s: &str) -> Result<Decay, String> {
+        let invalid = || {
+            format!("invalid decay {s:?} (expected hyperbolic, none, exp:<age> or step:<age>,...)")
+        };
+        match s.split_once(':') {
+            None if s == "hyperbolic" => Ok(Decay::Hyperbolic),
+            None if s == "none" => Ok(Decay::Off),
+            Some(("exp", age)) => Ok(Decay::Exponential {
+                half_life_days: parse_days(age).ok_or_else(invalid)?,
+            }),
+            Some(("step", ages)) => Ok(Decay::Step {
+                days: ages
+                    .split(',')
+                    .map(parse_days)
+                    .collect::<Option<Vec<f32>>>()
+                    .ok_or_else(invalid)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// An error for settings `factor` cannot use.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            Decay::Exponential { half_life_days }
+                if !(*half_life_days > 0.0 && half_life_days.is_finite()) =>
+            {
+                Err(format!("half_life_days must be positive, got {half_life_days}"))
+            }
+            Decay::Step { days } if days.is_empty() || days.iter().any(|d| !d.is_finite()) => {
+                Err("step decay needs a list of finite days".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A positive age in days, from a number of days or a duration.
+fn parse_days(s: &str) -> Option<f32> {
+    let days = match s.parse::<f32>() {
+        Ok(d) => d,
+        Err(_) => parse_duration(s)? as f32 / 86400.0,
+    };
+    (days > 0.0 && days.is_finite()).then_some(days)
+}
@@ -0,0 +1,286 @@
+//! `redact`: replaces contact data in record text with placeholders and re-embeds.
+
+use std::path::PathBuf;
+
+use regex::Regex;
+
+use crate::store::{load_store, update_store, Record};
+use crate::progress::Progress;
+use crate::{config, embed_text, local_only, path_arg, resolve_path, store_error};
+
+const ALL_PATTERNS: &[&str] = &["emails", "phones", "cards"];
+
+struct Pattern {
+    name: &'static str,
+    placeholder: &'static str,
+    regex: Regex,
+    /// Extra check on a match, e.g. the Luhn checksum for card numbers.
+    accept: fn(&str) -> bool,
+}
+
+pub fn cmd_redact(args: &[String]) -> Result<(), &'static str> {
+    let mut names: Vec<String> = ALL_PATTERNS.iter().map(|s| s.to_string()).collect();
+    let mut ids: Vec<u128> = Vec::new();
+    let mut all = false;
+    let mut dry_run = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--patterns" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    names = v.split(',').map(|s| s.trim().to_string()).collect();
+                }
+            }
+            "--id" => {
+                i += 1;
+                match args.get(i).and_then(|v| v.parse().ok()) {
+                    Some(id) => ids.push(id),
+                    None => {
+                        eprintln!("Invalid --id");
+                        return Err("invalid id");
+                    }
+                }
+            }
+            "--all" => all = true,
+            "--dry-run" => dry_run = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    // Exactly one of the two selections.
+    if ids.is_empty() != all {
+        eprintln!("Pass either --id <id>... or --all");
+        return Err("missing selection");
+    }
+    let patterns = build_patterns(&names)?;
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    let selected = |rec: &Record| all || ids.contains(&rec.id);
+    let report = |rec: &Record, counts: &[(&str, usize)]| {
+        let summary: Vec<String> = counts
+            .iter()
+            .filter(|(_, n)| *n > 0)
+            .map(|(name, n)| format!("{name}={n}"))
+            .collect();
+        println!("{}\t{}", rec.id, summary.join(","));
+    };
+
+    let changed = if dry_run {
+        let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+        let mut changed = 0;
+        for rec in store.records.iter().filter(|r| selected(r)) {
+            let (_, counts) = redact_text(&rec.text, &patterns);
+            if counts.iter().any(|(_, n)| *n > 0) {
+                report(rec, &counts);
+                changed += 1;
+            }
+        }
+        changed
+    } else {
+        update_store(&path, |store| {
+            let mut changed = 0;
+            let total = store.records.iter().filter(|r| selected(r)).count();
+            let progress = Progress::new("redact", total);
+            for rec in store.records.iter_mut().filter(|r| selected(r)) {
+                let (text, counts) = redact_text(&rec.text, &patterns);
+                if counts.iter().any(|(_, n)| *n > 0) {
+                    report(rec, &counts);
+                    rec.vector = embed_text(&text);
+                    rec.text = text;
+                    changed += 1;
+                }
+                progress.inc(1);
+            }
+            progress.finish();
+            Ok(changed)
+        })
+        .map_err(store_error(&path, "write failed"))?
+    };
+
+    let verb = if dry_run { "Would redact" } else { "Redacted" };
+    eprintln!("{verb} {changed} records");
+    Ok(())
+}
+
+fn build_patterns(names: &[String]) -> Result<Vec<Pattern>, &'static str> {
+    let accept_any: fn(&str) -> bool = |_| true;
+    names
+        .iter()
+        .map(|name| {
+            let (name, placeholder, re, accept): (&'static str, _, _, fn(&str) -> bool) =
+                match name.as_str() {
+                    "emails" => (
+                        "emails",
+                        "[EMAIL]",
+                        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+                        accept_any,
+                    ),
+                    // A `+` country code, an area code in parentheses, or digit
+                    // groups joined by one kind of separator; see `phone_like`.
+                    "phones" => (
+                        "phones",
+                        "[PHONE]",
+                        PHONE_RE,
+                        phone_like,
+                    ),
+                    "cards" => ("cards", "[CARD]", r"\b\d(?:[ -]?\d){12,18}\b", luhn_valid),
+                    other => {
+                        eprintln!(
+                            "Unknown pattern: {other} (supported: {})",
+                            ALL_PATTERNS.join(",")
+                        );
+                        return Err("unknown pattern");
+                    }
+                };
+            Ok(Pattern {
+                name,
+                placeholder,
+                regex: Regex::new(re).expect("static pattern"),
+                accept,
+            })
+        })
+        .collect()
+}
+
+/// Cards run first so their digits are not taken for phone numbers.
+fn redact_text<'p>(text: &str, patterns: &'p [Pattern]) -> (String, Vec<(&'p str, usize)>) {
+    let mut ordered: Vec<&Pattern> = patterns.iter().collect();
+    ordered.sort_by_key(|p| p.name != "cards");
+
+    let mut out = text.to_string();
+    let mut counts = Vec::new();
+    for pattern in ordered {
+        let mut n = 0;
+        out = pattern
+            .regex
+            .replace_all(&out, |caps: &regex::Captures| {
+                let m = &caps[0];
+                if (pattern.accept)(m) {
+                    n += 1;
+                    pattern.placeholder.to_string()
+                } else {
+                    m.to_string()
+                }
+            })
+            .into_owned();
+        counts.push((pattern.name, n));
+    }
+    (out, counts)
+}
+
+const PHONE_RE: &str = concat!(
+    r"(?:\+\d{1,3}[\s.-]?(?:\(\d{1,4}\)[\s.-]?)?|\(\d{1,4}\)[\s.-]?|\b)",
+    r"(?:\d{2,4}(?:(?:-\d{2,8}){1,4}|(?:\.\d{2,8}){1,4}|(?: \d{2,8}){1,4})|\d{5,12})\b",
+);
+
+/// 8 to 15 digits that read as a phone number rather than a date, an IPv4
+/// address or an id: a bare digit run only counts after a `+` or `(...)`.
+fn phone_like(s: &str) -> bool {
+    let digits = s.chars().filter(char::is_ascii_digit).count();
+    if !(8..=15).contains(&digits) {
+        return false;
+    }
+    if s.starts_with('+') || s.starts_with('(') {
+        return true;
+    }
+    let Some(sep) = s.chars().find(|c| !c.is_ascii_digit()) else {
+        return false;
+    };
+    let groups: Vec<usize> = s.split(sep).map(str::len).collect();
+    let date = matches!(groups[..], [4, 2, 2] | [2, 2, 4]) && sep != ' ';
+    let dotted_quad = sep == '.' && groups.len() == 4 && groups.iter().all(|&n| n <= 3);
+    !date && !dotted_quad
+}
+
+fn luhn_valid(s: &str) -> bool {
+    let digits: Vec<u32> = s.chars().filter_map(|c| c.to_digit(10)).collect();
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phones(text: &str) -> String {
+        let patterns = build_patterns(&["phones".to_string()]).unwrap();
+        redact_text(text, &patterns).0
+    }
+
+    #[test]
+    fn phone_numbers_are_redacted() {
+        for phone in [
+            "+1 (555) 123-4567",
+            "+44 20 7946 0958",
+            "+491701234567",
+            "(030) 1234567",
+            "555-123-4567",
+            "555.123.4567",
+            "030 1234 5678",
+        ] {
+            assert_eq!(
+                phones(&format!("call {phone} today")),
+                "call [PHONE] today",
+                "{phone}"
+            );
+        }
+    }
+
+    #[test]
+    fn dates_addresses_and_ids_are_kept() {
+        for text in [
+            "Deployed on 2024-06-01 to host 192.168.100.200, build 1718000000123",
+            "released 01.06.2024",
+            "ts 1718000000",
+            "uuid 550e8400-e29b-41d4-a716-446655440000",
+            "order 12345678",
+            "v1.2.3 and 10.20.30",
+        ] {
+            assert_eq!(phones(text), text);
+        }
+    }
+
+    #[test]
+    fn a_phone_next_to_a_date_is_still_redacted() {
+        assert_eq!(phones("2024-06-01 555-123-4567"), "2024-06-01 [PHONE]");
+    }
+
+    #[test]
+    fn cards_need_a_valid_checksum() {
+        let patterns = build_patterns(&["cards".to_string()]).unwrap();
+        assert_eq!(
+            redact_text("card 4111 1111 1111 1111", &patterns).0,
+            "card [CARD]"
+        );
+        assert_eq!(
+            redact_text("id 4111 1111 1111 1112", &patterns).0,
+            "id 4111 1111 1111 1112"
+        );
+    }
+}
@@ -0,0 +1,388 @@
+//! `remember` / `recall`: one-argument front ends to `add` and `search` with
+//! defaults suited to agent prompts.
+//!
+//! - `remember "<text>"` guesses the kind from the wording, estimates the
+//!   weight, and skips text that is already stored (a near-identical record),
+//!   printing the id that holds it either way.
+//! - `recall "<question>"` searches, folds near-duplicate hits, and prints the
+//!   best memories as a bullet list cut to a token budget, ready to paste into
+//!   a prompt. With `--cite`, each line ends in `[n]` and a citation map
+//!   follows, as for `search --cite`.
+//! - `ask "<question>"` answers from memory without a model: it picks the
+//!   sentences of the best hits closest to the question and prints them, each
+//!   followed by the id of the record it came from.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::classify::guess_kind;
+use crate::dates::format_date;
+use crate::store::Record;
+use crate::{
+    add_text, collapse_hits, config, cosine_sim, embed_text, parse_flag, path_arg, print_citations,
+    query, resolve_path, search_any, tsv_field, SearchOptions,
+};
+
+/// Cosine similarity at which `remember` treats text as already stored.
+const DUPLICATE_SIM: f32 = 0.95;
+/// Existing records compared against before adding.
+const DUPLICATE_CANDIDATES: usize = 5;
+const DEFAULT_BUDGET: usize = 800;
+/// Hits fetched before folding and budgeting.
+const RECALL_CANDIDATES: usize = 30;
+/// Same default as `search --collapse`.
+const RECALL_COLLAPSE: f32 = 0.97;
+/// Hits whose sentences `ask` chooses from.
+const ASK_CANDIDATES: usize = 10;
+const DEFAULT_SENTENCES: usize = 3;
+/// Sentences less similar to the question than this are never part of an answer.
+const MIN_SENTENCE_SIM: f32 = 0.15;
+/// Sentences at least this similar to one already chosen repeat it.
+const SAME_SENTENCE_SIM: f32 = 0.9;
+
+pub fn cmd_remember(args: &[String]) -> Result<(), &'static str> {
+    let mut text: Option<String> = None;
+    let mut kind: Option<String> = None;
+    let mut tags: Vec<String> = Vec::new();
+    let mut force = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--kind" => {
+                i += 1;
+                kind = args.get(i).cloned();
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    tags.push(v.clone());
+                }
+            }
+            "--force" => force = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => text = Some(other.to_string()),
+        }
+        i += 1;
+    }
+
+    let Some(text) = text.filter(|t| !t.trim().is_empty()) else {
+        eprintln!("Usage: remember \"<text>\" [--kind <kind>] [--tag <tag>]... [--force]");
+        return Err("missing text");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+
+    if !force {
+        if let Some((sim, rec)) = closest_existing(&path, &profile, &text)? {
+            if sim >= DUPLICATE_SIM {
+                eprintln!("Already remembered ({sim:.2} similar); not added");
+                println!("{}", rec.id);
+                return Ok(());
+            }
+        }
+    }
+    let kind = kind.unwrap_or_else(|| match &profile.classify {
+        Some(classify) => classify.kind(&text),
+        None => guess_kind(&text).to_string(),
+    });
+    let (record, _) = add_text(
+        &path,
+        &profile,
+        text,
+        kind,
+        None,
+        tags,
+        BTreeMap::new(),
+        None,
+    )?;
+    eprintln!("Remembered as {}", record.kind);
+    println!("{}", record.id);
+    Ok(())
+}
+
+pub fn cmd_recall(args: &[String]) -> Result<(), &'static str> {
+    let mut question: Option<String> = None;
+    let mut budget = DEFAULT_BUDGET;
+    let mut cite = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--budget" => {
+                i += 1;
+                budget = parse_flag("--budget", args.get(i))?;
+            }
+            "--cite" => cite = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => question = Some(other.to_string()),
+        }
+        i += 1;
+    }
+
+    let Some(question) = question.filter(|q| !q.trim().is_empty()) else {
+        eprintln!("Usage: recall \"<question>\" [--budget <tokens>]");
+        return Err("missing question");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    let options = SearchOptions {
+        max_age: profile.max_age(),
+        ..profile_options(&profile, RECALL_CANDIDATES)
+    };
+    let scored = search_any(&path, &question, &options)?;
+    let hits = collapse_hits(scored, Some(RECALL_COLLAPSE), |(_, rec)| rec);
+
+    let mut used = 0;
+    let mut shown = 0;
+    let mut cited = Vec::new();
+    for ((_, rec), _) in &hits {
+        let marker = if cite {
+            format!(" [{}]", shown + 1)
+        } else {
+            String::new()
+        };
+        let line = format!(
+            "- [{} {}] {}",
+            rec.kind,
+            format_date(rec.ts),
+            tsv_field(&rec.text)
+        );
+        let cost = estimate_tokens(&line) + estimate_tokens(&marker);
+        if used + cost > budget {
+            // Better a cut-off best match than nothing at all.
+            if shown == 0 {
+                println!("{}…{marker}", fit_tokens(&line, budget));
+                used = budget;
+                shown = 1;
+                cited.push((rec.id, rec.ts));
+            }
+            break;
+        }
+        println!("{line}{marker}");
+        used += cost;
+        shown += 1;
+        cited.push((rec.id, rec.ts));
+    }
+    if cite {
+        let source = path.display().to_string();
+        let cited: Vec<(u128, i64, &str)> = cited
+            .into_iter()
+            .map(|(id, ts)| (id, ts, source.as_str()))
+            .collect();
+        print_citations(&cited);
+    }
+    if shown == 0 {
+        eprintln!("Nothing relevant remembered");
+    } else {
+        eprintln!("{shown} memories, ~{used} tokens (budget {budget})");
+    }
+    Ok(())
+}
+
+pub fn cmd_ask(args: &[String]) -> Result<(), &'static str> {
+    let mut question: Option<String> = None;
+    let mut sentences = DEFAULT_SENTENCES;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sentences" => {
+                i += 1;
+                sentences = parse_flag("--sentences", args.get(i))?;
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => question = Some(other.to_string()),
+        }
+        i += 1;
+    }
+
+    let Some(question) = question.filter(|q| !q.trim().is_empty()) else {
+        eprintln!("Usage: ask \"<question>\" [--sentences <n>]");
+        return Err("missing question");
+    };
+    let parsed = query::parse_query(&question).map_err(|e| {
+        eprintln!("{e}");
+        "invalid query"
+    })?;
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    let options = SearchOptions {
+        max_age: profile.max_age(),
+        ..profile_options(&profile, ASK_CANDIDATES)
+    };
+    let hits = search_any(&path, &question, &options)?;
+
+    // Every sentence of every hit, scored mostly on its own similarity to the
+    // question and a little on its record's, so context breaks near-ties.
+    let query_vec = parsed.vector();
+    let mut candidates: Vec<(f32, Vec<f32>, &str, u128)> = Vec::new();
+    for (_, rec) in &hits {
+        let record_sim = cosine_sim(&query_vec, &embed_text(&rec.text));
+        for sentence in split_sentences(&rec.text) {
+            let vector = embed_text(sentence);
+            let sim = cosine_sim(&query_vec, &vector);
+            if sim >= MIN_SENTENCE_SIM {
+                candidates.push((0.8 * sim + 0.2 * record_sim, vector, sentence, rec.id));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let mut chosen: Vec<(Vec<f32>, &str, u128)> = Vec::new();
+    for (_, vector, sentence, id) in candidates {
+        if chosen.len() >= sentences {
+            break;
+        }
+        if chosen
+            .iter()
+            .any(|(v, _, _)| cosine_sim(v, &vector) >= SAME_SENTENCE_SIM)
+        {
+            continue;
+        }
+        chosen.push((vector, sentence, id));
+    }
+    if chosen.is_empty() {
+        eprintln!("No answer found in memory");
+        return Ok(());
+    }
+    for (_, sentence, id) in &chosen {
+        println!("- {sentence} [{id}]");
+    }
+    let mut sources: Vec<u128> = chosen.iter().map(|(_, _, id)| *id).collect();
+    sources.sort_unstable();
+    sources.dedup();
+    eprintln!(
+        "{} sentences from {} of {} memories",
+        chosen.len(),
+        sources.len(),
+        hits.len()
+    );
+    Ok(())
+}
+
+/// Sentences of `text`, split after `.`, `!`, `?` (and their CJK forms) and at
+/// line breaks, trimmed; empty pieces are dropped.
+fn split_sentences(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let end = i + c.len_utf8();
+        let boundary = match c {
+            '\n' | '。' | '！' | '？' => true,
+            // `3.5` and `e.g.x` are not sentence ends.
+            '.' | '!' | '?' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if boundary {
+            out.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    out.push(text[start..].trim());
+    out.retain(|s| !s.is_empty());
+    out
+}
+
+/// The stored record most similar to `text`, if any. Skipped for text that is
+/// not a valid query (e.g. `kind:` with nothing after it).
+fn closest_existing(
+    path: &Path,
+    profile: &config::Profile,
+    text: &str,
+) -> Result<Option<(f32, Record)>, &'static str> {
+    if query::parse_query(text).is_err() {
+        return Ok(None);
+    }
+    let vector = embed_text(text);
+    let hits = search_any(path, text, &profile_options(profile, DUPLICATE_CANDIDATES))?;
+    // Remote hits carry no vectors, and the ranking mixes in weight and
+    // recency, so compare every candidate directly.
+    Ok(hits
+        .into_iter()
+        .map(|(_, rec)| {
+            let sim = if rec.vector.is_empty() {
+                cosine_sim(&vector, &embed_text(&rec.text))
+            } else {
+                cosine_sim(&vector, &rec.vector)
+            };
+            (sim, rec)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0)))
+}
+
+/// Search options from the profile, as `search` without flags would use, but
+/// over all time; `recall` adds the profile's `default_max_age`.
+fn profile_options(profile: &config::Profile, limit: usize) -> SearchOptions {
+    SearchOptions {
+        limit,
+        scoring: profile.scoring.clone(),
+        filter: Default::default(),
+        per_kind: None,
+        index: profile.index.unwrap_or_default(),
+        rerank: profile.rerank.unwrap_or(false),
+        window: profile.shard_window,
+        include_archive: false,
+        max_memory: profile.memory_limit(),
+        max_age: None,
+        timing: false,
+    }
+}
+
+/// Rough token count: about four ASCII characters per token, and one per
+/// character for other scripts (CJK text tokenizes close to that).
+fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.bytes().filter(u8::is_ascii).count();
+    let other = text.chars().filter(|c| !c.is_ascii()).count();
+    ascii.div_ceil(4) + other
+}
+
+/// The longest prefix of `text` within `budget` tokens.
+fn fit_tokens(text: &str, budget: usize) -> &str {
+    let (mut ascii, mut other) = (0usize, 0usize);
+    for (i, c) in text.char_indices() {
+        if c.is_ascii() {
+            ascii += 1;
+        } else {
+            other += 1;
+        }
+        if ascii.div_ceil(4) + other > budget {
+            return &text[..i];
+        }
+    }
+    text
+}
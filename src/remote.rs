@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::path::Path;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::server::{
+    AddRequest, AddResponse, ApiError, ApiHit, ApiRecord, CompactRequest, GetRequest, RecentRequest,
+    SearchRequest,
+};
+use crate::{Nearest, Record, SearchOptions};
+
+/// Returns the store URL when `--path` points at a memstore server.
+pub fn store_url(path: &Path) -> Option<&str> {
+    let s = path.to_str()?;
+    if s.starts_with("http://") || s.starts_with("https://") {
+        Some(s.trim_end_matches('/'))
+    } else {
+        None
+    }
+}
+
+pub fn add(
+    url: &str,
+    text: String,
+    kind: String,
+    weight: Option<f32>,
+    tags: Vec<String>,
+    attrs: BTreeMap<String, String>,
+    expected_generation: Option<u64>,
+) -> io::Result<(Record, Option<Nearest>)> {
+    let req = AddRequest {
+        text,
+        kind,
+        weight: weight.unwrap_or(1.0),
+        tags,
+        attrs,
+        auto_weight: weight.is_none(),
+        expected_generation,
+    };
+    let resp: AddResponse = call(url, "add", &req)?;
+    let nearest = match (resp.nearest_id, resp.nearest_similarity) {
+        (Some(id), Some(similarity)) => Some(Nearest { id, similarity }),
+        _ => None,
+    };
+    let record = Record {
+        id: resp.id,
+        ts: resp.ts,
+        kind: resp.kind,
+        weight: resp.weight,
+        text: resp.text,
+        vector: Vec::new(),
+        tags: resp.tags,
+        attrs: resp.attrs,
+    };
+    Ok((record, nearest))
+}
+
+pub fn search(url: &str, query: &str, options: &SearchOptions) -> io::Result<Vec<(f32, Record)>> {
+    let filter = options.filter.clone();
+    let req = SearchRequest {
+        query: query.to_string(),
+        limit: options.limit,
+        must: filter.must,
+        must_not: filter.must_not,
+        per_kind: options.per_kind,
+        kind: filter.kind,
+        tags: filter.tags,
+        since: filter.since,
+        until: filter.until,
+        as_of: filter.as_of,
+        lang: filter.lang,
+        max_age: options.max_age,
+    };
+    let hits: Vec<ApiHit> = call(url, "search", &req)?;
+    Ok(hits
+        .into_iter()
+        .map(|hit| (hit.score, hit.record.into()))
+        .collect())
+}
+
+pub fn get(url: &str, id: u128) -> io::Result<Record> {
+    let rec: ApiRecord = call(url, "get", &GetRequest { id })?;
+    Ok(rec.into())
+}
+
+pub fn recent(url: &str, limit: usize) -> io::Result<Vec<Record>> {
+    let recs: Vec<ApiRecord> = call(url, "recent", &RecentRequest { limit })?;
+    Ok(recs.into_iter().map(Record::from).collect())
+}
+
+pub fn compact(url: &str, keep: usize) -> io::Result<()> {
+    call(url, "compact", &CompactRequest { keep })
+}
+
+fn call<Req: Serialize, Resp: DeserializeOwned>(
+    url: &str,
+    op: &str,
+    req: &Req,
+) -> io::Result<Resp> {
+    let mut request = ureq::post(&format!("{url}/{op}"));
+    if let Ok(token) = env::var("MEMSTORE_TOKEN") {
+        request = request.set("Authorization", &format!("Bearer {token}"));
+    }
+    match request.send_json(req) {
+        Ok(resp) => resp.into_json(),
+        Err(ureq::Error::Status(code, resp)) => {
+            let msg = resp
+                .into_json::<ApiError>()
+                .map(|e| e.error)
+                .unwrap_or_default();
+            Err(io::Error::other(format!("server returned {code}: {msg}")))
+        }
+        Err(e) => Err(io::Error::other(e.to_string())),
+    }
+}
@@ -0,0 +1,70 @@
+//! `repair --reassign-dup-ids`: ids are wall-clock millis, so batch imports and
+//! concurrent writers could mint the same id twice. The first record with an id
+//! keeps it, so `supersedes` and `chunk_of` links naming it still resolve; every
+//! later one gets the next free id and keeps the old one in `previous_id`.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::store::update_store;
+use crate::{config, local_only, path_arg, resolve_path, store_error};
+
+pub fn cmd_repair(args: &[String]) -> Result<(), &'static str> {
+    let mut reassign = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--reassign-dup-ids" => reassign = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    if !reassign {
+        eprintln!("Usage: repair --reassign-dup-ids [--path <file>] [--profile <name>]");
+        return Err("nothing to repair");
+    }
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    let changed = update_store(&path, |store| {
+        let mut taken: HashSet<u128> = store.records.iter().map(|r| r.id).collect();
+        let mut kept: HashSet<u128> = HashSet::new();
+        let mut changed = Vec::new();
+        for rec in store.records.iter_mut() {
+            if kept.insert(rec.id) {
+                continue;
+            }
+            let old = rec.id;
+            let mut id = old + 1;
+            while taken.contains(&id) {
+                id += 1;
+            }
+            taken.insert(id);
+            rec.id = id;
+            rec.attrs.insert("previous_id".to_string(), old.to_string());
+            changed.push((old, id));
+        }
+        Ok(changed)
+    })
+    .map_err(store_error(&path, "write failed"))?;
+    for (old, new) in &changed {
+        println!("{old}\t{new}");
+    }
+    eprintln!("Reassigned {} duplicate ids", changed.len());
+    Ok(())
+}
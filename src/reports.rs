@@ -0,0 +1,360 @@
+//! Read-only summaries over a whole store.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::filter::RecordFilter;
+use crate::{
+    collapse_hits, config, dates, load_store, local_only, now_secs, parse_since, path_arg,
+    resolve_path, store_error, tsv_field, Record,
+};
+
+/// Records at least this similar are folded into one digest line.
+const DIGEST_DUPLICATE_SIM: f32 = 0.95;
+
+#[derive(Serialize)]
+struct Bucket {
+    period: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    count: usize,
+}
+
+pub fn cmd_aggregate(args: &[String]) -> Result<(), &'static str> {
+    let mut by = "day".to_string();
+    let mut group: Option<String> = None;
+    let mut format = "table".to_string();
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--by" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    by = v.clone();
+                }
+            }
+            "--group" => {
+                i += 1;
+                group = args.get(i).cloned();
+            }
+            "--format" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    format = v.clone();
+                }
+            }
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let period_of: fn(i64) -> String = match by.as_str() {
+        "day" => dates::format_date,
+        "week" => |ts| dates::format_date(dates::week_start(ts)),
+        _ => {
+            eprintln!("Unknown --by {by:?} (expected day or week)");
+            return Err("invalid argument");
+        }
+    };
+    let by_kind = match group.as_deref() {
+        None => false,
+        Some("kind") => true,
+        Some(other) => {
+            eprintln!("Unknown --group {other:?} (expected kind)");
+            return Err("invalid argument");
+        }
+    };
+    if format != "table" && format != "json" {
+        eprintln!("Unknown --format {format:?} (expected table or json)");
+        return Err("invalid argument");
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+
+    let mut counts: BTreeMap<(String, Option<String>), usize> = BTreeMap::new();
+    for rec in store.records.iter().filter(|r| filter.matches(r)) {
+        let kind = by_kind.then(|| rec.kind.clone());
+        *counts.entry((period_of(rec.ts), kind)).or_default() += 1;
+    }
+    let buckets: Vec<Bucket> = counts
+        .into_iter()
+        .map(|((period, kind), count)| Bucket {
+            period,
+            kind,
+            count,
+        })
+        .collect();
+
+    if format == "json" {
+        let json = serde_json::to_string_pretty(&buckets).map_err(|_| "encode failed")?;
+        println!("{json}");
+        return Ok(());
+    }
+    for b in &buckets {
+        match &b.kind {
+            Some(kind) => println!("{}\t{kind}\t{}", b.period, b.count),
+            None => println!("{}\t{}", b.period, b.count),
+        }
+    }
+    Ok(())
+}
+
+pub fn cmd_timeline(args: &[String]) -> Result<(), &'static str> {
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+    let mut records: Vec<_> = store.records.iter().filter(|r| filter.matches(r)).collect();
+    records.sort_by_key(|r| (r.ts, r.id));
+
+    let mut current_day = String::new();
+    for rec in records {
+        let day = dates::format_date(rec.ts);
+        if day != current_day {
+            if !current_day.is_empty() {
+                println!();
+            }
+            println!("{day}");
+            current_day = day;
+        }
+        println!(
+            "  {}\t{}\t{}\t{}",
+            dates::format_time(rec.ts),
+            rec.kind,
+            rec.id,
+            tsv_field(&rec.text)
+        );
+    }
+    Ok(())
+}
+
+/// New records grouped by kind, largest group first, with near-duplicates
+/// folded into the first of them (`+n similar`); readable as it is, or as
+/// Markdown for email or a journal.
+pub fn cmd_digest(args: &[String]) -> Result<(), &'static str> {
+    let mut format = "text".to_string();
+    let mut filter = RecordFilter::default();
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--since" => {
+                i += 1;
+                filter.since = Some(parse_since(args.get(i))?);
+            }
+            "--format" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    format = v.clone();
+                }
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    let markdown = match format.as_str() {
+        "text" => false,
+        "markdown" | "md" => true,
+        _ => {
+            eprintln!("Unknown --format {format:?} (expected text or markdown)");
+            return Err("invalid argument");
+        }
+    };
+
+    let now = now_secs();
+    let since = *filter.since.get_or_insert(now - 86400);
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+
+    let mut by_kind: BTreeMap<&str, Vec<&Record>> = BTreeMap::new();
+    for rec in store.records.iter().filter(|r| filter.matches(r)) {
+        by_kind.entry(rec.kind.as_str()).or_default().push(rec);
+    }
+    let mut groups: Vec<(&str, Vec<&Record>)> = by_kind.into_iter().collect();
+    groups.sort_by_key(|(_, recs)| std::cmp::Reverse(recs.len()));
+    let total: usize = groups.iter().map(|(_, recs)| recs.len()).sum();
+    let kinds = groups.len();
+
+    let stamp = |ts: i64| format!("{} {}", dates::format_date(ts), dates::format_time(ts));
+    let range = format!("{} to {} UTC", stamp(since), stamp(now));
+    if markdown {
+        println!("# Memory digest, {range}");
+        println!();
+        println!("{total} new records in {kinds} kinds.");
+    } else {
+        println!("Digest {range}: {total} new records in {kinds} kinds");
+    }
+    for (kind, mut recs) in groups {
+        let count = recs.len();
+        recs.sort_by_key(|r| (r.ts, r.id));
+        println!();
+        if markdown {
+            println!("## {kind} ({count})");
+            println!();
+        } else {
+            println!("{kind} ({count})");
+        }
+        for (rec, folded) in collapse_hits(recs, Some(DIGEST_DUPLICATE_SIM), |r| *r) {
+            let text = tsv_field(&rec.text);
+            let similar = match folded {
+                0 => String::new(),
+                n if markdown => format!(" _(+{n} similar)_"),
+                n => format!("  (+{n} similar)"),
+            };
+            if markdown {
+                println!("- **{}** {text}{similar}", stamp(rec.ts));
+            } else {
+                println!("  {}  {text}{similar}", stamp(rec.ts));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Breaks the encoded size of the records down by kind, by month, and into
+/// vectors vs text vs everything else (ids, timestamps, kinds, tags).
+pub fn cmd_du(args: &[String]) -> Result<(), &'static str> {
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+    let file_bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+
+    let (mut vectors, mut text, mut total) = (0u64, 0u64, 0u64);
+    let mut by_kind: BTreeMap<&str, u64> = BTreeMap::new();
+    let mut by_month: BTreeMap<String, u64> = BTreeMap::new();
+    for rec in &store.records {
+        let size = bincode::serialized_size(rec).map_err(|_| "encode failed")?;
+        // bincode prefixes each Vec/String with a u64 length.
+        vectors += 8 + 4 * rec.vector.len() as u64;
+        text += 8 + rec.text.len() as u64;
+        total += size;
+        *by_kind.entry(rec.kind.as_str()).or_default() += size;
+        *by_month.entry(dates::format_month(rec.ts)).or_default() += size;
+    }
+
+    let pct = |n: u64| {
+        if total == 0 {
+            0.0
+        } else {
+            n as f64 * 100.0 / total as f64
+        }
+    };
+    println!("file\t{file_bytes}");
+    println!("records\t{total}\t{}", store.records.len());
+    println!("vectors\t{vectors}\t{:.1}%", pct(vectors));
+    println!("text\t{text}\t{:.1}%", pct(text));
+    println!("other\t{}\t{:.1}%", total - vectors - text, pct(total - vectors - text));
+    for (kind, bytes) in &by_kind {
+        println!("kind\t{kind}\t{bytes}\t{:.1}%", pct(*bytes));
+    }
+    for (month, bytes) in &by_month {
+        println!("month\t{month}\t{bytes}\t{:.1}%", pct(*bytes));
+    }
+    Ok(())
+}
@@ -0,0 +1,253 @@
+//! `review`: spaced repetition over the store, to keep what it holds in the
+//! user's own head as well.
+//!
+//! ```text
+//! review [-n 10] [--kind <kind>] [--tag <tag>]   list records due, most overdue first
+//! review --interactive [-n 10] ...                show each, read a grade 0-5 from stdin
+//! review --id <id> --grade <0-5>                  grade one record
+//! ```
+//!
+//! Grades follow SM-2: 5 is instant recall, 3 recalled with effort, below 3
+//! forgotten. A pass grows the interval (1 day, 6 days, then times the ease),
+//! a miss starts it over at a day; the ease moves with every grade. The
+//! schedule lives in the record's attributes (`review_due`, `review_interval`
+//! in days, `review_ease`, `review_reps`) along with `last_accessed`, the time
+//! of the last review. A record never reviewed is due a day after it was
+//! added.
+//!
+//! Grading also moves the record's weight, by 0.1 per grade below 3 and back
+//! down by 0.1 per grade above it, within the profile's `weight_range`, so
+//! searches surface what keeps being forgotten.
+
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use crate::filter::RecordFilter;
+use crate::store::{load_store, update_store};
+use crate::{
+    config, local_only, now_secs, parse_flag, path_arg, resolve_path, store_error, tsv_field,
+    Record,
+};
+
+const DUE: &str = "review_due";
+const INTERVAL: &str = "review_interval";
+const EASE: &str = "review_ease";
+const REPS: &str = "review_reps";
+const LAST_ACCESSED: &str = "last_accessed";
+
+const DAY: i64 = 86400;
+const START_EASE: f32 = 2.5;
+const MIN_EASE: f32 = 1.3;
+const WEIGHT_STEP: f32 = 0.1;
+
+pub fn cmd_review(args: &[String]) -> Result<(), &'static str> {
+    let mut n: usize = 10;
+    let mut filter = RecordFilter::default();
+    let mut interactive = false;
+    let mut id: Option<u128> = None;
+    let mut grade: Option<u8> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "-n" => {
+                i += 1;
+                n = parse_flag("-n", args.get(i))?;
+            }
+            "--kind" => {
+                i += 1;
+                filter.kind = args.get(i).cloned();
+            }
+            "--tag" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    filter.tags.push(v.clone());
+                }
+            }
+            "--interactive" | "-i" => interactive = true,
+            "--id" => {
+                i += 1;
+                id = Some(parse_flag("--id", args.get(i))?);
+            }
+            "--grade" => {
+                i += 1;
+                grade = Some(parse_grade(args.get(i).map(String::as_str))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let limits = config::add_limits(&profile)?;
+    let (min_weight, max_weight) = limits
+        .weight_range
+        .map_or((0.0, f32::MAX), |r| (r.min, r.max));
+    let now = now_secs();
+
+    let grades = match (id, grade) {
+        (Some(id), Some(grade)) => BTreeMap::from([(id, grade)]),
+        (None, None) => {
+            let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+            let mut due: Vec<(i64, &Record)> = store
+                .records
+                .iter()
+                .filter(|rec| filter.matches(rec))
+                .map(|rec| (due_at(rec), rec))
+                .filter(|(due, _)| *due <= now)
+                .collect();
+            due.sort_by_key(|(due, _)| *due);
+            due.truncate(n);
+            if due.is_empty() {
+                eprintln!("Nothing is due for review");
+                return Ok(());
+            }
+            if !interactive {
+                for (due, rec) in &due {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        (now - due) / DAY,
+                        rec.kind,
+                        rec.id,
+                        tsv_field(&rec.text)
+                    );
+                }
+                return Ok(());
+            }
+            ask_grades(&due)
+        }
+        _ => {
+            eprintln!("--id and --grade go together");
+            return Err("invalid review");
+        }
+    };
+    if grades.is_empty() {
+        return Ok(());
+    }
+
+    let graded = update_store(&path, |store| {
+        let mut graded = 0;
+        for rec in store.records.iter_mut() {
+            let Some(&grade) = grades.get(&rec.id) else {
+                continue;
+            };
+            schedule(rec, grade, now);
+            let step = (3.0 - grade as f32) * WEIGHT_STEP;
+            rec.weight = (rec.weight + step).clamp(min_weight, max_weight);
+            graded += 1;
+        }
+        Ok(graded)
+    })
+    .map_err(store_error(&path, "write failed"))?;
+    if let Some(id) = id.filter(|_| graded == 0) {
+        eprintln!("No record with id {id}");
+        return Err("unknown id");
+    }
+    if graded < grades.len() {
+        eprintln!(
+            "{} graded records were deleted meanwhile",
+            grades.len() - graded
+        );
+    }
+    eprintln!("Reviewed {graded} records");
+    Ok(())
+}
+
+/// Shows each record on stderr and reads its grade from stdin. An empty line
+/// skips the record, `q` or the end of input stops early.
+fn ask_grades(due: &[(i64, &Record)]) -> BTreeMap<u128, u8> {
+    let mut grades = BTreeMap::new();
+    let mut lines = io::stdin().lock().lines();
+    for (n, (_, rec)) in due.iter().enumerate() {
+        eprintln!(
+            "\n[{}/{}] {} {}\n{}",
+            n + 1,
+            due.len(),
+            rec.kind,
+            rec.id,
+            rec.text
+        );
+        loop {
+            eprint!("grade 0-5 (enter skips, q quits): ");
+            let _ = io::stderr().flush();
+            let Some(Ok(line)) = lines.next() else {
+                return grades;
+            };
+            match line.trim() {
+                "" => break,
+                "q" => return grades,
+                value => match parse_grade(Some(value)) {
+                    Ok(grade) => {
+                        grades.insert(rec.id, grade);
+                        break;
+                    }
+                    Err(_) => continue,
+                },
+            }
+        }
+    }
+    grades
+}
+
+fn parse_grade(value: Option<&str>) -> Result<u8, &'static str> {
+    match value.and_then(|v| v.parse::<u8>().ok()) {
+        Some(grade) if grade <= 5 => Ok(grade),
+        _ => {
+            eprintln!("A grade is a number from 0 (forgotten) to 5 (instant recall)");
+            Err("invalid grade")
+        }
+    }
+}
+
+fn due_at(rec: &Record) -> i64 {
+    attr(rec, DUE).unwrap_or(rec.ts + DAY)
+}
+
+fn attr<T: std::str::FromStr>(rec: &Record, key: &str) -> Option<T> {
+    rec.attrs.get(key).and_then(|v| v.parse().ok())
+}
+
+/// One SM-2 step for `grade`, reviewed at `now`.
+fn schedule(rec: &mut Record, grade: u8, now: i64) {
+    let reps: u32 = attr(rec, REPS).unwrap_or(0);
+    let interval: f32 = attr(rec, INTERVAL).unwrap_or(0.0);
+    let ease: f32 = attr(rec, EASE).unwrap_or(START_EASE);
+
+    let (reps, interval) = if grade >= 3 {
+        let interval = match reps {
+            0 => 1.0,
+            1 => 6.0,
+            _ => (interval * ease).round(),
+        };
+        (reps + 1, interval)
+    } else {
+        (0, 1.0)
+    };
+    let miss = (5 - grade) as f32;
+    let ease = (ease + 0.1 - miss * (0.08 + miss * 0.02)).max(MIN_EASE);
+
+    let attrs = &mut rec.attrs;
+    attrs.insert(
+        DUE.to_string(),
+        (now + (interval * DAY as f32) as i64).to_string(),
+    );
+    attrs.insert(INTERVAL.to_string(), interval.to_string());
+    attrs.insert(EASE.to_string(), format!("{ease:.2}"));
+    attrs.insert(REPS.to_string(), reps.to_string());
+    attrs.insert(LAST_ACCESSED.to_string(), now.to_string());
+}
@@ -0,0 +1,235 @@
+//! `rpc`: newline-delimited JSON-RPC 2.0 on stdin/stdout for one store, so a
+//! plugin or agent harness can keep a single process instead of forking per call.
+//!
+//! ```text
+//! {"jsonrpc":"2.0","id":1,"method":"search","params":{"query":"rollback","limit":3}}
+//! {"jsonrpc":"2.0","id":1,"result":[{"score":0.82,"record":{...}}]}
+//! ```
+//!
+//! Params and results are the `serve` request and response bodies; `delete`
+//! takes `{"ids":[...]}` and returns `{"deleted":n,"generation":g}`, and
+//! `generation` returns `{"generation":g}`; `tx` takes `{"ops":[...]}` in the
+//! format of the `tx` command and returns `{"applied":[...],"generation":g}`.
+//! `add`, `delete` and `tx` accept `expected_generation` and fail with -32001
+//! if the store has moved on since (compare-and-swap for read-modify-write
+//! flows). Writes are held to the profile's `max_text`, `weight_range` and
+//! kind registry, and `add` and `tx` run `auto_compact` afterwards, as the
+//! `add` command and `serve` do. Searches use the profile's scoring, index and
+//! `default_max_age`, and repeated ones are answered from a cache until the
+//! store changes (see `search_cache.rs`).
+
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{AddLimits, AutoCompact};
+use crate::search_cache::SearchCache;
+use crate::server::{
+    handle_add_limited, handle_generation, handle_recent, ApiRecord, GetRequest, RecentRequest,
+    SearchRequest,
+};
+use crate::store::{update_store_if, GenerationConflict};
+use crate::tx::{self, TxRequest};
+use crate::{auto_compact, config, get_record, local_only, path_arg, resolve_path, SearchOptions};
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Store errors (I/O, corrupt file, unknown id).
+const STORE_ERROR: i64 = -32000;
+/// `expected_generation` no longer matches the store.
+const CONFLICT: i64 = -32001;
+
+#[derive(Deserialize)]
+struct RpcRequest {
+    method: String,
+    #[serde(default)]
+    params: Value,
+    /// Absent for notifications, which get no reply.
+    id: Option<Value>,
+}
+
+#[derive(Serialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct DeleteRequest {
+    ids: Vec<u128>,
+    #[serde(default)]
+    expected_generation: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct DeleteResponse {
+    deleted: usize,
+    generation: u64,
+}
+
+/// The store a session serves, the profile limits its writes are held to and
+/// the profile's search settings.
+struct Session {
+    path: PathBuf,
+    cache: SearchCache,
+    search: SearchOptions,
+    add_limits: AddLimits,
+    compact: Option<AutoCompact>,
+}
+
+pub fn cmd_rpc(args: &[String]) -> Result<(), &'static str> {
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let session = Session {
+        add_limits: config::add_limits(&profile)?,
+        compact: config::auto_compact(&profile)?,
+        cache: SearchCache::default(),
+        search: SearchOptions::from_profile(&profile),
+        path,
+    };
+
+    let stdout = io::stdout();
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|_| "read failed")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<RpcRequest>(&line) {
+            Ok(req) => {
+                let outcome = dispatch(&session, &req.method, req.params);
+                match req.id {
+                    Some(id) => reply(id, outcome),
+                    None => continue,
+                }
+            }
+            Err(e) => {
+                let code = if serde_json::from_str::<Value>(&line).is_ok() {
+                    INVALID_REQUEST
+                } else {
+                    PARSE_ERROR
+                };
+                reply(Value::Null, Err(rpc_error(code, e.to_string())))
+            }
+        };
+        let mut out = stdout.lock();
+        writeln!(out, "{response}")
+            .and_then(|_| out.flush())
+            .map_err(|_| "write failed")?;
+    }
+    Ok(())
+}
+
+/// Runs one call and returns its result already encoded as JSON.
+fn dispatch(session: &Session, method: &str, params: Value) -> Result<String, RpcError> {
+    let Session {
+        path,
+        cache,
+        search,
+        add_limits,
+        ..
+    } = session;
+    if matches!(method, "add" | "delete" | "tx") {
+        cache.invalidate(path);
+    }
+    match method {
+        "add" => call(params, |req| {
+            let resp = handle_add_limited(path, req, add_limits)?;
+            compact(session)?;
+            Ok(resp)
+        }),
+        "search" => call(params, |req: SearchRequest| cache.search(path, req, search)),
+        "get" => call(params, |req: GetRequest| {
+            get_record(path, req.id).map(ApiRecord::from)
+        }),
+        "recent" => call(params, |req: RecentRequest| handle_recent(path, req)),
+        "delete" => call(params, |req: DeleteRequest| {
+            let (deleted, generation) = update_store_if(path, req.expected_generation, |store| {
+                let before = store.records.len();
+                store.records.retain(|r| !req.ids.contains(&r.id));
+                Ok(before - store.records.len())
+            })?;
+            Ok(DeleteResponse {
+                deleted,
+                generation,
+            })
+        }),
+        "tx" => call(params, |req: TxRequest| {
+            let resp = tx::apply(path, req.ops, add_limits, req.expected_generation)?;
+            compact(session)?;
+            Ok(resp)
+        }),
+        "generation" => call(params, |_: Value| handle_generation(path)),
+        other => Err(rpc_error(
+            METHOD_NOT_FOUND,
+            format!("unknown method {other}"),
+        )),
+    }
+}
+
+/// Runs `auto_compact` after a write, if the profile or config sets limits.
+fn compact(session: &Session) -> io::Result<()> {
+    if let Some(limits) = &session.compact {
+        auto_compact(&session.path, limits)?;
+    }
+    Ok(())
+}
+
+fn call<Req, Resp, F>(params: Value, f: F) -> Result<String, RpcError>
+where
+    Req: DeserializeOwned,
+    Resp: Serialize,
+    F: FnOnce(Req) -> io::Result<Resp>,
+{
+    let req: Req = serde_json::from_value(params)
+        .map_err(|e| rpc_error(INVALID_PARAMS, format!("invalid params: {e}")))?;
+    let resp = f(req).map_err(|e| match e.kind() {
+        io::ErrorKind::InvalidInput => rpc_error(INVALID_PARAMS, e.to_string()),
+        _ if GenerationConflict::is(&e) => rpc_error(CONFLICT, e.to_string()),
+        _ => rpc_error(STORE_ERROR, e.to_string()),
+    })?;
+    serde_json::to_string(&resp).map_err(|e| rpc_error(STORE_ERROR, e.to_string()))
+}
+
+fn rpc_error(code: i64, message: String) -> RpcError {
+    RpcError { code, message }
+}
+
+/// Builds the response line by hand so u128 ids in results are written as
+/// plain numbers rather than going through `serde_json::Value`.
+fn reply(id: Value, outcome: Result<String, RpcError>) -> String {
+    let id = serde_json::to_string(&id).unwrap_or_else(|_| "null".to_string());
+    match outcome {
+        Ok(result) => format!(r#"{{"jsonrpc":"2.0","id":{id},"result":{result}}}"#),
+        Err(error) => {
+            let error = serde_json::to_string(&error).unwrap_or_default();
+            format!(r#"{{"jsonrpc":"2.0","id":{id},"error":{error}}}"#)
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! `schema`: JSON tool definitions for LLM function calling. Each tool's
+//! parameters are exactly the params of an `rpc` method (and the body of the
+//! matching `serve` endpoint), so a harness can forward the model's arguments
+//! unchanged:
+//!
+//! | tool              | rpc method | endpoint                     |
+//! |-------------------|------------|------------------------------|
+//! | `add_memory`      | `add`      | `POST /stores/<name>/add`    |
+//! | `search_memory`   | `search`   | `POST /stores/<name>/search` |
+//! | `recent_memories` | `recent`   | `POST /stores/<name>/recent` |
+
+use serde_json::{json, Value};
+
+pub fn cmd_schema(args: &[String]) -> Result<(), &'static str> {
+    let mut format = "openai".to_string();
+
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--format" {
+            i += 1;
+            if let Some(v) = args.get(i) {
+                format = v.clone();
+            }
+        }
+        i += 1;
+    }
+
+    let tools: Vec<Value> = match format.as_str() {
+        "openai" => tools()
+            .into_iter()
+            .map(|(name, description, parameters)| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": name,
+                        "description": description,
+                        "parameters": parameters,
+                    }
+                })
+            })
+            .collect(),
+        "anthropic" => tools()
+            .into_iter()
+            .map(|(name, description, input_schema)| {
+                json!({
+                    "name": name,
+                    "description": description,
+                    "input_schema": input_schema,
+                })
+            })
+            .collect(),
+        other => {
+            eprintln!("Unknown --format {other:?} (expected openai or anthropic)");
+            return Err("invalid argument");
+        }
+    };
+    let json = serde_json::to_string_pretty(&tools).map_err(|_| "encode failed")?;
+    println!("{json}");
+    Ok(())
+}
+
+/// Name, description and parameter schema of each tool. Defaults are those
+/// of `AddRequest`, `SearchRequest` and `RecentRequest`.
+fn tools() -> Vec<(&'static str, &'static str, Value)> {
+    vec![
+        (
+            "add_memory",
+            "Store a memory for later retrieval: a decision, a fact about the user or \
+             project, or a summary of work done. Keep each memory to one self-contained \
+             statement. Returns the stored record and its id.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "text": {
+                        "type": "string",
+                        "description": "The memory, written so it makes sense without the current conversation."
+                    },
+                    "kind": {
+                        "type": "string",
+                        "description": "Category such as summary, decision, fact or preference.",
+                        "default": "summary"
+                    },
+                    "weight": {
+                        "type": "number",
+                        "description": "Importance; higher ranks earlier in search.",
+                        "minimum": 0,
+                        "default": 1.0
+                    },
+                    "tags": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Labels to filter on, e.g. a project or person."
+                    },
+                    "auto_weight": {
+                        "type": "boolean",
+                        "description": "Ignore weight and let the store estimate importance.",
+                        "default": false
+                    }
+                },
+                "required": ["text"]
+            }),
+        ),
+        (
+            "search_memory",
+            "Search stored memories by meaning. Use before answering questions that \
+             may depend on earlier sessions. Returns records with a relevance score, \
+             best first.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "query": {
+                        "type": "string",
+                        "description": "What to look for, in natural language."
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of results.",
+                        "minimum": 1
+                    },
+                    "must": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Terms every result must contain."
+                    },
+                    "must_not": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Terms no result may contain."
+                    },
+                    "per_kind": {
+                        "type": "integer",
+                        "description": "At most this many results of each kind.",
+                        "minimum": 1
+                    }
+                },
+                "required": ["query", "limit"]
+            }),
+        ),
+        (
+            "recent_memories",
+            "List the most recently stored memories, newest first. Use at the start of \
+             a session to pick up where the last one left off.",
+            json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "description": "Number of records to return.",
+                        "minimum": 1
+                    }
+                },
+                "required": ["limit"]
+            }),
+        ),
+    ]
+}
@@ -0,0 +1,196 @@
+//! Search results remembered by `serve` and `rpc`, so an agent asking the same
+//! question again within a session skips the index search and scoring.
+//!
+//! An entry holds only the ids and scores of the hits; answering from it
+//! reloads the store and looks the records up. Entries are dropped when this
+//! process changes the store, when the store's fingerprint changes (another
+//! process wrote it) and after `TTL`, since the recency term makes scores
+//! drift as time passes. Stores without checksums have no fingerprint and are
+//! never cached.
+//!
+//! The lock is only held to look up and store entries, never across a search,
+//! so `serve`'s worker threads still search in parallel.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::index_cache::Fingerprint;
+use crate::server::{handle_search_with, ApiHit, SearchRequest};
+use crate::SearchOptions;
+use crate::store::load_store;
+
+const TTL: Duration = Duration::from_secs(300);
+const MAX_ENTRIES: usize = 256;
+
+/// Queries that differ only in whitespace share an entry.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key {
+    path: PathBuf,
+    query: String,
+    limit: usize,
+    must: Vec<String>,
+    must_not: Vec<String>,
+    per_kind: Option<usize>,
+    kind: Option<String>,
+    tags: Vec<String>,
+    since: Option<i64>,
+    until: Option<i64>,
+    as_of: Option<i64>,
+    lang: Option<String>,
+    max_age: Option<i64>,
+    /// The caller's scoring and index settings, which change the hits too.
+    settings: String,
+}
+
+struct Entry {
+    fingerprint: Fingerprint,
+    at: Instant,
+    hits: Vec<(f32, u128)>,
+}
+
+#[derive(Default)]
+pub struct SearchCache {
+    entries: Mutex<HashMap<Key, Entry>>,
+}
+
+impl SearchCache {
+    /// `handle_search_with`, answered from the cache when the same search ran
+    /// with the same settings against the same version of the store.
+    pub fn search(
+        &self,
+        path: &Path,
+        req: SearchRequest,
+        options: &SearchOptions,
+    ) -> io::Result<Vec<ApiHit>> {
+        let key = Key {
+            path: path.to_path_buf(),
+            query: req.query.split_whitespace().collect::<Vec<_>>().join(" "),
+            limit: req.limit,
+            must: req.must.clone(),
+            must_not: req.must_not.clone(),
+            per_kind: req.per_kind,
+            kind: req.kind.clone(),
+            tags: req.tags.clone(),
+            since: req.since,
+            until: req.until,
+            as_of: req.as_of,
+            lang: req.lang.clone(),
+            max_age: req.max_age,
+            settings: format!("{options:?}"),
+        };
+        // Taken before searching, so a write in between only causes a miss later.
+        let Some(fingerprint) = Fingerprint::of(path) else {
+            return handle_search_with(path, req, options);
+        };
+        let cached = self
+            .entries()
+            .get(&key)
+            .filter(|e| e.fingerprint == fingerprint && e.at.elapsed() < TTL)
+            .map(|e| e.hits.clone());
+        if let Some(cached) = cached {
+            if let Some(hits) = lookup(path, &cached)? {
+                return Ok(hits);
+            }
+        }
+        let hits = handle_search_with(path, req, options)?;
+        let mut entries = self.entries();
+        if entries.len() >= MAX_ENTRIES {
+            evict(&mut entries);
+        }
+        entries.insert(
+            key,
+            Entry {
+                fingerprint,
+                at: Instant::now(),
+                hits: hits.iter().map(|h| (h.score, h.record.id)).collect(),
+            },
+        );
+        Ok(hits)
+    }
+
+    /// Drops every entry for the store at `path`; call after changing it.
+    pub fn invalidate(&self, path: &Path) {
+        self.entries().retain(|key, _| key.path != path);
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<Key, Entry>> {
+        self.entries.lock().expect("search cache lock")
+    }
+}
+
+/// Drops expired entries, or the oldest one if none has expired.
+fn evict(entries: &mut HashMap<Key, Entry>) {
+    entries.retain(|_, e| e.at.elapsed() < TTL);
+    if entries.len() >= MAX_ENTRIES {
+        let oldest = entries
+            .iter()
+            .min_by_key(|(_, e)| e.at)
+            .map(|(k, _)| k.clone());
+        if let Some(oldest) = oldest {
+            entries.remove(&oldest);
+        }
+    }
+}
+
+/// The cached hits with their records, or `None` if one has gone missing.
+fn lookup(path: &Path, hits: &[(f32, u128)]) -> io::Result<Option<Vec<ApiHit>>> {
+    let mut records: HashMap<u128, _> = load_store(path)?
+        .records
+        .into_iter()
+        .map(|r| (r.id, r))
+        .collect();
+    Ok(hits
+        .iter()
+        .map(|&(score, id)| {
+            records.remove(&id).map(|rec| ApiHit {
+                score,
+                record: rec.into(),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+    use crate::config::Profile;
+    use crate::server::{handle_add, AddRequest};
+
+    #[test]
+    fn different_settings_do_not_share_hits() {
+        let dir = env::temp_dir().join(format!("memstore-search-cache-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("memories.hnsw");
+        let add: AddRequest = serde_json::from_value(serde_json::json!({
+            "text": "deploy on fridays",
+            "kind": "decision",
+        }))
+        .unwrap();
+        handle_add(&path, add).unwrap();
+
+        let request = || -> SearchRequest {
+            serde_json::from_value(serde_json::json!({"query": "deploy", "limit": 3})).unwrap()
+        };
+        let plain = SearchOptions::from_profile(&Profile::default());
+        let mut boosted = plain.clone();
+        boosted
+            .scoring
+            .kind_boost
+            .insert("decision".to_string(), 3.0);
+
+        let cache = SearchCache::default();
+        let first = cache.search(&path, request(), &plain).unwrap();
+        let second = cache.search(&path, request(), &boosted).unwrap();
+        assert_eq!(cache.entries().len(), 2);
+        assert!(second[0].score > first[0].score * 2.0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,211 @@
+//! `selftest`: a quick check that this build and machine can run a store at
+//! all, for after an upgrade or on a new machine. Works in a fresh store in
+//! the temp directory, never the configured one, and removes it afterwards
+//! (`--keep` leaves it for a look).
+//!
+//! The steps build on each other, so the first failure ends the run:
+//! adding records, finding one of them again, saving the search index and
+//! getting the same answer from the saved copy, deleting a record,
+//! compacting, and verifying the file that is left.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use crate::filter::RecordFilter;
+use crate::store::{load_store, update_store, verify_store};
+use crate::{
+    add_fitted, compact_store, index_cache, now_millis, oversize, search_store, IndexKind, Scoring,
+    SearchOptions,
+};
+
+/// Enough records that searches go through the index rather than a scan.
+const FILLER: usize = 40;
+const KEEP: usize = 10;
+const PROBE: &str = "The staging database password rotates every ninety days";
+const PROBE_QUERY: &str = "staging database password";
+const TOPICS: &[&str] = &[
+    "deploy rollback checklist",
+    "tokio runtime tuning",
+    "frontend bundle size",
+    "weekly planning notes",
+    "release branch naming",
+];
+
+type Step = fn(&Path) -> Result<String, String>;
+
+const STEPS: &[(&str, Step)] = &[
+    ("add", add),
+    ("search", search),
+    ("index", index),
+    ("delete", delete),
+    ("compact", compact),
+    ("verify", verify),
+];
+
+pub fn cmd_selftest(args: &[String]) -> Result<(), &'static str> {
+    let mut keep = false;
+    for arg in args {
+        if arg == "--keep" {
+            keep = true;
+        }
+    }
+
+    let dir = env::temp_dir().join(format!(
+        "memstore-selftest-{}-{}",
+        process::id(),
+        now_millis()
+    ));
+    if let Err(e) = fs::create_dir_all(&dir) {
+        eprintln!("{}: {e}", dir.display());
+        return Err("mkdir failed");
+    }
+    let path = dir.join("selftest.mem");
+
+    let mut failed = None;
+    for (name, step) in STEPS {
+        match step(&path) {
+            Ok(note) => println!("pass\t{name}\t{note}"),
+            Err(e) => {
+                println!("FAIL\t{name}\t{e}");
+                failed = Some(name);
+                break;
+            }
+        }
+    }
+
+    if keep {
+        eprintln!("Kept {}", dir.display());
+    } else if let Err(e) = fs::remove_dir_all(&dir) {
+        eprintln!("Warning: could not remove {}: {e}", dir.display());
+    }
+    match failed {
+        Some(name) => {
+            eprintln!("Self-test failed at {name}; later steps were skipped");
+            Err("selftest failed")
+        }
+        None => {
+            eprintln!("Self-test passed ({} steps)", STEPS.len());
+            Ok(())
+        }
+    }
+}
+
+fn add(path: &Path) -> Result<String, String> {
+    let mut texts: Vec<String> = (0..FILLER)
+        .map(|n| format!("Note {n} on {}", TOPICS[n % TOPICS.len()]))
+        .collect();
+    texts.insert(FILLER / 2, PROBE.to_string());
+    for text in &texts {
+        let fitted = oversize::fit(text.clone(), None)?;
+        add_fitted(
+            path,
+            fitted,
+            "fact".to_string(),
+            None,
+            Vec::new(),
+            BTreeMap::new(),
+            None,
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    let stored = records(path)?;
+    if stored != texts.len() {
+        return Err(format!(
+            "added {} records, store holds {stored}",
+            texts.len()
+        ));
+    }
+    Ok(format!("{stored} records"))
+}
+
+fn search(path: &Path) -> Result<String, String> {
+    let top = top_hit(path)?;
+    Ok(format!("found record {top}"))
+}
+
+fn index(path: &Path) -> Result<String, String> {
+    let before = top_hit(path)?;
+    let cached = index_cache::cache_files(path);
+    if cached.is_empty() {
+        return Err("no index was saved next to the store".to_string());
+    }
+    // Same store, so this search loads the saved index instead of building one.
+    let after = top_hit(path)?;
+    if after != before {
+        return Err(format!(
+            "saved index found {after}, built one found {before}"
+        ));
+    }
+    Ok(format!("{} files, same result reloaded", cached.len()))
+}
+
+fn delete(path: &Path) -> Result<String, String> {
+    let probe = top_hit(path)?;
+    let before = records(path)?;
+    update_store(path, |store| {
+        store.records.retain(|r| r.id != probe);
+        Ok(())
+    })
+    .map_err(|e| e.to_string())?;
+    if records(path)? != before - 1 {
+        return Err(format!("record {probe} was not removed"));
+    }
+    let hits = search_store(path, PROBE_QUERY, &options()).map_err(|e| e.to_string())?;
+    if hits.iter().any(|(_, rec)| rec.id == probe) {
+        return Err(format!("deleted record {probe} is still found"));
+    }
+    Ok(format!("record {probe}"))
+}
+
+fn compact(path: &Path) -> Result<String, String> {
+    let removed = compact_store(path, KEEP).map_err(|e| e.to_string())?;
+    let left = records(path)?;
+    if left != KEEP {
+        return Err(format!("kept {left} records, expected {KEEP}"));
+    }
+    Ok(format!("removed {}, kept {left}", removed.len()))
+}
+
+fn verify(path: &Path) -> Result<String, String> {
+    let report = verify_store(path).map_err(|e| e.to_string())?;
+    if let Some(problem) = report.problems.first() {
+        return Err(problem.clone());
+    }
+    Ok(format!(
+        "version {}, {} records",
+        report.version, report.records
+    ))
+}
+
+fn records(path: &Path) -> Result<usize, String> {
+    Ok(load_store(path).map_err(|e| e.to_string())?.records.len())
+}
+
+/// Id of the best hit for the probe query, which must be the probe record.
+fn top_hit(path: &Path) -> Result<u128, String> {
+    let hits = search_store(path, PROBE_QUERY, &options()).map_err(|e| e.to_string())?;
+    match hits.first() {
+        Some((_, rec)) if rec.text == PROBE => Ok(rec.id),
+        Some((_, rec)) => Err(format!("expected the probe record, found {:?}", rec.text)),
+        None => Err("no hits".to_string()),
+    }
+}
+
+fn options() -> SearchOptions {
+    SearchOptions {
+        limit: 1,
+        scoring: Scoring::default(),
+        filter: RecordFilter::default(),
+        per_kind: None,
+        index: IndexKind::Hnsw,
+        rerank: false,
+        window: None,
+        include_archive: false,
+        max_memory: None,
+        max_age: None,
+        timing: false,
+    }
+}
@@ -0,0 +1,692 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::config::{AddLimits, AutoCompact, Profile};
+use crate::filter::RecordFilter;
+use crate::search_cache::SearchCache;
+use crate::store::GenerationConflict;
+use crate::{
+    add_fitted, auto_compact, compact_store, get_record, oversize, parse_flag, path_arg,
+    recent_records, search_store, store_generation, warm, IndexKind, Record, SearchOptions,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddRequest {
+    pub text: String,
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: BTreeMap<String, String>,
+    /// Ignore `weight` and let the server estimate one.
+    #[serde(default)]
+    pub auto_weight: bool,
+    /// Fail with 409 unless the store is still at this generation.
+    #[serde(default)]
+    pub expected_generation: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchRequest {
+    pub query: String,
+    pub limit: usize,
+    #[serde(default)]
+    pub must: Vec<String>,
+    #[serde(default)]
+    pub must_not: Vec<String>,
+    #[serde(default)]
+    pub per_kind: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub until: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub as_of: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lang: Option<String>,
+    /// Seconds; see `SearchOptions::max_age`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<i64>,
+}
+
+fn default_kind() -> String {
+    "summary".to_string()
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetRequest {
+    pub id: u128,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecentRequest {
+    pub limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CompactRequest {
+    pub keep: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GenerationResponse {
+    pub generation: u64,
+}
+
+/// Record as exchanged over HTTP. Vectors stay on the server.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiRecord {
+    pub id: u128,
+    pub ts: i64,
+    pub kind: String,
+    pub weight: f32,
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub attrs: BTreeMap<String, String>,
+}
+
+/// Reply to `add`: the stored record's fields (so older clients can read it as an
+/// `ApiRecord`) plus the closest record that was already there. Spelled out rather
+/// than `#[serde(flatten)]`, which cannot buffer the u128 id.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddResponse {
+    pub id: u128,
+    pub ts: i64,
+    pub kind: String,
+    pub weight: f32,
+    pub text: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub attrs: BTreeMap<String, String>,
+    #[serde(default)]
+    pub nearest_id: Option<u128>,
+    #[serde(default)]
+    pub nearest_similarity: Option<f32>,
+    /// The store's generation after this add.
+    #[serde(default)]
+    pub generation: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiHit {
+    pub score: f32,
+    pub record: ApiRecord,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ApiError {
+    pub error: String,
+}
+
+/// `serve --tokens <file>`: which bearer tokens may use which stores.
+///
+/// ```toml
+/// [[tokens]]
+/// token = "agent-a-secret"
+/// access = "read-write"          # or "read"
+/// stores = ["agent-a", "shared"] # "*" for every store
+/// ```
+#[derive(Debug, Deserialize)]
+struct TokenFile {
+    #[serde(default)]
+    tokens: Vec<TokenGrant>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenGrant {
+    token: String,
+    access: Access,
+    stores: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Access {
+    Read,
+    ReadWrite,
+}
+
+/// An open `GET /events` stream and the stores it may see (`None` for all).
+struct Subscriber {
+    stores: Option<Vec<String>>,
+    frames: Sender<String>,
+}
+
+impl From<Record> for ApiRecord {
+    fn from(rec: Record) -> Self {
+        ApiRecord {
+            id: rec.id,
+            ts: rec.ts,
+            kind: rec.kind,
+            weight: rec.weight,
+            text: rec.text,
+            tags: rec.tags,
+            attrs: rec.attrs,
+        }
+    }
+}
+
+impl From<ApiRecord> for Record {
+    fn from(rec: ApiRecord) -> Self {
+        Record {
+            id: rec.id,
+            ts: rec.ts,
+            kind: rec.kind,
+            weight: rec.weight,
+            text: rec.text,
+            vector: Vec::new(),
+            tags: rec.tags,
+            attrs: rec.attrs,
+        }
+    }
+}
+
+pub fn cmd_serve(args: &[String]) -> Result<(), &'static str> {
+    let mut addr = "127.0.0.1:7700".to_string();
+    let mut dir: Option<PathBuf> = None;
+    let mut tokens: Option<PathBuf> = None;
+    let mut preload = false;
+    let mut threads: usize = 4;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--addr" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    addr = v.clone();
+                }
+            }
+            "--dir" => {
+                i += 1;
+                dir = args.get(i).map(|v| path_arg(v));
+            }
+            "--tokens" => {
+                i += 1;
+                tokens = args.get(i).map(|v| path_arg(v));
+            }
+            "--preload" => preload = true,
+            "--threads" => {
+                i += 1;
+                threads = parse_flag("--threads", args.get(i))?;
+                if threads == 0 {
+                    eprintln!("--threads must be at least 1");
+                    return Err("invalid threads");
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    // `memory/` in the cwd was the default before the per-user data directory.
+    let dir = dir.unwrap_or_else(|| match crate::config::data_dir() {
+        Some(data) if !Path::new("memory").is_dir() => data.join("stores"),
+        _ => PathBuf::from("memory"),
+    });
+    let grants = match &tokens {
+        Some(file) => Some(load_grants(file)?),
+        None => None,
+    };
+    let limits = crate::config::auto_compact(&Profile::default())?;
+    let add_limits = crate::config::add_limits(&Profile::default())?;
+    let server = Server::http(&addr).map_err(|e| {
+        eprintln!("Cannot listen on {addr}: {e}");
+        "listen failed"
+    })?;
+    eprintln!(
+        "memstore serving {} on http://{addr}/stores/<name>",
+        dir.display()
+    );
+    if let Some(grants) = &grants {
+        eprintln!("requiring a bearer token ({} configured)", grants.len());
+    }
+    if preload {
+        preload_stores(&dir);
+    }
+
+    let state = State {
+        dir,
+        grants,
+        limits,
+        add_limits,
+        subscribers: Mutex::new(Vec::new()),
+        cache: SearchCache::default(),
+        search: SearchOptions::from_profile(&Profile::default()),
+        writes: Mutex::new(()),
+    };
+    // Each worker takes the next request itself; see `State` for what they share.
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| serve_requests(&server, &state));
+        }
+    });
+    Ok(())
+}
+
+/// What `serve`'s worker threads share.
+///
+/// Consistency: reads (`search`, `get`, `recent`) take no lock and run in
+/// parallel. Each loads the store file, which saves replace atomically, so a
+/// read sees the store as of the last completed write: never half a write,
+/// and every write whose response was already sent. Writes (`add`, `compact`)
+/// run one at a time under `writes`, which also keeps their events in order;
+/// the store lock orders them against other processes.
+struct State {
+    dir: PathBuf,
+    grants: Option<Vec<TokenGrant>>,
+    limits: Option<AutoCompact>,
+    add_limits: AddLimits,
+    subscribers: Mutex<Vec<Subscriber>>,
+    cache: SearchCache,
+    /// Scoring and index settings for every search.
+    search: SearchOptions,
+    writes: Mutex<()>,
+}
+
+fn serve_requests(server: &Server, state: &State) {
+    for mut request in server.incoming_requests() {
+        if *request.method() == Method::Get && request.url().trim_end_matches('/') == "/events" {
+            subscribe(state.grants.as_deref(), request, &state.subscribers);
+            continue;
+        }
+        let (status, body) = handle(state, &mut request);
+        let header = Header::from_bytes("Content-Type", "application/json").expect("static header");
+        let response = Response::from_string(body)
+            .with_status_code(status)
+            .with_header(header);
+        if let Err(e) = request.respond(response) {
+            eprintln!("respond failed: {e}");
+        }
+    }
+}
+
+/// `serve --preload`: warms every store in `dir` before taking requests.
+/// A store that fails to load is reported and left for the first request.
+fn preload_stores(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut records = 0;
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().is_none_or(|ext| ext != "hnsw") || !path.is_file() {
+            continue;
+        }
+        match warm::warm(&path, IndexKind::default()) {
+            Ok(report) => records += report.records,
+            Err(e) => eprintln!("preload {}: {e}", path.display()),
+        }
+    }
+    eprintln!("preloaded {records} records");
+}
+
+fn load_grants(file: &Path) -> Result<Vec<TokenGrant>, &'static str> {
+    let text = fs::read_to_string(file).map_err(|e| {
+        eprintln!("Cannot read {}: {e}", file.display());
+        "read failed"
+    })?;
+    let parsed: TokenFile = toml::from_str(&text).map_err(|e| {
+        eprintln!("Invalid token file {}: {e}", file.display());
+        "invalid token file"
+    })?;
+    Ok(parsed.tokens)
+}
+
+fn find_grant<'a>(grants: &'a [TokenGrant], request: &Request) -> Option<&'a TokenGrant> {
+    let token = request
+        .headers()
+        .iter()
+        .find(|h| h.field.equiv("Authorization"))
+        .and_then(|h| h.value.as_str().strip_prefix("Bearer "))
+        .map(str::trim)?;
+    grants.iter().find(|g| g.token == token)
+}
+
+/// With grants configured, the request needs an `Authorization: Bearer` token
+/// that covers the store, and read-write access for `add` and `compact`.
+fn authorize(
+    grants: &[TokenGrant],
+    request: &Request,
+    store: &str,
+    op: &str,
+) -> Result<(), (u16, String)> {
+    let Some(grant) = find_grant(grants, request) else {
+        return Err(error(401, "missing or unknown token"));
+    };
+    if !grant.stores.iter().any(|s| s == "*" || s == store) {
+        return Err(error(403, "token has no access to this store"));
+    }
+    if matches!(op, "add" | "compact") && grant.access != Access::ReadWrite {
+        return Err(error(403, "token is read-only"));
+    }
+    Ok(())
+}
+
+/// Streams `GET /events` as server-sent events on a thread of its own, limited to
+/// the token's stores. Only changes made through this server are reported.
+fn subscribe(
+    grants: Option<&[TokenGrant]>,
+    request: Request,
+    subscribers: &Mutex<Vec<Subscriber>>,
+) {
+    let stores = match grants {
+        None => None,
+        Some(grants) => match find_grant(grants, &request) {
+            Some(grant) if grant.stores.iter().any(|s| s == "*") => None,
+            Some(grant) => Some(grant.stores.clone()),
+            None => {
+                let (status, body) = error(401, "missing or unknown token");
+                let _ = request.respond(Response::from_string(body).with_status_code(status));
+                return;
+            }
+        },
+    };
+    let (frames, rx) = mpsc::channel::<String>();
+    subscribers
+        .lock()
+        .expect("subscribers lock")
+        .push(Subscriber { stores, frames });
+    thread::spawn(move || {
+        let mut out = request.into_writer();
+        let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\n\
+                    Cache-Control: no-cache\r\nConnection: close\r\n\r\n";
+        // Ends when the client goes away; `publish` then drops the subscriber.
+        for chunk in std::iter::once(head.to_string()).chain(rx) {
+            let written = out.write_all(chunk.as_bytes()).and_then(|_| out.flush());
+            if written.is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// A `delete` event for records dropped by compaction, if there were any.
+fn publish_deletes(subscribers: &Mutex<Vec<Subscriber>>, store: &str, removed: &[u128]) {
+    if !removed.is_empty() {
+        let ids: Vec<String> = removed.iter().map(u128::to_string).collect();
+        publish(subscribers, store, "delete", &format!("\"ids\":[{}]", ids.join(",")));
+    }
+}
+
+/// Sends `event` with `{"store":...,<data>}` to every subscriber that may see `store`.
+fn publish(subscribers: &Mutex<Vec<Subscriber>>, store: &str, event: &str, data: &str) {
+    let frame = format!("event: {event}\ndata: {{\"store\":\"{store}\",{data}}}\n\n");
+    let mut subscribers = subscribers.lock().expect("subscribers lock");
+    subscribers.retain(|sub| {
+        let visible = sub
+            .stores
+            .as_ref()
+            .is_none_or(|stores| stores.iter().any(|s| s == store));
+        !visible || sub.frames.send(frame.clone()).is_ok()
+    });
+}
+
+/// Routes `POST /stores/<name>/<op>` to the store file `<dir>/<name>.hnsw`.
+fn handle(state: &State, request: &mut Request) -> (u16, String) {
+    if *request.method() != Method::Post {
+        return error(405, "only POST is supported");
+    }
+    let url = request.url().to_string();
+    let parts: Vec<&str> = url.trim_matches('/').split('/').collect();
+    let (name, op) = match parts.as_slice() {
+        ["stores", name, op] => (*name, *op),
+        _ => return error(404, "expected /stores/<name>/<op>"),
+    };
+    if !valid_store_name(name) {
+        return error(400, "invalid store name");
+    }
+    if let Some(grants) = &state.grants {
+        if let Err(denied) = authorize(grants, request, name, op) {
+            return denied;
+        }
+    }
+    let path = state.dir.join(format!("{name}.hnsw"));
+    let (cache, subscribers) = (&state.cache, &state.subscribers);
+
+    let mut body = String::new();
+    if request.as_reader().read_to_string(&mut body).is_err() {
+        return error(400, "unreadable body");
+    }
+
+    match op {
+        "add" => respond_with(&body, |req: AddRequest| {
+            let _write = state.writes.lock().expect("writes lock");
+            cache.invalidate(&path);
+            let resp = handle_add_limited(&path, req, &state.add_limits)?;
+            if let Ok(json) = serde_json::to_string(&resp) {
+                publish(subscribers, name, "add", &format!("\"record\":{json}"));
+            }
+            if let Some(limits) = &state.limits {
+                publish_deletes(subscribers, name, &auto_compact(&path, limits)?);
+            }
+            Ok(resp)
+        }),
+        "search" => respond_with(&body, |req: SearchRequest| {
+            cache.search(&path, req, &state.search)
+        }),
+        "get" => respond_with(&body, |req: GetRequest| get_record(&path, req.id).map(ApiRecord::from)),
+        "recent" => respond_with(&body, |req: RecentRequest| handle_recent(&path, req)),
+        "generation" => respond_with(&body, |_: serde_json::Value| handle_generation(&path)),
+        "compact" => respond_with(&body, |req: CompactRequest| {
+            let _write = state.writes.lock().expect("writes lock");
+            cache.invalidate(&path);
+            publish_deletes(subscribers, name, &compact_store(&path, req.keep)?);
+            Ok(())
+        }),
+        _ => error(404, "unknown operation"),
+    }
+}
+
+pub fn handle_add(path: &Path, req: AddRequest) -> std::io::Result<AddResponse> {
+    handle_add_limited(path, req, &AddLimits::default())
+}
+
+/// `add` under `max_text`, `weight_range` and the kind registry. Rejected text,
+/// weights or kinds are an `InvalidInput` error (400); chunked text answers
+/// with the first chunk.
+pub fn handle_add_limited(
+    path: &Path,
+    req: AddRequest,
+    limits: &AddLimits,
+) -> std::io::Result<AddResponse> {
+    let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+    let weight = (!req.auto_weight).then_some(req.weight);
+    if let Some(w) = weight {
+        limits.check_weight(w).map_err(invalid)?;
+    }
+    if let Some(warning) = limits.check_kind(&req.kind).map_err(invalid)? {
+        eprintln!("add: {warning}");
+    }
+    let fitted = oversize::fit(req.text, limits.max_text.as_ref()).map_err(invalid)?;
+    let (rec, nearest, generation) = add_fitted(
+        path,
+        fitted,
+        req.kind,
+        weight,
+        req.tags,
+        req.attrs,
+        req.expected_generation,
+    )?;
+    Ok(AddResponse {
+        id: rec.id,
+        ts: rec.ts,
+        kind: rec.kind,
+        weight: rec.weight,
+        text: rec.text,
+        tags: rec.tags,
+        attrs: rec.attrs,
+        nearest_id: nearest.map(|n| n.id),
+        nearest_similarity: nearest.map(|n| n.similarity),
+        generation: Some(generation),
+    })
+}
+
+pub fn handle_search(path: &Path, req: SearchRequest) -> std::io::Result<Vec<ApiHit>> {
+    handle_search_with(path, req, &SearchOptions::from_profile(&Profile::default()))
+}
+
+/// `search` with `base`'s scoring, index and limits (see
+/// `SearchOptions::from_profile`); the request sets the limit and filters, and
+/// its `max_age` replaces the base one.
+pub fn handle_search_with(
+    path: &Path,
+    req: SearchRequest,
+    base: &SearchOptions,
+) -> std::io::Result<Vec<ApiHit>> {
+    let options = SearchOptions {
+        limit: req.limit,
+        filter: RecordFilter {
+            kind: req.kind,
+            tags: req.tags,
+            since: req.since,
+            until: req.until,
+            must: req.must,
+            must_not: req.must_not,
+            as_of: req.as_of,
+            lang: req.lang,
+        },
+        per_kind: req.per_kind,
+        max_age: req.max_age.or(base.max_age),
+        ..base.clone()
+    };
+    let hits = search_store(path, &req.query, &options)?;
+    Ok(hits
+        .into_iter()
+        .map(|(score, rec)| ApiHit {
+            score,
+            record: rec.into(),
+        })
+        .collect())
+}
+
+/// The current generation, to pass back as `expected_generation`.
+pub fn handle_generation(path: &Path) -> std::io::Result<GenerationResponse> {
+    Ok(GenerationResponse {
+        generation: store_generation(path)?,
+    })
+}
+
+pub fn handle_recent(path: &Path, req: RecentRequest) -> std::io::Result<Vec<ApiRecord>> {
+    let recs = recent_records(path, req.limit)?;
+    Ok(recs.into_iter().map(ApiRecord::from).collect())
+}
+
+fn respond_with<Req, Resp, F>(body: &str, f: F) -> (u16, String)
+where
+    Req: DeserializeOwned,
+    Resp: Serialize,
+    F: FnOnce(Req) -> std::io::Result<Resp>,
+{
+    let req: Req = match serde_json::from_str(body) {
+        Ok(req) => req,
+        Err(e) => return error(400, &format!("invalid request: {e}")),
+    };
+    match f(req) {
+        Ok(resp) => match serde_json::to_string(&resp) {
+            Ok(json) => (200, json),
+            Err(e) => error(500, &e.to_string()),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::InvalidInput => error(400, &e.to_string()),
+        Err(e) if GenerationConflict::is(&e) => error(409, &e.to_string()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => error(404, &e.to_string()),
+        Err(e) => error(500, &e.to_string()),
+    }
+}
+
+fn error(status: u16, msg: &str) -> (u16, String) {
+    let body = serde_json::to_string(&ApiError {
+        error: msg.to_string(),
+    })
+    .unwrap_or_default();
+    (status, body)
+}
+
+fn valid_store_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+
+    #[test]
+    fn search_filters_survive_the_wire() {
+        let dir = env::temp_dir().join(format!("memstore-server-filters-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("memories.hnsw");
+        for (kind, tag) in [("decision", "infra"), ("note", "infra"), ("decision", "ui")] {
+            let req: AddRequest = serde_json::from_value(serde_json::json!({
+                "text": format!("deploy rollback plan {kind} {tag}"),
+                "kind": kind,
+                "tags": [tag],
+            }))
+            .unwrap();
+            handle_add(&path, req).unwrap();
+        }
+
+        let sent = SearchRequest {
+            query: "deploy rollback".to_string(),
+            limit: 10,
+            must: Vec::new(),
+            must_not: Vec::new(),
+            per_kind: None,
+            kind: Some("decision".to_string()),
+            tags: vec!["infra".to_string()],
+            since: None,
+            until: None,
+            as_of: None,
+            lang: None,
+            max_age: Some(3600),
+        };
+        let received: SearchRequest =
+            serde_json::from_str(&serde_json::to_string(&sent).unwrap()).unwrap();
+        assert_eq!(received.kind, sent.kind);
+        assert_eq!(received.tags, sent.tags);
+        assert_eq!(received.max_age, sent.max_age);
+
+        let hits = handle_search(&path, received).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].record.kind, "decision");
+        assert_eq!(hits[0].record.tags, ["infra"]);
+
+        let aged = serde_json::from_value(serde_json::json!({
+            "query": "deploy rollback",
+            "limit": 10,
+            "as_of": 1,
+        }))
+        .unwrap();
+        assert!(handle_search(&path, aged).unwrap().is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
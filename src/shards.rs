@@ -0,0 +1,183 @@
+//! Monthly shards. After `memstore shard`, `<store>.shards` lists the months
+//! of the store and their records live next to it in `<stem>-YYYY-MM.<ext>`.
+//! Adds go to the current month's shard, so compaction and index rebuilds only
+//! touch that file, while searches fan out over a window of recent months.
+//!
+//! Each shard is an ordinary store file and can be passed to any command with
+//! `--path`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::dates::format_month;
+use crate::store::{load_store, lock_store, sibling_path, update_store, write_atomic, Record};
+use crate::{
+    cap_per_kind, config, index_cache, ivf, now_secs, path_arg, pq, remote, resolve_path,
+    search_store, store_error, SearchOptions,
+};
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    period: String,
+    /// `YYYY-MM`, oldest first.
+    months: Vec<String>,
+}
+
+pub fn cmd_shard(args: &[String]) -> Result<(), &'static str> {
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    if remote::store_url(&path).is_some() {
+        eprintln!("This command only works on local stores");
+        return Err("remote unsupported");
+    }
+    if is_sharded(&path) {
+        eprintln!("{} is already sharded", path.display());
+        return Err("already sharded");
+    }
+
+    let _lock = lock_store(&path).map_err(store_error(&path, "lock failed"))?;
+    let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+    let total = store.records.len();
+    let mut by_month: BTreeMap<String, Vec<Record>> = BTreeMap::new();
+    for rec in store.records {
+        by_month.entry(format_month(rec.ts)).or_default().push(rec);
+    }
+    for (month, records) in &by_month {
+        let shard = shard_path(&path, month);
+        update_store(&shard, |s| {
+            if s.records.is_empty() {
+                s.meta = store.meta.clone();
+            }
+            s.records.extend(records.iter().cloned());
+            Ok(())
+        })
+        .map_err(store_error(&shard, "write failed"))?;
+    }
+    let manifest = Manifest {
+        period: "monthly".to_string(),
+        months: by_month.into_keys().collect(),
+    };
+    write_manifest(&path, &manifest).map_err(store_error(&path, "write failed"))?;
+
+    // Every record now lives in a shard; the old file and its caches go.
+    let caches = index_cache::cache_files(&path)
+        .into_iter()
+        .chain(ivf::cache_files(&path))
+        .chain(pq::cache_files(&path));
+    for file in caches.chain(Some(path.clone()).filter(|p| p.exists())) {
+        fs::remove_file(&file).map_err(store_error(&file, "write failed"))?;
+    }
+    eprintln!(
+        "Split {total} records into {} monthly shards",
+        manifest.months.len()
+    );
+    Ok(())
+}
+
+pub fn is_sharded(path: &Path) -> bool {
+    manifest_path(path).exists()
+}
+
+/// The shard that records added now go to, registered in the manifest if it
+/// is new.
+pub fn current(path: &Path) -> io::Result<PathBuf> {
+    let month = format_month(now_secs());
+    let _lock = lock_store(path)?;
+    let mut manifest = read_manifest(path)?;
+    if !manifest.months.contains(&month) {
+        manifest.months.push(month.clone());
+        manifest.months.sort();
+        write_manifest(path, &manifest)?;
+    }
+    Ok(shard_path(path, &month))
+}
+
+/// The newest shard, which compaction works on; older months are left alone.
+pub fn newest(path: &Path) -> io::Result<Option<PathBuf>> {
+    let manifest = read_manifest(path)?;
+    Ok(manifest.months.last().map(|m| shard_path(path, m)))
+}
+
+/// Shards from the last `months` calendar months, counting the current one,
+/// or all of them for `None`. Newest first.
+pub fn in_window(path: &Path, months: Option<usize>) -> io::Result<Vec<PathBuf>> {
+    let manifest = read_manifest(path)?;
+    let cutoff = months.map(|n| month_index(&format_month(now_secs())) - n as i64 + 1);
+    Ok(manifest
+        .months
+        .iter()
+        .rev()
+        .filter(|m| cutoff.is_none_or(|c| month_index(m) >= c))
+        .map(|m| shard_path(path, m))
+        .collect())
+}
+
+/// Searches each shard in `options.window` and merges the hits. Scores do not
+/// depend on the rest of the store, so they compare across shards as they are.
+pub fn search(path: &Path, query: &str, options: &SearchOptions) -> io::Result<Vec<(f32, Record)>> {
+    let mut hits = Vec::new();
+    for shard in in_window(path, options.window)? {
+        hits.extend(search_store(&shard, query, options)?);
+    }
+    hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+    if let Some(n) = options.per_kind {
+        cap_per_kind(&mut hits, n, |(_, rec)| &rec.kind);
+    }
+    hits.truncate(options.limit);
+    Ok(hits)
+}
+
+/// `<stem>-YYYY-MM.<ext>` in the store's directory.
+fn shard_path(path: &Path, month: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{month}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{month}"),
+    };
+    path.with_file_name(name)
+}
+
+/// Months since year 0, so windows can cross year boundaries.
+fn month_index(month: &str) -> i64 {
+    let (year, month) = month.split_once('-').unwrap_or((month, "1"));
+    year.parse::<i64>().unwrap_or(0) * 12 + month.parse::<i64>().unwrap_or(1) - 1
+}
+
+fn manifest_path(path: &Path) -> PathBuf {
+    sibling_path(path, ".shards")
+}
+
+fn read_manifest(path: &Path) -> io::Result<Manifest> {
+    let bytes = fs::read(manifest_path(path))?;
+    serde_json::from_slice(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_manifest(path: &Path, manifest: &Manifest) -> io::Result<()> {
+    let json = serde_json::to_vec_pretty(manifest).map_err(|_| io::ErrorKind::InvalidData)?;
+    write_atomic(&manifest_path(path), &[&json])
+}
@@ -0,0 +1,93 @@
+//! Short excerpts of record text for `search --snippet`.
+
+use std::collections::HashSet;
+
+/// Up to `width` characters of `text` around the densest run of `terms`,
+/// with each matching word wrapped in `marks`. Cut ends get an ellipsis.
+pub fn snippet(text: &str, terms: &HashSet<String>, width: usize, marks: (&str, &str)) -> String {
+    let chars: Vec<char> = text
+        .chars()
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .collect();
+    let hits = matching_words(&chars, terms);
+
+    let (start, end) = if chars.len() <= width {
+        (0, chars.len())
+    } else {
+        // Lead in a little before the first hit so it is not glued to the edge.
+        let lead = width / 4;
+        let best_start = hits
+            .iter()
+            .map(|&(s, _)| s.saturating_sub(lead))
+            .max_by_key(|&start| {
+                let count = hits
+                    .iter()
+                    .filter(|&&(s, e)| s >= start && e <= start + width)
+                    .count();
+                // Prefer the earliest window among equals.
+                (count, std::cmp::Reverse(start))
+            })
+            .unwrap_or(0);
+        let start = best_start.min(chars.len() - width);
+        (start, start + width)
+    };
+    // Avoid cutting words in half where a space is close by.
+    let slack = width / 8;
+    let start = match chars[start..end].iter().take(slack).position(|&c| c == ' ') {
+        Some(p) if start > 0 && chars[start - 1] != ' ' => start + p + 1,
+        _ => start,
+    };
+    let end = match chars[start..end]
+        .iter()
+        .rev()
+        .take(slack)
+        .position(|&c| c == ' ')
+    {
+        Some(p) if end < chars.len() && chars[end] != ' ' => end - p - 1,
+        _ => end,
+    };
+
+    let mut out = String::new();
+    if start > 0 {
+        out.push('…');
+    }
+    let mut i = start;
+    for &(s, e) in hits.iter().filter(|&&(s, e)| s < end && e > start) {
+        let (s, e) = (s.max(start), e.min(end));
+        out.extend(&chars[i..s]);
+        out.push_str(marks.0);
+        out.extend(&chars[s..e]);
+        out.push_str(marks.1);
+        i = e;
+    }
+    out.extend(&chars[i..end]);
+    if end < chars.len() {
+        out.push('…');
+    }
+    out
+}
+
+/// `[start, end)` char ranges of words whose token is in `terms`,
+/// split the same way as `tokenize`.
+fn matching_words(chars: &[char], terms: &HashSet<String>) -> Vec<(usize, usize)> {
+    let mut hits = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if !chars[i].is_alphanumeric() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && chars[i].is_alphanumeric() {
+            i += 1;
+        }
+        let word: String = chars[start..i]
+            .iter()
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        if terms.contains(&word) {
+            hits.push((start, i));
+        }
+    }
+    hits
+}
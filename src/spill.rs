@@ -0,0 +1,88 @@
+//! Searching a store file larger than the memory budget (`search --max-memory`
+//! or the profile's `max_memory`). Records are decoded and scored one at a
+//! time and only the best few are kept, so memory follows the result size
+//! rather than the store size. No index is built or loaded: every record is
+//! scored, which is slower than an indexed search on a warm cache but exact,
+//! and degrades instead of running a small machine out of memory.
+//!
+//! `--kind` is matched exactly here; resolving it against the stored kinds
+//! (`kinds::resolve`) needs every record up front.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::filter::RecordFilter;
+use crate::store::{stream_records, Record};
+use crate::{cautions, now_secs, print_timing, score_record, SearchOptions};
+
+pub fn search(
+    path: &Path,
+    query_vec: &[f32],
+    filter: &RecordFilter,
+    options: &SearchOptions,
+) -> io::Result<Vec<(f32, Record)>> {
+    let started = Instant::now();
+    let with_cautions = filter.kind.as_deref() == Some(cautions::CAUTION_KIND);
+    let now = filter.as_of.unwrap_or_else(now_secs);
+    // With --per-kind, the best of each kind, so capping later still fills `limit`.
+    let keep = match options.per_kind {
+        Some(n) => n.min(options.limit),
+        None => options.limit,
+    };
+    let mut best: HashMap<String, Top> = HashMap::new();
+    for rec in stream_records(path)? {
+        let rec = rec?;
+        if !filter.matches(&rec) || (!with_cautions && rec.kind == cautions::CAUTION_KIND) {
+            continue;
+        }
+        let score = score_record(query_vec, &rec, &options.scoring, now);
+        let group = match options.per_kind {
+            Some(_) => rec.kind.clone(),
+            None => String::new(),
+        };
+        best.entry(group)
+            .or_insert_with(|| Top::new(keep))
+            .push(score, rec);
+    }
+    let mut scored: Vec<(f32, Record)> = best.into_values().flat_map(Top::into_sorted).collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    if options.timing {
+        print_timing("scan (over max_memory)", started);
+    }
+    Ok(scored)
+}
+
+/// The `keep` best-scored records pushed so far. Sorts and trims whenever it
+/// holds twice that many, so at most `2 * keep` records are held at once.
+struct Top {
+    keep: usize,
+    items: Vec<(f32, Record)>,
+}
+
+impl Top {
+    fn new(keep: usize) -> Top {
+        Top {
+            keep,
+            items: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, score: f32, rec: Record) {
+        self.items.push((score, rec));
+        if self.items.len() >= self.keep.saturating_mul(2).max(1) {
+            self.trim();
+        }
+    }
+
+    fn trim(&mut self) {
+        self.items.sort_by(|a, b| b.0.total_cmp(&a.0));
+        self.items.truncate(self.keep);
+    }
+
+    fn into_sorted(mut self) -> Vec<(f32, Record)> {
+        self.trim();
+        self.items
+    }
+}
@@ -0,0 +1,1254 @@
+//! On-disk store layout and its version history.
+//!
+//! bincode is not self-describing, so a missing field cannot fall back to a
+//! serde default. Instead every released layout is frozen here as its own
+//! struct and migrated forward step by step after decoding.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use bincode::Options;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::now_secs;
+
+pub const VECTOR_DIM: usize = 256;
+pub const STORE_VERSION: u32 = 5;
+
+/// Every file starts with a `HEADER_LEN`-byte header: the 8-byte `MAGIC`,
+/// then the layout version and a flags word, each a little-endian u32. With
+/// `FLAG_CHECKSUMS` the rest of the file is
+///
+/// ```text
+/// body_crc: u32            CRC32 of everything below
+/// payload_len: u64
+/// payload                  bincode(Store)
+/// record_crcs              bincode(Vec<u32>), CRC32 of each bincode(Record)
+/// ```
+///
+/// otherwise the bincode payload follows the header directly.
+///
+/// With `FLAG_SIGNED` a 32-byte HMAC-SHA256 sits between the header and the
+/// rest, computed with `MEMSTORE_SIGNING_KEY` over the header and everything
+/// after the MAC. It detects edits made without the key.
+const MAGIC: &[u8; 8] = b"MEMSTORE";
+const HEADER_LEN: usize = 16;
+const MAC_LEN: usize = 32;
+const FLAG_CHECKSUMS: u32 = 1;
+const FLAG_SIGNED: u32 = 2;
+const KNOWN_FLAGS: u32 = FLAG_CHECKSUMS | FLAG_SIGNED;
+
+/// State of a store's HMAC as seen with the current `MEMSTORE_SIGNING_KEY`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signature {
+    Unsigned,
+    Valid,
+    Invalid,
+    /// Signed, but no key is configured to check it.
+    NoKey,
+    /// Unsigned, but a key is configured, so the file should have been signed.
+    /// Only `rekey` accepts it, to sign stores written before the key was set.
+    Missing,
+}
+
+/// A decoded file header and the byte ranges it frames.
+struct Frame<'a> {
+    version: u32,
+    payload: &'a [u8],
+    record_crcs: Option<Vec<u32>>,
+    body_crc_ok: bool,
+    signature: Signature,
+}
+
+pub struct VerifyReport {
+    pub version: u32,
+    pub records: usize,
+    pub checksummed: bool,
+    pub signature: Signature,
+    pub problems: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Record {
+    pub id: u128,
+    pub ts: i64,
+    pub kind: String,
+    pub weight: f32,
+    pub text: String,
+    pub vector: Vec<f32>,
+    pub tags: Vec<String>,
+    /// Free-form metadata, e.g. `supersedes` on consolidated summaries.
+    pub attrs: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Store {
+    pub version: u32,
+    pub vector_dim: usize,
+    pub meta: StoreMeta,
+    /// Bumped by every `update_store`, so writers can check nothing changed
+    /// since they read (see `update_store_if`).
+    pub generation: u64,
+    pub records: Vec<Record>,
+}
+
+/// Identifies a store file found out of context. `created_at` is 0 for stores
+/// written before metadata existed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct StoreMeta {
+    pub name: String,
+    pub description: String,
+    pub created_at: i64,
+}
+
+mod v1 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Record {
+        pub id: u128,
+        pub ts: i64,
+        pub kind: String,
+        pub weight: f32,
+        pub text: String,
+        pub vector: Vec<f32>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Store {
+        pub _version: u32,
+        pub vector_dim: usize,
+        pub records: Vec<Record>,
+    }
+}
+
+mod v2 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct StoreMeta {
+        pub name: String,
+        pub description: String,
+        pub created_at: i64,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Store {
+        pub _version: u32,
+        pub vector_dim: usize,
+        pub meta: StoreMeta,
+        pub records: Vec<super::v1::Record>,
+    }
+}
+
+mod v3 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Record {
+        pub id: u128,
+        pub ts: i64,
+        pub kind: String,
+        pub weight: f32,
+        pub text: String,
+        pub vector: Vec<f32>,
+        pub tags: Vec<String>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Store {
+        pub _version: u32,
+        pub vector_dim: usize,
+        pub meta: super::v2::StoreMeta,
+        pub records: Vec<Record>,
+    }
+}
+
+mod v4 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Store {
+        pub _version: u32,
+        pub vector_dim: usize,
+        pub meta: super::StoreMeta,
+        pub records: Vec<super::Record>,
+    }
+}
+
+/// v2 added store metadata.
+fn migrate_v1(old: v1::Store) -> v2::Store {
+    v2::Store {
+        _version: 2,
+        vector_dim: old.vector_dim,
+        meta: v2::StoreMeta {
+            name: String::new(),
+            description: String::new(),
+            created_at: 0,
+        },
+        records: old.records,
+    }
+}
+
+/// v3 added record tags.
+fn migrate_v2(old: v2::Store) -> v3::Store {
+    v3::Store {
+        _version: 3,
+        vector_dim: old.vector_dim,
+        meta: old.meta,
+        records: old
+            .records
+            .into_iter()
+            .map(|r| v3::Record {
+                id: r.id,
+                ts: r.ts,
+                kind: r.kind,
+                weight: r.weight,
+                text: r.text,
+                vector: r.vector,
+                tags: Vec::new(),
+            })
+            .collect(),
+    }
+}
+
+/// v4 added record attributes.
+fn migrate_v3(old: v3::Store) -> v4::Store {
+    v4::Store {
+        _version: 4,
+        vector_dim: old.vector_dim,
+        meta: StoreMeta {
+            name: old.meta.name,
+            description: old.meta.description,
+            created_at: old.meta.created_at,
+        },
+        records: old
+            .records
+            .into_iter()
+            .map(|r| Record {
+                id: r.id,
+                ts: r.ts,
+                kind: r.kind,
+                weight: r.weight,
+                text: r.text,
+                vector: r.vector,
+                tags: r.tags,
+                attrs: BTreeMap::new(),
+            })
+            .collect(),
+    }
+}
+
+/// v5 added the generation counter.
+fn migrate_v4(old: v4::Store) -> Store {
+    Store {
+        version: 5,
+        vector_dim: old.vector_dim,
+        meta: old.meta,
+        generation: 0,
+        records: old.records,
+    }
+}
+
+pub fn new_store() -> Store {
+    Store {
+        version: STORE_VERSION,
+        vector_dim: VECTOR_DIM,
+        meta: StoreMeta {
+            created_at: now_secs(),
+            ..StoreMeta::default()
+        },
+        generation: 0,
+        records: Vec::new(),
+    }
+}
+
+pub fn ensure_parent_dir(path: &Path) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Writes the store to a sibling temp file, syncs it and renames it over
+/// `path`, so a crash mid-write leaves either the old or the new file intact.
+/// A signed file is only replaced while `MEMSTORE_SIGNING_KEY` is set, so a
+/// write without the key cannot quietly drop the signature.
+pub fn save_store(path: &Path, store: &Store) -> io::Result<()> {
+    ensure_parent_dir(path)?;
+    if signing_key().is_none() && is_signed(path)? {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "store is signed but MEMSTORE_SIGNING_KEY is not set; set it to write the store",
+        ));
+    }
+    write_atomic(path, &[&encode_store(store)?])
+}
+
+/// Whether the file at `path` has `FLAG_SIGNED` set; a missing file has not.
+fn is_signed(path: &Path) -> io::Result<bool> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    match File::open(path) {
+        Ok(file) => file.take(HEADER_LEN as u64).read_to_end(&mut header)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+    if header.len() < HEADER_LEN || !header.starts_with(MAGIC) {
+        return Ok(false);
+    }
+    let flags = u32::from_le_bytes([header[12], header[13], header[14], header[15]]);
+    Ok(flags & FLAG_SIGNED != 0)
+}
+
+/// The complete file image of `store`, header included.
+pub fn encode_store(store: &Store) -> io::Result<Vec<u8>> {
+    let payload = bincode::serialize(store).map_err(|_| io::ErrorKind::InvalidData)?;
+    let record_crcs = store
+        .records
+        .iter()
+        .map(record_crc)
+        .collect::<io::Result<Vec<u32>>>()?;
+    let sums = bincode::serialize(&record_crcs).map_err(|_| io::ErrorKind::InvalidData)?;
+    let mut body = Vec::with_capacity(8 + payload.len() + sums.len());
+    body.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    body.extend_from_slice(&payload);
+    body.extend_from_slice(&sums);
+
+    let key = signing_key();
+    let flags = FLAG_CHECKSUMS | if key.is_some() { FLAG_SIGNED } else { 0 };
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(&store.version.to_le_bytes());
+    header.extend_from_slice(&flags.to_le_bytes());
+    let body_crc = crc32(&body).to_le_bytes();
+
+    let mac = key.map(|key| hmac_for(&key, &[&header, &body_crc, &body]).finalize().into_bytes());
+    let mac: &[u8] = mac.as_deref().unwrap_or_default();
+    Ok([&header[..], mac, &body_crc, &body].concat())
+}
+
+/// Writes `parts` to a sibling temp file, syncs it and renames it over `path`.
+pub fn write_atomic(path: &Path, parts: &[&[u8]]) -> io::Result<()> {
+    let tmp = sibling_path(path, ".tmp");
+    let written = (|| {
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        for part in parts {
+            writer.write_all(part)?;
+        }
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    })();
+    if let Err(e) = written.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(e);
+    }
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// Re-signs the file at `path` with `new_key` without decoding it, after checking
+/// its current signature with `MEMSTORE_SIGNING_KEY`. Unsigned files gain a
+/// signature; this is the one place an unsigned file is accepted while a key is
+/// set. Returns `false` for headerless legacy files, which cannot carry one.
+pub fn resign_file(path: &Path, new_key: &[u8]) -> io::Result<bool> {
+    let data = fs::read(path)?;
+    let frame = split_header(&data)?;
+    let problem = match frame.signature {
+        Signature::Invalid => Some("signature mismatch with the current MEMSTORE_SIGNING_KEY"),
+        Signature::NoKey => Some("file is signed; set MEMSTORE_SIGNING_KEY to its current key"),
+        Signature::Unsigned | Signature::Missing | Signature::Valid => None,
+    };
+    if let Some(problem) = problem {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, problem));
+    }
+    if !frame.body_crc_ok {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "checksum mismatch"));
+    }
+    if !data.starts_with(MAGIC) {
+        return Ok(false);
+    }
+
+    let mut flags = [0u8; 4];
+    flags.copy_from_slice(&data[12..HEADER_LEN]);
+    let flags = u32::from_le_bytes(flags);
+    let rest = if flags & FLAG_SIGNED != 0 {
+        &data[HEADER_LEN + MAC_LEN..]
+    } else {
+        &data[HEADER_LEN..]
+    };
+    let mut header = data[..12].to_vec();
+    header.extend_from_slice(&(flags | FLAG_SIGNED).to_le_bytes());
+    let mac = hmac_for(new_key, &[&header, rest]).finalize().into_bytes();
+    write_atomic(path, &[&header, &mac, rest])?;
+    Ok(true)
+}
+
+/// Size and mtime (ns) of the store file, for caches derived from it.
+pub fn file_stamp(path: &Path) -> io::Result<(u64, u128)> {
+    let meta = fs::metadata(path)?;
+    let mtime = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok((meta.len(), mtime))
+}
+
+/// The body CRC recorded in the header, read without loading the store.
+/// `None` for files written without checksums.
+pub fn body_checksum(path: &Path) -> io::Result<Option<u32>> {
+    let mut head = Vec::with_capacity(HEADER_LEN + MAC_LEN + 4);
+    File::open(path)?
+        .take((HEADER_LEN + MAC_LEN + 4) as u64)
+        .read_to_end(&mut head)?;
+    if !head.starts_with(MAGIC) || head.len() < HEADER_LEN {
+        return Ok(None);
+    }
+    let flags = u32::from_le_bytes([head[12], head[13], head[14], head[15]]);
+    if flags & FLAG_CHECKSUMS == 0 {
+        return Ok(None);
+    }
+    let at = if flags & FLAG_SIGNED != 0 {
+        HEADER_LEN + MAC_LEN
+    } else {
+        HEADER_LEN
+    };
+    Ok(head
+        .get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])))
+}
+
+/// `<path><suffix>`, e.g. `memories.hnsw.tmp`.
+pub fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Makes the rename itself durable. Directories cannot be opened for syncing
+/// on every platform, so failures are ignored.
+fn sync_parent_dir(path: &Path) {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+}
+
+/// Takes an exclusive advisory lock on `<path>.lock`, held until the returned
+/// file is dropped. The store file itself is replaced on every save, so it
+/// cannot carry the lock.
+pub fn lock_store(path: &Path) -> io::Result<File> {
+    ensure_parent_dir(path)?;
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(sibling_path(path, ".lock"))?;
+    lock.lock()?;
+    Ok(lock)
+}
+
+/// Load-modify-save under the store lock, so concurrent writers (several
+/// agents, or `serve`) cannot drop each other's changes. Readers need no lock
+/// because saves replace the file atomically.
+pub fn update_store<T>(path: &Path, f: impl FnOnce(&mut Store) -> io::Result<T>) -> io::Result<T> {
+    update_store_if(path, None, f).map(|(out, _)| out)
+}
+
+/// `update_store` that first checks the store is still at `expected`
+/// generation, failing with a `GenerationConflict` otherwise. Returns the
+/// generation the write produced.
+pub fn update_store_if<T>(
+    path: &Path,
+    expected: Option<u64>,
+    f: impl FnOnce(&mut Store) -> io::Result<T>,
+) -> io::Result<(T, u64)> {
+    let _lock = lock_store(path)?;
+    let mut store = load_store(path)?;
+    if let Some(expected) = expected.filter(|e| *e != store.generation) {
+        return Err(io::Error::other(GenerationConflict {
+            expected,
+            actual: store.generation,
+        }));
+    }
+    let out = f(&mut store)?;
+    store.generation += 1;
+    save_store(path, &store)?;
+    Ok((out, store.generation))
+}
+
+/// A compare-and-swap write found the store at another generation.
+#[derive(Debug)]
+pub struct GenerationConflict {
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl std::fmt::Display for GenerationConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "store is at generation {}, expected {}",
+            self.actual, self.expected
+        )
+    }
+}
+
+impl std::error::Error for GenerationConflict {}
+
+impl GenerationConflict {
+    /// Whether `e` came from a failed compare-and-swap.
+    pub fn is(e: &io::Error) -> bool {
+        e.get_ref().is_some_and(|inner| inner.is::<GenerationConflict>())
+    }
+}
+
+pub fn load_store(path: &Path) -> io::Result<Store> {
+    load_store_versioned(path).map(|(store, _)| store)
+}
+
+/// Loads a store migrated to the current layout, along with the version the
+/// file was written in. Missing files load as an empty current-version store.
+pub fn load_store_versioned(path: &Path) -> io::Result<(Store, u32)> {
+    if !path.exists() {
+        return Ok((new_store(), STORE_VERSION));
+    }
+    let file = File::open(path)?;
+    let mut data = Vec::new();
+    let mut reader = file;
+    reader.read_to_end(&mut data)?;
+    decode_store(&data)
+}
+
+/// Records of the store at `path` decoded one at a time, so a store larger
+/// than memory can still be scanned; see `Store::iter_records`. A missing
+/// file has no records.
+pub fn stream_records(path: &Path) -> io::Result<RecordStream> {
+    if !path.exists() {
+        return Ok(RecordStream::Loaded(Vec::new().into_iter()));
+    }
+    let file = File::open(path)?;
+    let limit = file.metadata()?.len();
+    Store::iter_records(BufReader::new(file), limit)
+}
+
+impl Store {
+    /// Records of the store file `reader` yields, decoded lazily as the
+    /// iterator is advanced, so memory stays at one record whatever the file
+    /// size. `limit` is the most bytes any one record may take, normally the
+    /// file size; a corrupt length beyond it is an error, not an allocation.
+    ///
+    /// The checksum and signature are checked as the file is read; a mismatch
+    /// is the item after the last record, so callers must not act on the
+    /// records before the stream ends. Layouts older than `STORE_VERSION` have
+    /// no streaming decoder and are read whole first.
+    pub fn iter_records(reader: impl BufRead + 'static, limit: u64) -> io::Result<RecordStream> {
+        let mut reader: Box<dyn BufRead> = Box::new(reader);
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        (&mut reader)
+            .take(HEADER_LEN as u64)
+            .read_to_end(&mut header)?;
+        let word = |at: usize| {
+            u32::from_le_bytes([header[at], header[at + 1], header[at + 2], header[at + 3]])
+        };
+        let current = header.len() == HEADER_LEN
+            && header.starts_with(MAGIC)
+            && word(8) == STORE_VERSION
+            && word(12) & !KNOWN_FLAGS == 0;
+        if !current {
+            let mut data = header;
+            reader.read_to_end(&mut data)?;
+            let (store, _) = decode_store(&data)?;
+            return Ok(RecordStream::Loaded(store.records.into_iter()));
+        }
+        let flags = word(12);
+
+        let mut expected_mac = None;
+        let mut mac = None;
+        if flags & FLAG_SIGNED == 0 && signing_key().is_some() {
+            return Err(signature_missing());
+        }
+        if flags & FLAG_SIGNED != 0 {
+            let mut stored = vec![0u8; MAC_LEN];
+            reader.read_exact(&mut stored)?;
+            // Without a key the signature cannot be checked, as in `decode_store`.
+            if let Some(key) = signing_key() {
+                mac = Some(hmac_for(&key, &[&header[..]]));
+                expected_mac = Some(stored);
+            }
+        }
+        let mut reader = Checked {
+            inner: reader,
+            crc: !0,
+            mac,
+        };
+        let mut expected_crc = None;
+        if flags & FLAG_CHECKSUMS != 0 {
+            let mut crc = [0u8; 4];
+            reader.read_exact(&mut crc)?;
+            expected_crc = Some(u32::from_le_bytes(crc));
+            // The CRC covers what follows it; the MAC covers the CRC too.
+            reader.crc = !0;
+            let mut payload_len = [0u8; 8];
+            reader.read_exact(&mut payload_len)?;
+        }
+        let head: StoreHead = bincode_options(limit)
+            .deserialize_from(&mut reader)
+            .map_err(corrupt)?;
+        if head.vector_dim != VECTOR_DIM {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "incompatible store format",
+            ));
+        }
+        Ok(RecordStream::File(Box::new(FileRecords {
+            reader,
+            remaining: head.records,
+            limit,
+            expected_crc,
+            expected_mac,
+            done: false,
+        })))
+    }
+}
+
+/// The fields of a bincode `Store` before its records, with the record count
+/// in place of the `Vec`.
+#[derive(Deserialize)]
+struct StoreHead {
+    _version: u32,
+    vector_dim: usize,
+    _meta: StoreMeta,
+    _generation: u64,
+    records: u64,
+}
+
+/// See `Store::iter_records`.
+pub enum RecordStream {
+    Loaded(std::vec::IntoIter<Record>),
+    File(Box<FileRecords>),
+}
+
+pub struct FileRecords {
+    reader: Checked<Box<dyn BufRead>>,
+    remaining: u64,
+    /// Size of the file; no record can be longer.
+    limit: u64,
+    expected_crc: Option<u32>,
+    expected_mac: Option<Vec<u8>>,
+    done: bool,
+}
+
+/// A reader that keeps a running CRC-32 and HMAC of everything read through it.
+struct Checked<R> {
+    inner: R,
+    crc: u32,
+    mac: Option<Hmac<Sha256>>,
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.crc = crc32_update(self.crc, &buf[..n]);
+        if let Some(mac) = self.mac.as_mut() {
+            mac.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl Iterator for RecordStream {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        match self {
+            RecordStream::Loaded(records) => records.next().map(Ok),
+            RecordStream::File(file) => file.next(),
+        }
+    }
+}
+
+impl FileRecords {
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        if self.done {
+            return None;
+        }
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            let rec = bincode_options(self.limit)
+                .deserialize_from(&mut self.reader)
+                .map_err(corrupt);
+            self.done = rec.is_err();
+            return Some(rec);
+        }
+        self.done = true;
+        self.finish().err().map(Err)
+    }
+
+    /// Reads the rest of the file and checks the checksum and signature.
+    fn finish(&mut self) -> io::Result<()> {
+        io::copy(&mut self.reader, &mut io::sink())?;
+        if let Some(expected) = self.expected_crc {
+            if !self.reader.crc != expected {
+                return Err(checksum_mismatch());
+            }
+        }
+        if let (Some(mac), Some(expected)) = (self.reader.mac.take(), &self.expected_mac) {
+            mac.verify_slice(expected).map_err(|_| signature_mismatch())?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a file image from `encode_store` (or any older layout), checking
+/// its signature and checksum.
+pub fn decode_store(data: &[u8]) -> io::Result<(Store, u32)> {
+    let frame = split_header(data)?;
+    match frame.signature {
+        Signature::Invalid => return Err(signature_mismatch()),
+        Signature::Missing => return Err(signature_missing()),
+        Signature::Unsigned | Signature::Valid | Signature::NoKey => {}
+    }
+    if !frame.body_crc_ok {
+        return Err(checksum_mismatch());
+    }
+    let version = frame.version;
+    let store = decode_version(version, frame.payload)?;
+    Ok((store, version))
+}
+
+fn signature_mismatch() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "signature mismatch: the store was modified outside memstore or MEMSTORE_SIGNING_KEY is wrong",
+    )
+}
+
+fn signature_missing() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "store is not signed but MEMSTORE_SIGNING_KEY is set; sign it with `memstore rekey`",
+    )
+}
+
+fn checksum_mismatch() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "checksum mismatch, the file is corrupt or truncated; run `memstore verify`",
+    )
+}
+
+fn decode_version(version: u32, payload: &[u8]) -> io::Result<Store> {
+    let store = match version {
+        1 => migrate_v4(migrate_v3(migrate_v2(migrate_v1(decode(payload)?)))),
+        2 => migrate_v4(migrate_v3(migrate_v2(decode(payload)?))),
+        3 => migrate_v4(migrate_v3(decode(payload)?)),
+        4 => migrate_v4(decode(payload)?),
+        STORE_VERSION => decode(payload)?,
+        v if v > STORE_VERSION => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "store version {v} is newer than this memstore supports ({STORE_VERSION}); upgrade memstore"
+                ),
+            ))
+        }
+        v => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unknown store version {v}"),
+            ))
+        }
+    };
+    if store.vector_dim != VECTOR_DIM {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "incompatible store format",
+        ));
+    }
+    Ok(store)
+}
+
+/// Checks the header, the whole-file checksum and every record checksum,
+/// collecting problems instead of stopping at the first one.
+pub fn verify_store(path: &Path) -> io::Result<VerifyReport> {
+    let data = fs::read(path)?;
+    let frame = split_header(&data)?;
+    let mut report = VerifyReport {
+        version: frame.version,
+        records: 0,
+        checksummed: frame.record_crcs.is_some(),
+        signature: frame.signature,
+        problems: Vec::new(),
+    };
+    if frame.signature == Signature::Invalid {
+        report.problems.push("signature mismatch".to_string());
+    }
+    if !frame.body_crc_ok {
+        report.problems.push("file checksum mismatch".to_string());
+    }
+    let store = match decode_version(frame.version, frame.payload) {
+        Ok(store) => store,
+        Err(e) => {
+            report.problems.push(e.to_string());
+            return Ok(report);
+        }
+    };
+    report.records = store.records.len();
+    let mut seen: BTreeMap<u128, usize> = BTreeMap::new();
+    for rec in &store.records {
+        *seen.entry(rec.id).or_default() += 1;
+    }
+    for (id, n) in seen.into_iter().filter(|(_, n)| *n > 1) {
+        report.problems.push(format!(
+            "duplicate id {id} ({n} records); run `memstore repair --reassign-dup-ids`"
+        ));
+    }
+    // Indexes assume every vector has the store's dimension and finite values.
+    for rec in &store.records {
+        if rec.vector.len() != VECTOR_DIM {
+            report.problems.push(format!(
+                "record {}: vector has {} dimensions, expected {VECTOR_DIM}",
+                rec.id,
+                rec.vector.len()
+            ));
+        } else if rec.vector.iter().any(|v| !v.is_finite()) {
+            report
+                .problems
+                .push(format!("record {}: vector has non-finite values", rec.id));
+        }
+    }
+    if let Some(crcs) = &frame.record_crcs {
+        if crcs.len() != store.records.len() {
+            report.problems.push(format!(
+                "{} record checksums for {} records",
+                crcs.len(),
+                store.records.len()
+            ));
+        }
+        for (rec, expected) in store.records.iter().zip(crcs) {
+            if record_crc(rec)? != *expected {
+                report
+                    .problems
+                    .push(format!("record {}: checksum mismatch", rec.id));
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Splits a file into its framing parts. Files written before the header
+/// existed start directly with the bincode `version` field (1 or 2).
+fn split_header(data: &[u8]) -> io::Result<Frame<'_>> {
+    let truncated = || io::Error::new(io::ErrorKind::InvalidData, "truncated memstore file");
+    let read_u32 = |at: usize| {
+        data.get(at..at + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    if !data.starts_with(MAGIC) {
+        return match read_u32(0) {
+            Some(v @ (1 | 2)) => Ok(Frame {
+                version: v,
+                payload: data,
+                record_crcs: None,
+                body_crc_ok: true,
+                signature: unsigned(),
+            }),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a memstore file (missing MEMSTORE header)",
+            )),
+        };
+    }
+
+    let (Some(version), Some(flags)) = (read_u32(8), read_u32(12)) else {
+        return Err(truncated());
+    };
+    if flags & !KNOWN_FLAGS != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("store uses unsupported format flags {flags:#x}; upgrade memstore"),
+        ));
+    }
+    let (rest_at, signature) = if flags & FLAG_SIGNED == 0 {
+        (HEADER_LEN, unsigned())
+    } else {
+        let rest_at = HEADER_LEN + MAC_LEN;
+        let mac = data.get(HEADER_LEN..rest_at).ok_or_else(truncated)?;
+        let signature = match signing_key() {
+            None => Signature::NoKey,
+            Some(key) => {
+                match hmac_for(&key, &[&data[..HEADER_LEN], &data[rest_at..]]).verify_slice(mac) {
+                    Ok(()) => Signature::Valid,
+                    Err(_) => Signature::Invalid,
+                }
+            }
+        };
+        (rest_at, signature)
+    };
+    if flags & FLAG_CHECKSUMS == 0 {
+        return Ok(Frame {
+            version,
+            payload: &data[rest_at..],
+            record_crcs: None,
+            body_crc_ok: true,
+            signature,
+        });
+    }
+
+    let expected = read_u32(rest_at).ok_or_else(truncated)?;
+    let body = data.get(rest_at + 4..).ok_or_else(truncated)?;
+    let len_bytes = body.get(..8).ok_or_else(truncated)?;
+    let mut len = [0u8; 8];
+    len.copy_from_slice(len_bytes);
+    let payload_len = usize::try_from(u64::from_le_bytes(len)).map_err(|_| truncated())?;
+    let payload_end = 8usize.checked_add(payload_len).ok_or_else(truncated)?;
+    let payload = body.get(8..payload_end).ok_or_else(truncated)?;
+    let crcs = &body[payload_end..];
+    let record_crcs = bincode_options(crcs.len() as u64)
+        .deserialize(crcs)
+        .map_err(|_| truncated())?;
+    Ok(Frame {
+        version,
+        payload,
+        record_crcs: Some(record_crcs),
+        body_crc_ok: crc32(body) == expected,
+        signature,
+    })
+}
+
+/// How an unsigned file stands with the current key.
+fn unsigned() -> Signature {
+    match signing_key() {
+        Some(_) => Signature::Missing,
+        None => Signature::Unsigned,
+    }
+}
+
+/// The signing key, if stores should be signed and checked.
+fn signing_key() -> Option<Vec<u8>> {
+    #[cfg(test)]
+    if let Some(key) = tests::KEY.with(|key| key.borrow().clone()) {
+        return Some(key);
+    }
+    env::var_os("MEMSTORE_SIGNING_KEY")
+        .filter(|k| !k.is_empty())
+        .map(|k| k.into_encoded_bytes())
+}
+
+fn hmac_for(key: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
+fn record_crc(rec: &Record) -> io::Result<u32> {
+    let bytes = bincode::serialize(rec).map_err(|_| io::ErrorKind::InvalidData)?;
+    Ok(crc32(&bytes))
+}
+
+/// CRC-32 (IEEE 802.3, reflected), the same checksum zlib and gzip use.
+pub fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(!0, data)
+}
+
+/// Feeds `data` into a running CRC-32; start from `!0` and invert the result.
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0u32; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut k = 0;
+            while k < 8 {
+                c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+                k += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    for b in data {
+        crc = TABLE[((crc ^ *b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn decode<'a, T: Deserialize<'a>>(data: &'a [u8]) -> io::Result<T> {
+    bincode_options(data.len() as u64)
+        .deserialize(data)
+        .map_err(corrupt)
+}
+
+/// The encoding `bincode::serialize` writes, read back without ever taking
+/// in more than `limit` bytes. A corrupt length field then fails with
+/// `SizeLimit` instead of allocating whatever it claims, which from a reader
+/// bincode would do before finding out the bytes are not there.
+fn bincode_options(limit: u64) -> impl Options {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit)
+}
+
+fn corrupt(e: bincode::Error) -> io::Error {
+    let detail = match e.as_ref() {
+        bincode::ErrorKind::SizeLimit => {
+            "a length field claims more data than the file holds".to_string()
+        }
+        bincode::ErrorKind::Io(io) if io.kind() == io::ErrorKind::UnexpectedEof => {
+            "the file ends in the middle of a record".to_string()
+        }
+        _ => e.to_string(),
+    };
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("corrupt store payload: {detail}"),
+    )
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::cell::RefCell;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    thread_local! {
+        /// Stands in for `MEMSTORE_SIGNING_KEY`, which is shared by every test thread.
+        pub static KEY: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+    }
+
+    fn with_key<T>(key: Option<&str>, f: impl FnOnce() -> T) -> T {
+        KEY.with(|k| *k.borrow_mut() = key.map(|key| key.as_bytes().to_vec()));
+        let out = f();
+        KEY.with(|k| *k.borrow_mut() = None);
+        out
+    }
+
+    /// Text with the characters line-based formats trip over mixed in.
+    pub(crate) fn any_text() -> impl Strategy<Value = String> {
+        prop_oneof![any::<String>(), "[a-z \\r\\n\\t|\\\\\"'é中🦀]{0,40}"]
+    }
+
+    pub(crate) fn any_record() -> impl Strategy<Value = Record> {
+        (
+            (any::<u128>(), any::<i64>(), "[a-z_-]{1,12}", 0.0f32..100.0),
+            any_text(),
+            prop::collection::vec(-1.0f32..1.0, VECTOR_DIM),
+            prop::collection::vec(any_text(), 0..4),
+            prop::collection::btree_map("[a-z_]{1,10}", any_text(), 0..4),
+        )
+            .prop_map(
+                |((id, ts, kind, weight), text, vector, tags, attrs)| Record {
+                    id,
+                    ts,
+                    kind,
+                    weight,
+                    text,
+                    vector,
+                    tags,
+                    attrs,
+                },
+            )
+    }
+
+    fn any_store() -> impl Strategy<Value = Store> {
+        (
+            any_text(),
+            any_text(),
+            any::<i64>(),
+            any::<u64>(),
+            prop::collection::vec(any_record(), 0..6),
+        )
+            .prop_map(
+                |(name, description, created_at, generation, records)| Store {
+                    version: STORE_VERSION,
+                    vector_dim: VECTOR_DIM,
+                    meta: StoreMeta {
+                        name,
+                        description,
+                        created_at,
+                    },
+                    generation,
+                    records,
+                },
+            )
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn any_store_round_trips(store in any_store()) {
+            let data = encode_store(&store).unwrap();
+            let (decoded, version) = decode_store(&data).unwrap();
+            prop_assert_eq!(version, STORE_VERSION);
+            prop_assert_eq!(bytes(&decoded), bytes(&store));
+
+            let records = streamed(data).unwrap();
+            prop_assert_eq!(
+                bincode::serialize(&records).unwrap(),
+                bincode::serialize(&store.records).unwrap()
+            );
+        }
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("memstore-store-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("memories.hnsw")
+    }
+
+    #[test]
+    fn v4_files_migrate_with_generation_zero() {
+        let store = sample_store(3);
+        let payload = bincode::serialize(&(4u32, VECTOR_DIM, &store.meta, &store.records)).unwrap();
+        let data = [
+            MAGIC,
+            &4u32.to_le_bytes()[..],
+            &0u32.to_le_bytes(),
+            &payload,
+        ]
+        .concat();
+        let (decoded, version) = decode_store(&data).unwrap();
+        assert_eq!(version, 4);
+        assert_eq!(decoded.version, STORE_VERSION);
+        assert_eq!(decoded.generation, 0);
+        assert_eq!(
+            bincode::serialize(&decoded.records).unwrap(),
+            bincode::serialize(&store.records).unwrap()
+        );
+    }
+
+    #[test]
+    fn writes_bump_the_generation_and_stale_ones_fail() {
+        let path = scratch("generation");
+        let ((), first) = update_store_if(&path, None, |_| Ok(())).unwrap();
+        let ((), second) = update_store_if(&path, Some(first), |_| Ok(())).unwrap();
+        assert_eq!(second, first + 1);
+        assert_eq!(load_store(&path).unwrap().generation, second);
+
+        let err = update_store_if(&path, Some(first), |_| Ok(())).unwrap_err();
+        assert!(GenerationConflict::is(&err), "{err}");
+        assert_eq!(load_store(&path).unwrap().generation, second);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn saves_replace_the_file_whole_or_not_at_all() {
+        let path = scratch("atomic");
+        let tmp = sibling_path(&path, ".tmp");
+        save_store(&path, &sample_store(3)).unwrap();
+        assert_eq!(bytes(&load_store(&path).unwrap()), bytes(&sample_store(3)));
+        assert!(!tmp.exists());
+
+        // A write that died before its rename leaves a partial temp file,
+        // which neither the store nor the next save cares about.
+        fs::write(&tmp, &encode_store(&sample_store(5)).unwrap()[..40]).unwrap();
+        assert_eq!(bytes(&load_store(&path).unwrap()), bytes(&sample_store(3)));
+        save_store(&path, &sample_store(5)).unwrap();
+        assert_eq!(bytes(&load_store(&path).unwrap()), bytes(&sample_store(5)));
+        assert!(!tmp.exists());
+
+        // A save that cannot write leaves the last complete store in place.
+        fs::create_dir(&tmp).unwrap();
+        assert!(save_store(&path, &sample_store(1)).is_err());
+        assert_eq!(bytes(&load_store(&path).unwrap()), bytes(&sample_store(5)));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    pub(super) fn sample_store(n: usize) -> Store {
+        let mut store = new_store();
+        store.meta.name = "sample".to_string();
+        store.generation = 7;
+        for i in 0..n {
+            let mut vector = vec![0.0; VECTOR_DIM];
+            vector[i % VECTOR_DIM] = 1.0;
+            store.records.push(Record {
+                id: (i as u128) << 70 | i as u128,
+                ts: 1_700_000_000 + i as i64,
+                kind: ["fact", "decision"][i % 2].to_string(),
+                weight: 1.0 + i as f32 / 4.0,
+                text: format!("record {i}\nsecond line"),
+                vector,
+                tags: vec![format!("t{}", i % 3)],
+                attrs: BTreeMap::from([("n".to_string(), i.to_string())]),
+            });
+        }
+        store
+    }
+
+    fn bytes(store: &Store) -> Vec<u8> {
+        bincode::serialize(store).unwrap()
+    }
+
+    fn streamed(data: Vec<u8>) -> io::Result<Vec<Record>> {
+        let limit = data.len() as u64;
+        Store::iter_records(io::Cursor::new(data), limit)?.collect()
+    }
+
+    #[test]
+    fn signed_store_round_trips() {
+        let store = sample_store(5);
+        let data = with_key(Some("k1"), || encode_store(&store)).unwrap();
+        let (decoded, version) = with_key(Some("k1"), || decode_store(&data)).unwrap();
+        assert_eq!(version, STORE_VERSION);
+        assert_eq!(bytes(&decoded), bytes(&store));
+        let records = with_key(Some("k1"), || streamed(data.clone())).unwrap();
+        assert_eq!(records.len(), 5);
+        // Without a key the signature cannot be checked, and is not required.
+        assert!(decode_store(&data).is_ok());
+    }
+
+    #[test]
+    fn wrong_key_or_tampering_is_refused() {
+        let data = with_key(Some("k1"), || encode_store(&sample_store(3))).unwrap();
+        assert!(with_key(Some("k2"), || decode_store(&data)).is_err());
+        assert!(with_key(Some("k2"), || streamed(data.clone())).is_err());
+
+        let mut tampered = data.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(with_key(Some("k1"), || decode_store(&tampered)).is_err());
+    }
+
+    #[test]
+    fn unsigned_store_is_refused_when_a_key_is_set() {
+        let data = encode_store(&sample_store(3)).unwrap();
+        assert!(decode_store(&data).is_ok());
+        let err = with_key(Some("k1"), || decode_store(&data)).unwrap_err();
+        assert!(err.to_string().contains("not signed"), "{err}");
+        assert!(with_key(Some("k1"), || streamed(data.clone())).is_err());
+    }
+
+    #[test]
+    fn signed_store_is_not_rewritten_without_the_key() {
+        let path = scratch("keep-signature");
+        with_key(Some("k1"), || save_store(&path, &sample_store(2))).unwrap();
+        let signed = fs::read(&path).unwrap();
+
+        let err = save_store(&path, &sample_store(3)).unwrap_err();
+        assert!(err.to_string().contains("MEMSTORE_SIGNING_KEY"), "{err}");
+        assert_eq!(fs::read(&path).unwrap(), signed);
+
+        with_key(Some("k1"), || save_store(&path, &sample_store(3))).unwrap();
+        let store = with_key(Some("k1"), || load_store(&path)).unwrap();
+        assert_eq!(store.records.len(), 3);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
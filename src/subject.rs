@@ -0,0 +1,158 @@
+//! `subject export|erase`: everything stored about one data subject, identified
+//! by a tag such as `person:alice`. A sharded store is handled shard by shard.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::server::ApiRecord;
+use crate::store::{load_store, sibling_path, update_store, Record, STORE_VERSION};
+use crate::{
+    archive, config, index_cache, ivf, path_arg, pq, remote, resolve_path, shards, store_error,
+};
+
+pub fn cmd_subject(args: &[String]) -> Result<(), &'static str> {
+    let op = args.first().map(String::as_str);
+    if !matches!(op, Some("export" | "erase")) {
+        eprintln!("Usage: subject export|erase --tag <tag> [--yes] [--path <file>]");
+        return Err("unknown subject command");
+    }
+    let mut tag: Option<String> = None;
+    let mut yes = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--tag" => {
+                i += 1;
+                tag = args.get(i).cloned();
+            }
+            "--yes" => yes = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let Some(tag) = tag else {
+        eprintln!("Missing --tag");
+        return Err("missing tag");
+    };
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    if remote::store_url(&path).is_some() {
+        eprintln!("This command only works on local stores");
+        return Err("remote unsupported");
+    }
+    let sharded = shards::is_sharded(&path);
+    let stores = if sharded {
+        shards::in_window(&path, None).map_err(store_error(&path, "read failed"))?
+    } else {
+        vec![path.clone()]
+    };
+
+    // The cold archives hold records about the subject too.
+    let mut records = Vec::new();
+    for store in &stores {
+        records.extend(
+            load_store(store)
+                .map_err(store_error(store, "read failed"))?
+                .records,
+        );
+        records.extend(
+            archive::load_archive(store)
+                .map_err(store_error(store, "read failed"))?
+                .records,
+        );
+    }
+    if op == Some("export") {
+        // One JSON object per line; vectors are derived data and left out.
+        for rec in records.into_iter().filter(|r| r.tags.contains(&tag)) {
+            let json = serde_json::to_string(&ApiRecord::from(rec)).map_err(|_| "encode failed")?;
+            println!("{json}");
+        }
+        return Ok(());
+    }
+
+    if !yes {
+        let n = records.iter().filter(|r| r.tags.contains(&tag)).count();
+        eprintln!("Would erase {n} records tagged {tag}; pass --yes to erase them");
+        return Err("not confirmed");
+    }
+    // A shard that fails does not stop the others from being erased; the
+    // command still fails at the end.
+    let mut erased = 0;
+    let mut failed = 0;
+    for store in &stores {
+        match erase(store, &tag) {
+            Ok(n) => erased += n,
+            Err(e) => {
+                eprintln!("{}: {e}", store.display());
+                failed += 1;
+            }
+        }
+    }
+    eprintln!("Erased {erased} records tagged {tag}");
+    for store in &stores {
+        warn_about_backups(store);
+    }
+    if sharded {
+        warn_about_backups(&path);
+    }
+    if failed > 0 && sharded {
+        eprintln!(
+            "{failed} of {} shards could not be erased; run the erase again",
+            stores.len()
+        );
+    }
+    if failed > 0 {
+        return Err("write failed");
+    }
+    Ok(())
+}
+
+/// Removes the records tagged `tag` from the store at `path` and its archive.
+/// Records are removed from the file itself (there are no tombstones); the
+/// cached search indexes are built from their vectors, so they go too.
+fn erase(path: &Path, tag: &str) -> io::Result<usize> {
+    let untagged = |r: &Record| !r.tags.iter().any(|t| t == tag);
+    let erased = update_store(path, |store| {
+        let before = store.records.len();
+        store.records.retain(untagged);
+        let archived = archive::retain(path, untagged)?;
+        Ok(before - store.records.len() + archived)
+    })?;
+    let caches = index_cache::cache_files(path)
+        .into_iter()
+        .chain(ivf::cache_files(path))
+        .chain(pq::cache_files(path));
+    for cache in caches {
+        fs::remove_file(&cache)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", cache.display())))?;
+    }
+    Ok(erased)
+}
+
+/// `upgrade` backups are copies of the store from before the erase.
+fn warn_about_backups(path: &Path) {
+    for version in 1..STORE_VERSION {
+        let backup = sibling_path(path, &format!(".v{version}.bak"));
+        if backup.exists() {
+            eprintln!(
+                "Warning: {} may still contain erased records; delete it if it is no longer needed",
+                backup.display()
+            );
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! `tag`: curating tags on stored records.
+//!
+//! ```text
+//! tag add --id <id>... <tag>...      tag remove --id <id>... <tag>...
+//! tag list                           tag rename --from <tag> --to <tag>
+//! tag add --query <text> [--threshold 0.8] [--dry-run] <tag>...
+//! ```
+//!
+//! With `--query`, every record similar enough to the query is retagged at
+//! once, which retrofits tags onto an existing store; `--dry-run` shows the
+//! records and their similarity first.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::store::{load_store, update_store, Record};
+use crate::{
+    config, cosine_sim, local_only, parse_flag, parse_float, path_arg, query, resolve_path,
+    store_error, tsv_field,
+};
+
+const USAGE: &str = "Usage: tag add|remove (--id <id>... | --query <text> [--threshold <sim>] [--dry-run]) <tag>... \
+                     | tag list | tag rename --from <tag> --to <tag>";
+/// Cosine similarity `tag add --query` needs by default.
+const DEFAULT_THRESHOLD: f32 = 0.8;
+
+pub fn cmd_tag(args: &[String]) -> Result<(), &'static str> {
+    let Some(sub) = args.first().map(String::as_str) else {
+        eprintln!("{USAGE}");
+        return Err("missing tag command");
+    };
+    let mut ids: Vec<u128> = Vec::new();
+    let mut tags: Vec<String> = Vec::new();
+    let mut from: Option<String> = None;
+    let mut to: Option<String> = None;
+    let mut query: Option<String> = None;
+    let mut threshold = DEFAULT_THRESHOLD;
+    let mut dry_run = false;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--id" => {
+                i += 1;
+                ids.push(parse_flag("--id", args.get(i))?);
+            }
+            "--from" => {
+                i += 1;
+                from = args.get(i).cloned();
+            }
+            "--to" => {
+                i += 1;
+                to = args.get(i).cloned();
+            }
+            "--query" => {
+                i += 1;
+                query = args.get(i).cloned();
+            }
+            "--threshold" => {
+                i += 1;
+                threshold = parse_float("--threshold", args.get(i))?;
+            }
+            "--dry-run" => dry_run = true,
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => tags.push(other.to_string()),
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+
+    match sub {
+        "add" | "remove" => {
+            let adding = sub == "add";
+            if tags.is_empty() || ids.is_empty() == query.is_none() {
+                eprintln!("{USAGE}");
+                return Err("missing argument");
+            }
+            if let Some(query) = query {
+                return retag_by_query(&path, &query, threshold, &tags, adding, dry_run);
+            }
+            let (changed, matched) = retag(&path, |r| ids.contains(&r.id), &tags, adding)
+                .map_err(store_error(&path, "write failed"))?;
+            let missing: Vec<&u128> = ids.iter().filter(|id| !matched.contains(id)).collect();
+            for id in &missing {
+                eprintln!("No record with id {id}");
+            }
+            eprintln!("Updated {changed} records");
+            if missing.is_empty() {
+                Ok(())
+            } else {
+                Err("unknown id")
+            }
+        }
+        "list" => {
+            let store = load_store(&path).map_err(store_error(&path, "read failed"))?;
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for tag in store.records.iter().flat_map(|r| &r.tags) {
+                *counts.entry(tag.as_str()).or_insert(0) += 1;
+            }
+            let mut counts: Vec<(&str, usize)> = counts.into_iter().collect();
+            counts.sort_by_key(|c| std::cmp::Reverse(c.1));
+            for (tag, n) in counts {
+                println!("{n}\t{tag}");
+            }
+            Ok(())
+        }
+        "rename" => {
+            let (Some(from), Some(to)) = (from, to) else {
+                eprintln!("{USAGE}");
+                return Err("missing argument");
+            };
+            let changed = rename(&path, &from, &to).map_err(store_error(&path, "write failed"))?;
+            eprintln!("Renamed {from:?} to {to:?} on {changed} records");
+            Ok(())
+        }
+        other => {
+            eprintln!("Unknown tag command {other:?}");
+            eprintln!("{USAGE}");
+            Err("unknown tag command")
+        }
+    }
+}
+
+/// Adds or removes `tags` on the records `select` picks, in one locked pass.
+/// Returns how many records changed and the ids of all that were picked.
+fn retag(
+    path: &Path,
+    select: impl Fn(&Record) -> bool,
+    tags: &[String],
+    adding: bool,
+) -> io::Result<(usize, HashSet<u128>)> {
+    update_store(path, |store| {
+        let mut matched = HashSet::new();
+        let mut changed = 0;
+        for rec in store.records.iter_mut() {
+            if !select(rec) {
+                continue;
+            }
+            matched.insert(rec.id);
+            let before = rec.tags.len();
+            if adding {
+                for tag in tags {
+                    if !rec.tags.contains(tag) {
+                        rec.tags.push(tag.clone());
+                    }
+                }
+            } else {
+                rec.tags.retain(|t| !tags.contains(t));
+            }
+            if rec.tags.len() != before {
+                changed += 1;
+            }
+        }
+        Ok((changed, matched))
+    })
+}
+
+/// Retags every record whose cosine similarity to the query reaches
+/// `threshold` and that passes the query's filters (`kind:`, `tag:` ...).
+/// `dry_run` lists them as `similarity id kind text` instead.
+fn retag_by_query(
+    path: &Path,
+    query: &str,
+    threshold: f32,
+    tags: &[String],
+    adding: bool,
+    dry_run: bool,
+) -> Result<(), &'static str> {
+    let parsed = query::parse_query(query).map_err(|e| {
+        eprintln!("{e}");
+        "invalid query"
+    })?;
+    let query_vec = parsed.vector();
+    let similarity = |r: &Record| cosine_sim(&query_vec, &r.vector);
+    let select = |r: &Record| parsed.filter.matches(r) && similarity(r) >= threshold;
+    if dry_run {
+        let store = load_store(path).map_err(store_error(path, "read failed"))?;
+        let mut hits: Vec<(f32, &Record)> = store
+            .records
+            .iter()
+            .filter(|&r| select(r))
+            .map(|r| (similarity(r), r))
+            .collect();
+        hits.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (sim, rec) in &hits {
+            println!("{sim:.3}\t{}\t{}\t{}", rec.id, rec.kind, tsv_field(&rec.text));
+        }
+        let verb = if adding { "tag" } else { "untag" };
+        eprintln!("Would {verb} {} records (dry run)", hits.len());
+        return Ok(());
+    }
+    let (changed, matched) =
+        retag(path, select, tags, adding).map_err(store_error(path, "write failed"))?;
+    eprintln!("{} records matched; updated {changed}", matched.len());
+    Ok(())
+}
+
+/// Replaces `from` with `to` on every record; a record that already has `to`
+/// just loses `from`. Returns how many records changed.
+fn rename(path: &Path, from: &str, to: &str) -> io::Result<usize> {
+    update_store(path, |store| {
+        let mut changed = 0;
+        for rec in store.records.iter_mut() {
+            let Some(at) = rec.tags.iter().position(|t| t == from) else {
+                continue;
+            };
+            if rec.tags.iter().any(|t| t == to) {
+                rec.tags.remove(at);
+            } else {
+                rec.tags[at] = to.to_string();
+            }
+            changed += 1;
+        }
+        Ok(changed)
+    })
+}
@@ -0,0 +1,129 @@
+//! `add --template <name> --field key=value...`: records of one kind written
+//! from a `[templates.<name>]` layout in the config, so they read alike and
+//! match the same queries.
+//!
+//! Each `{field}` in the template text is replaced by the field's value. A
+//! line whose placeholders were all left empty is dropped, so optional fields
+//! leave no `Alternatives:` stub behind. Fields the template does not mention
+//! are an error, which catches misspelled names. The record gets the
+//! template's kind and tags, a `template` attribute, and the fields listed in
+//! `attrs` as attributes of their own.
+
+use std::collections::BTreeMap;
+
+use crate::config::{self, Template};
+
+/// A filled-in template, ready for `add`.
+pub struct Rendered {
+    pub text: String,
+    pub kind: String,
+    pub tags: Vec<String>,
+    pub attrs: BTreeMap<String, String>,
+}
+
+/// Looks `name` up in the config and fills it in from `key=value` pairs.
+pub fn render_named(name: &str, fields: &[String]) -> Result<Rendered, &'static str> {
+    let config = config::load_config().map_err(|e| {
+        eprintln!("Cannot read {}: {e}", config::config_path().display());
+        "config failed"
+    })?;
+    let Some(template) = config.templates.get(name) else {
+        let known: Vec<&str> = config.templates.keys().map(String::as_str).collect();
+        if known.is_empty() {
+            eprintln!("No template {name:?}; define [templates.{name}] in the config");
+        } else {
+            eprintln!("No template {name:?} (known: {})", known.join(", "));
+        }
+        return Err("unknown template");
+    };
+    let mut values = BTreeMap::new();
+    for field in fields {
+        let Some((key, value)) = field.split_once('=') else {
+            eprintln!("--field takes key=value, got {field:?}");
+            return Err("invalid field");
+        };
+        values.insert(key.trim().to_string(), value.to_string());
+    }
+    render(name, template, &values).map_err(|e| {
+        eprintln!("Template {name:?}: {e}");
+        "invalid field"
+    })
+}
+
+pub fn render(
+    name: &str,
+    template: &Template,
+    values: &BTreeMap<String, String>,
+) -> Result<Rendered, String> {
+    let placeholders = placeholders(&template.text);
+    if let Some(unknown) = values.keys().find(|k| !placeholders.contains(*k)) {
+        return Err(format!(
+            "no field {unknown:?} (fields: {})",
+            placeholders.join(", ")
+        ));
+    }
+    let missing: Vec<&str> = template
+        .required
+        .iter()
+        .filter(|f| values.get(*f).is_none_or(|v| v.trim().is_empty()))
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(format!("missing required fields: {}", missing.join(", ")));
+    }
+
+    let mut lines = Vec::new();
+    for line in template.text.lines() {
+        let mut out = line.to_string();
+        let mut filled = false;
+        let mut mentioned = false;
+        for field in placeholders
+            .iter()
+            .filter(|f| line.contains(&format!("{{{f}}}")))
+        {
+            mentioned = true;
+            let value = values.get(field).map_or("", |v| v.trim());
+            filled |= !value.is_empty();
+            out = out.replace(&format!("{{{field}}}"), value);
+        }
+        if filled || !mentioned {
+            lines.push(out);
+        }
+    }
+    let text = lines.join("\n").trim().to_string();
+    if text.is_empty() {
+        return Err("every field is empty".to_string());
+    }
+
+    let mut attrs = BTreeMap::new();
+    attrs.insert("template".to_string(), name.to_string());
+    for field in &template.attrs {
+        if let Some(value) = values.get(field).filter(|v| !v.trim().is_empty()) {
+            attrs.insert(field.clone(), value.trim().to_string());
+        }
+    }
+    Ok(Rendered {
+        text,
+        kind: template.kind.clone().unwrap_or_else(|| name.to_string()),
+        tags: template.tags.clone(),
+        attrs,
+    })
+}
+
+/// Names between `{` and `}` in `text`, in order of first appearance.
+fn placeholders(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        let after = &rest[start + 1..];
+        let Some(end) = after.find('}') else {
+            break;
+        };
+        let name = &after[..end];
+        if !name.is_empty() && !name.contains('{') && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+        rest = &after[end + 1..];
+    }
+    names
+}
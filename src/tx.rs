@@ -0,0 +1,399 @@
+//! `tx`: a batch of adds, edits and deletes applied as one write, so either
+//! all of them land or none do.
+//!
+//! ```text
+//! {"op":"add","text":"Rollback needs the old schema","kind":"decision","tags":["infra"]}
+//! {"op":"edit","id":1718000000000,"text":"Rollback needs the v2 schema","weight":2.0}
+//! {"op":"delete","id":1717990000000}
+//! ```
+//!
+//! Operations are read as JSON Lines from a file or stdin and applied in
+//! order to one copy of the store, which is saved only if every one of them
+//! succeeded: a bad weight or kind, text over `max_text`, an unknown id or a
+//! store that has moved past `--expected-generation` fails the whole batch
+//! and leaves the file untouched. Text is never truncated or chunked here.
+//! `rpc` takes the same operations as the `tx` method.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, AddLimits};
+use crate::store::{ensure_parent_dir, update_store_if, Record};
+use crate::{
+    embed_text, lang, local_only, now_millis, now_secs, parse_flag, path_arg, resolve_path, shards,
+    store_error,
+};
+
+const DEFAULT_KIND: &str = "summary";
+
+/// One line of a batch. Flat rather than a tagged enum: serde buffers tagged
+/// enums through a representation that cannot hold the u128 ids.
+#[derive(Debug, Deserialize)]
+pub struct Op {
+    pub op: OpKind,
+    /// Record to edit or delete.
+    #[serde(default)]
+    pub id: Option<u128>,
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub kind: Option<String>,
+    #[serde(default)]
+    pub weight: Option<f32>,
+    /// Replaces the record's tags on `edit`.
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OpKind {
+    Add,
+    Edit,
+    Delete,
+}
+
+#[derive(Deserialize)]
+pub struct TxRequest {
+    pub ops: Vec<Op>,
+    #[serde(default)]
+    pub expected_generation: Option<u64>,
+}
+
+/// The record each operation touched, in the order given.
+#[derive(Serialize)]
+pub struct Applied {
+    pub op: OpKind,
+    pub id: u128,
+}
+
+#[derive(Serialize)]
+pub struct TxResponse {
+    pub applied: Vec<Applied>,
+    pub generation: u64,
+}
+
+pub fn cmd_tx(args: &[String]) -> Result<(), &'static str> {
+    let mut input: Option<PathBuf> = None;
+    let mut expected_generation: Option<u64> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--expected-generation" => {
+                i += 1;
+                expected_generation = Some(parse_flag("--expected-generation", args.get(i))?);
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            other => input = Some(PathBuf::from(other)),
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    let limits = config::add_limits(&profile)?;
+
+    let input = input.unwrap_or_else(|| PathBuf::from("-"));
+    let reader: Box<dyn BufRead> = if input == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(
+            File::open(&input).map_err(store_error(&input, "read failed"))?,
+        ))
+    };
+    let mut ops = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line.map_err(store_error(&input, "read failed"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let op = serde_json::from_str::<Op>(&line).map_err(|e| {
+            eprintln!("{}:{}: {e}", input.display(), n + 1);
+            "invalid input"
+        })?;
+        ops.push(op);
+    }
+    if ops.is_empty() {
+        eprintln!("No operations given; nothing written");
+        return Ok(());
+    }
+
+    ensure_parent_dir(&path).map_err(|_| "mkdir failed")?;
+    let response = apply(&path, ops, &limits, expected_generation).map_err(|e| {
+        eprintln!("{}: tx failed, nothing written: {e}", path.display());
+        "tx failed"
+    })?;
+    for applied in &response.applied {
+        let op = match applied.op {
+            OpKind::Add => "add",
+            OpKind::Edit => "edit",
+            OpKind::Delete => "delete",
+        };
+        println!("{op}\t{}", applied.id);
+    }
+    println!("generation\t{}", response.generation);
+    Ok(())
+}
+
+/// Applies `ops` in order under one store lock and saves once. Invalid
+/// operations are an `InvalidInput` error and unknown ids `NotFound`; either
+/// way nothing is saved.
+pub fn apply(
+    path: &Path,
+    ops: Vec<Op>,
+    limits: &AddLimits,
+    expected_generation: Option<u64>,
+) -> io::Result<TxResponse> {
+    if shards::is_sharded(path) {
+        return Err(invalid("tx does not support sharded stores".to_string()));
+    }
+    // Checked up front, so a bad last line fails before any embedding work.
+    for (n, op) in ops.iter().enumerate() {
+        check(op, limits).map_err(|e| invalid(format!("operation {}: {e}", n + 1)))?;
+    }
+    let (applied, generation) = update_store_if(path, expected_generation, |store| {
+        let mut applied = Vec::with_capacity(ops.len());
+        let mut next_id = now_millis();
+        for (n, op) in ops.into_iter().enumerate() {
+            let missing = |id: u128| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("operation {}: no record with id {id}", n + 1),
+                )
+            };
+            let id = match op.op {
+                OpKind::Add => {
+                    // Ids are wall-clock millis, one apart within the batch.
+                    while store.records.iter().any(|r| r.id == next_id) {
+                        next_id += 1;
+                    }
+                    let text = op.text.unwrap_or_default();
+                    let mut attrs = BTreeMap::new();
+                    if let Some(lang) = lang::detect(&text) {
+                        attrs.insert(lang::ATTR.to_string(), lang.to_string());
+                    }
+                    store.records.push(Record {
+                        id: next_id,
+                        ts: now_secs(),
+                        kind: op.kind.unwrap_or_else(|| DEFAULT_KIND.to_string()),
+                        weight: op.weight.unwrap_or(1.0),
+                        vector: embed_text(&text),
+                        text,
+                        tags: op.tags.unwrap_or_default(),
+                        attrs,
+                    });
+                    next_id += 1;
+                    next_id - 1
+                }
+                OpKind::Edit => {
+                    let id = op.id.unwrap_or_default();
+                    let rec = store
+                        .records
+                        .iter_mut()
+                        .find(|r| r.id == id)
+                        .ok_or_else(|| missing(id))?;
+                    if let Some(text) = op.text {
+                        // The old guess describes the old text.
+                        rec.attrs.remove(lang::ATTR);
+                        if let Some(lang) = lang::detect(&text) {
+                            rec.attrs.insert(lang::ATTR.to_string(), lang.to_string());
+                        }
+                        rec.vector = embed_text(&text);
+                        rec.text = text;
+                    }
+                    if let Some(kind) = op.kind {
+                        rec.kind = kind;
+                    }
+                    if let Some(weight) = op.weight {
+                        rec.weight = weight;
+                    }
+                    if let Some(tags) = op.tags {
+                        rec.tags = tags;
+                    }
+                    id
+                }
+                OpKind::Delete => {
+                    let id = op.id.unwrap_or_default();
+                    let at = store
+                        .records
+                        .iter()
+                        .position(|r| r.id == id)
+                        .ok_or_else(|| missing(id))?;
+                    store.records.remove(at);
+                    id
+                }
+            };
+            applied.push(Applied { op: op.op, id });
+        }
+        Ok(applied)
+    })?;
+    Ok(TxResponse {
+        applied,
+        generation,
+    })
+}
+
+/// What can be checked without the store: required fields, weights, kinds
+/// and `max_text`. Unknown kinds under `warn` are reported on stderr.
+fn check(op: &Op, limits: &AddLimits) -> Result<(), String> {
+    match op.op {
+        OpKind::Add => match &op.text {
+            Some(text) if !text.trim().is_empty() => {}
+            _ => return Err("add needs text".to_string()),
+        },
+        OpKind::Edit if op.id.is_none() => return Err("edit needs an id".to_string()),
+        OpKind::Delete if op.id.is_none() => return Err("delete needs an id".to_string()),
+        OpKind::Edit => {}
+        OpKind::Delete => return Ok(()),
+    }
+    if let Some(weight) = op.weight {
+        limits.check_weight(weight)?;
+    }
+    let kind = match op.op {
+        OpKind::Add => Some(op.kind.as_deref().unwrap_or(DEFAULT_KIND)),
+        _ => op.kind.as_deref(),
+    };
+    if let Some(kind) = kind {
+        if let Some(warning) = limits.check_kind(kind)? {
+            eprintln!("Warning: {warning}");
+        }
+    }
+    if let (Some(text), Some(limit)) = (&op.text, &limits.max_text) {
+        if text.len() > limit.byte_limit() {
+            return Err(format!(
+                "text is {} bytes, over the max_text limit of {}",
+                text.len(),
+                limit.max_bytes
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use super::*;
+    use crate::store::load_store;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("memstore-tx-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("memories.hnsw")
+    }
+
+    fn ops(lines: &[&str]) -> Vec<Op> {
+        lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn ops_keep_full_u128_ids() {
+        let id = u128::MAX - 1;
+        let [op] = ops(&[&format!(r#"{{"op":"delete","id":{id}}}"#)])
+            .try_into()
+            .unwrap();
+        assert_eq!(op.id, Some(id));
+
+        let request: TxRequest = serde_json::from_str(&format!(
+            r#"{{"ops":[{{"op":"edit","id":{id},"weight":2.0}}],"expected_generation":3}}"#
+        ))
+        .unwrap();
+        assert_eq!(request.ops[0].id, Some(id));
+        assert_eq!(request.expected_generation, Some(3));
+
+        let response = serde_json::to_string(&TxResponse {
+            applied: vec![Applied {
+                op: OpKind::Edit,
+                id,
+            }],
+            generation: 4,
+        })
+        .unwrap();
+        assert_eq!(
+            response,
+            format!(r#"{{"applied":[{{"op":"edit","id":{id}}}],"generation":4}}"#)
+        );
+    }
+
+    #[test]
+    fn edit_detects_the_language_of_the_new_text() {
+        let path = scratch("lang");
+        let limits = AddLimits::default();
+        let added = apply(
+            &path,
+            ops(&[
+                r#"{"op":"add","text":"The rollback is what we should have done and it was not"}"#,
+            ]),
+            &limits,
+            None,
+        )
+        .unwrap();
+        let id = added.applied[0].id;
+        let lang_of = |path: &Path| {
+            load_store(path).unwrap().records[0]
+                .attrs
+                .get(lang::ATTR)
+                .cloned()
+        };
+        assert_eq!(lang_of(&path).as_deref(), Some("en"));
+
+        let edit = format!(
+            r#"{{"op":"edit","id":{id},"text":"Wir haben das nicht gemacht, und es ist auch nicht nötig"}}"#
+        );
+        apply(&path, ops(&[&edit]), &limits, None).unwrap();
+        assert_eq!(lang_of(&path).as_deref(), Some("de"));
+
+        let edit = format!(r#"{{"op":"edit","id":{id},"text":"v2 schema"}}"#);
+        apply(&path, ops(&[&edit]), &limits, None).unwrap();
+        assert_eq!(lang_of(&path), None);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn a_failed_operation_saves_nothing() {
+        let path = scratch("atomic");
+        let limits = AddLimits::default();
+        let err = apply(
+            &path,
+            ops(&[
+                r#"{"op":"add","text":"first"}"#,
+                r#"{"op":"delete","id":12345}"#,
+            ]),
+            &limits,
+            None,
+        )
+        .err()
+        .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(load_store(&path).unwrap().records.is_empty());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
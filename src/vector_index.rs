@@ -0,0 +1,275 @@
+//! `index add-vector` / `index query`: memstore as a bare ANN index over
+//! vectors computed elsewhere, for callers that only want the persisted HNSW
+//! and keep their own text. Nothing is embedded and no text is stored.
+//!
+//! Entries are `(id, vector)` pairs in their own file, by default the
+//! profile's store path with `.vectors` appended, so they never mix with
+//! records. The first vector fixes the dimension; later ones must match it.
+//! Adding an id that exists replaces its vector. The HNSW graph is cached next
+//! to the file like a store's, and rebuilt only after the file changes.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use hnsw_rs::prelude::Neighbour;
+use serde::{Deserialize, Serialize};
+
+use crate::index_cache::{self, Fingerprint};
+use crate::store::{file_stamp, lock_store, sibling_path, write_atomic};
+use crate::{config, local_only, parse_flag, path_arg, resolve_path, store_error, HNSW_EF_SEARCH};
+
+const MAGIC: &[u8; 8] = b"MEMVECS1";
+/// Widest vector accepted.
+const MAX_DIM: usize = 8192;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Vectors {
+    /// 0 until the first vector is added.
+    dim: usize,
+    entries: BTreeMap<u128, Vec<f32>>,
+}
+
+/// One line of `add-vector --from`.
+#[derive(Deserialize)]
+struct Line {
+    id: u128,
+    vector: Vec<f32>,
+}
+
+pub fn cmd_index(args: &[String]) -> Result<(), &'static str> {
+    let sub = args.first().map(String::as_str);
+    let mut ids: Vec<u128> = Vec::new();
+    let mut vector: Option<Vec<f32>> = None;
+    let mut from: Option<PathBuf> = None;
+    let mut k: usize = 10;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 1;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--id" => {
+                i += 1;
+                ids.push(parse_flag("--id", args.get(i))?);
+            }
+            "--vector" => {
+                i += 1;
+                vector = Some(parse_vector(args.get(i))?);
+            }
+            "--from" => {
+                i += 1;
+                from = args.get(i).map(|v| path_arg(v));
+            }
+            "-k" => {
+                i += 1;
+                k = parse_flag("-k", args.get(i))?;
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let profile = config::select_profile(profile.as_deref())?;
+            sibling_path(&resolve_path(None, &profile), ".vectors")
+        }
+    };
+    local_only(&path)?;
+    match (sub, ids.as_slice(), vector, from) {
+        (Some("add-vector"), [id], Some(vector), None) => add(&path, vec![(*id, vector)]),
+        (Some("add-vector"), [], None, Some(from)) => {
+            let entries = read_lines(&from)?;
+            add(&path, entries)
+        }
+        (Some("query"), [], Some(vector), None) => query(&path, &vector, k),
+        (Some("rm"), [_, ..], None, None) => remove(&path, &ids),
+        _ => {
+            eprintln!("Usage: index add-vector --id <id> --vector <v> | add-vector --from <file.jsonl | ->");
+            eprintln!("       index query --vector <v> [-k <n>] | rm --id <id>... [--path <file>]");
+            Err("invalid index command")
+        }
+    }
+}
+
+fn add(path: &Path, entries: Vec<(u128, Vec<f32>)>) -> Result<(), &'static str> {
+    let n = entries.len();
+    update(path, |vectors| {
+        for (id, vector) in entries {
+            if vectors.dim == 0 {
+                vectors.dim = vector.len();
+            }
+            if vector.len() != vectors.dim {
+                eprintln!(
+                    "Vector for {id} has {} dimensions; this index holds {}",
+                    vector.len(),
+                    vectors.dim
+                );
+                return Err("dimension mismatch");
+            }
+            vectors.entries.insert(id, vector);
+        }
+        Ok(())
+    })?;
+    eprintln!("Added {n} vectors");
+    Ok(())
+}
+
+fn remove(path: &Path, ids: &[u128]) -> Result<(), &'static str> {
+    let removed = update(path, |vectors| {
+        let before = vectors.entries.len();
+        vectors.entries.retain(|id, _| !ids.contains(id));
+        Ok(before - vectors.entries.len())
+    })?;
+    eprintln!("Removed {removed} of {} vectors", ids.len());
+    Ok(())
+}
+
+/// Prints `similarity id` for the `k` nearest entries, nearest first.
+fn query(path: &Path, vector: &[f32], k: usize) -> Result<(), &'static str> {
+    // Taken before reading, as for stores: a write in between only costs a rebuild.
+    let stamp = file_stamp(path).ok();
+    let data = match fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("{} holds no vectors yet", path.display());
+            return Ok(());
+        }
+        Err(e) => return Err(store_error(path, "read failed")(e)),
+    };
+    let vectors = decode(&data).map_err(store_error(path, "read failed"))?;
+    if vectors.entries.is_empty() {
+        eprintln!("{} holds no vectors yet", path.display());
+        return Ok(());
+    }
+    if vector.len() != vectors.dim {
+        eprintln!(
+            "Query has {} dimensions; this index holds {}",
+            vector.len(),
+            vectors.dim
+        );
+        return Err("dimension mismatch");
+    }
+    let ids: Vec<u128> = vectors.entries.keys().copied().collect();
+    let vecs: Vec<Vec<f32>> = vectors.entries.into_values().collect();
+    let fingerprint = stamp.map(|stamp| Fingerprint::of_data(stamp, &data));
+    let k = k.min(vecs.len());
+    let neighbours: Vec<Neighbour> =
+        index_cache::with_index(path, fingerprint, &vecs, false, |hnsw| {
+            hnsw.search(vector, k, HNSW_EF_SEARCH.max(k))
+        });
+    for n in neighbours {
+        println!("{:.4}\t{}", 1.0 - n.distance, ids[n.d_id]);
+    }
+    Ok(())
+}
+
+/// Load-modify-save under the same lock a store at `path` would take.
+fn update<T>(
+    path: &Path,
+    f: impl FnOnce(&mut Vectors) -> Result<T, &'static str>,
+) -> Result<T, &'static str> {
+    let _lock = lock_store(path).map_err(store_error(path, "lock failed"))?;
+    let mut vectors = match fs::read(path) {
+        Ok(data) => decode(&data).map_err(store_error(path, "read failed"))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vectors::default(),
+        Err(e) => return Err(store_error(path, "read failed")(e)),
+    };
+    let out = f(&mut vectors)?;
+    let body = bincode::serialize(&vectors).map_err(|_| "encode failed")?;
+    write_atomic(path, &[MAGIC, &body]).map_err(store_error(path, "write failed"))?;
+    Ok(out)
+}
+
+fn decode(data: &[u8]) -> io::Result<Vectors> {
+    let body = data.strip_prefix(MAGIC).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a vector index (missing MEMVECS1 header)",
+        )
+    })?;
+    bincode::deserialize(body).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("corrupt vector index: {e}"),
+        )
+    })
+}
+
+/// `--vector`: a JSON array (`[0.1, 0.2]`) or bare comma-separated numbers.
+fn parse_vector(value: Option<&String>) -> Result<Vec<f32>, &'static str> {
+    let Some(value) = value else {
+        eprintln!("Missing value for --vector");
+        return Err("missing value");
+    };
+    let inner = value.trim().trim_start_matches('[').trim_end_matches(']');
+    let parsed: Result<Vec<f32>, _> = inner.split(',').map(|v| v.trim().parse()).collect();
+    match parsed {
+        Ok(vector) => check_vector(vector).map_err(|e| {
+            eprintln!("Invalid --vector: {e}");
+            "invalid vector"
+        }),
+        Err(_) => {
+            eprintln!("--vector takes numbers separated by commas, e.g. 0.1,0.2,0.3");
+            Err("invalid vector")
+        }
+    }
+}
+
+fn check_vector(vector: Vec<f32>) -> Result<Vec<f32>, String> {
+    if vector.is_empty() || vector.len() > MAX_DIM {
+        return Err(format!(
+            "expected 1 to {MAX_DIM} dimensions, got {}",
+            vector.len()
+        ));
+    }
+    if vector.iter().any(|v| !v.is_finite()) {
+        return Err("values must be finite".to_string());
+    }
+    if vector.iter().all(|v| *v == 0.0) {
+        return Err("the zero vector has no direction".to_string());
+    }
+    Ok(vector)
+}
+
+/// `{"id": .., "vector": [..]}` per line, from a file or stdin for `-`.
+fn read_lines(from: &Path) -> Result<Vec<(u128, Vec<f32>)>, &'static str> {
+    let reader: Box<dyn BufRead> = if from == Path::new("-") {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(
+            File::open(from).map_err(store_error(from, "read failed"))?,
+        ))
+    };
+    let mut entries = Vec::new();
+    for (n, line) in reader.lines().enumerate() {
+        let line = line.map_err(store_error(from, "read failed"))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str::<Line>(&line)
+            .map_err(|e| e.to_string())
+            .and_then(|l| Ok((l.id, check_vector(l.vector)?)));
+        match entry {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                eprintln!("{}:{}: {e}", from.display(), n + 1);
+                return Err("invalid vector");
+            }
+        }
+    }
+    Ok(entries)
+}
@@ -0,0 +1,145 @@
+//! `warm`: pays a store's cold start ahead of the first search. Loads every
+//! shard, builds the search index if its cache is stale and reads the store
+//! and index files once, so they sit in the OS page cache when an agent asks.
+//! `serve --preload` does the same for every store in its directory at start.
+//!
+//! Nothing is pinned: the process exits (or, under `serve`, reloads per
+//! request), so memory pressure can still evict the files. Prints one
+//! `field\tvalue` line per figure, sizes in bytes.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::store::{load_store, VECTOR_DIM};
+use crate::{
+    collect_vectors, config, index_cache, ivf, local_only, path_arg, pq, resolve_path, shards,
+    store_error, IndexKind,
+};
+
+#[derive(Default)]
+pub struct Report {
+    pub records: usize,
+    /// Vectors as held in memory while searching.
+    pub vector_bytes: u64,
+    pub store_bytes: u64,
+    pub index_bytes: u64,
+}
+
+pub fn cmd_warm(args: &[String]) -> Result<(), &'static str> {
+    let mut index: Option<IndexKind> = None;
+    let mut path: Option<PathBuf> = None;
+    let mut profile: Option<String> = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--index" => {
+                i += 1;
+                index = match args.get(i).map(String::as_str) {
+                    Some("hnsw") => Some(IndexKind::Hnsw),
+                    Some("ivf") => Some(IndexKind::Ivf),
+                    Some("pq") => Some(IndexKind::Pq),
+                    _ => {
+                        eprintln!("--index must be hnsw, ivf or pq");
+                        return Err("invalid index");
+                    }
+                };
+            }
+            "--path" => {
+                i += 1;
+                if let Some(v) = args.get(i) {
+                    path = Some(path_arg(v));
+                }
+            }
+            "--profile" => {
+                i += 1;
+                profile = args.get(i).cloned();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let profile = config::select_profile(profile.as_deref())?;
+    let path = resolve_path(path, &profile);
+    local_only(&path)?;
+    if !path.exists() && !shards::is_sharded(&path) {
+        eprintln!("No such file: {}", path.display());
+        return Err("missing store");
+    }
+    let index = index.or(profile.index).unwrap_or_default();
+
+    let started = Instant::now();
+    let report = warm(&path, index).map_err(store_error(&path, "read failed"))?;
+    println!("records\t{}", report.records);
+    println!("vectors\t{}", report.vector_bytes);
+    println!("store\t{}", report.store_bytes);
+    println!("index\t{}", report.index_bytes);
+    if let Some(peak) = peak_rss() {
+        println!("peak_rss\t{peak}");
+    }
+    println!("elapsed_ms\t{}", started.elapsed().as_millis());
+    Ok(())
+}
+
+/// Loads `path` (every shard of a sharded store), brings its `index` cache up
+/// to date and reads the files into the page cache.
+pub fn warm(path: &Path, index: IndexKind) -> io::Result<Report> {
+    let stores = if shards::is_sharded(path) {
+        shards::in_window(path, None)?
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut report = Report::default();
+    for store in stores {
+        // The fingerprint is taken before loading, as in `search`.
+        let fingerprint = index_cache::Fingerprint::of(&store);
+        let records = load_store(&store)?.records;
+        report.records += records.len();
+        report.vector_bytes += (records.len() * VECTOR_DIM * 4) as u64;
+        report.store_bytes += fs::metadata(&store)?.len();
+        if records.is_empty() || fingerprint.is_none() {
+            continue;
+        }
+        let caches = match index {
+            IndexKind::Hnsw => {
+                let vecs = collect_vectors(&records);
+                index_cache::with_index(&store, fingerprint, &vecs, false, |_| ());
+                index_cache::cache_files(&store)
+            }
+            IndexKind::Ivf => {
+                ivf::warm(&store, fingerprint, &records);
+                ivf::cache_files(&store)
+            }
+            IndexKind::Pq => {
+                pq::warm(&store, fingerprint, &records);
+                pq::cache_files(&store)
+            }
+        };
+        for file in caches {
+            report.index_bytes += touch(&file)?;
+        }
+    }
+    Ok(report)
+}
+
+/// Reads `file` to the end, returning its length.
+fn touch(file: &Path) -> io::Result<u64> {
+    io::copy(&mut fs::File::open(file)?, &mut io::sink())
+}
+
+/// Peak resident memory of this process in bytes, where `/proc` has it.
+fn peak_rss() -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let kb = status
+        .lines()
+        .find_map(|l| l.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kb * 1024)
+}
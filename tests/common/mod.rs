@@ -0,0 +1,61 @@
+//! Helpers shared by the integration tests, which drive the `memstore` binary.
+
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output};
+
+/// A scratch directory, removed when dropped.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let dir = env::temp_dir().join(format!("memstore-test-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// `memstore` run in `dir`, with no config, key or store path from the
+/// environment of the test run leaking in.
+pub fn memstore(dir: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_memstore"));
+    cmd.current_dir(dir)
+        .env("MEMSTORE_CONFIG", dir.join("config.toml"))
+        .env_remove("MEMSTORE_PATH")
+        .env_remove("MEMSTORE_TOKEN")
+        .env_remove("MEMSTORE_SIGNING_KEY");
+    cmd
+}
+
+/// Runs `cmd` and returns its stdout, failing the test with its stderr if it
+/// does not succeed.
+pub fn stdout(cmd: &mut Command) -> String {
+    let out = cmd.output().unwrap();
+    assert!(
+        out.status.success(),
+        "{cmd:?} failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    String::from_utf8(out.stdout).unwrap()
+}
+
+/// Runs `cmd`, which is expected to fail, and returns its stderr.
+pub fn failure(cmd: &mut Command) -> String {
+    let out: Output = cmd.output().unwrap();
+    assert!(!out.status.success(), "{cmd:?} succeeded");
+    String::from_utf8(out.stderr).unwrap()
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Output, Stdio};
+
+use common::{memstore, stdout, TempDir};
+
+fn compact_answering(dir: &Path, store: &Path, answer: &str) -> Output {
+    let mut child = memstore(dir)
+        .args(["compact", "--keep", "2", "--confirm", "--path"])
+        .arg(store)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(answer.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn ids(dir: &Path, store: &Path) -> Vec<String> {
+    let out = stdout(
+        memstore(dir)
+            .args(["export", "--format", "jsonl", "--path"])
+            .arg(store),
+    );
+    out.lines()
+        .map(|line| {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            value["id"].to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn confirm_removes_exactly_the_listed_records() {
+    let dir = TempDir::new("compact-confirm");
+    let store = dir.path().join("memories.hnsw");
+    for n in 0..5 {
+        stdout(
+            memstore(dir.path())
+                .args(["add", "--text", &format!("note {n}"), "--path"])
+                .arg(&store),
+        );
+    }
+    let before = ids(dir.path(), &store);
+    assert_eq!(before.len(), 5);
+
+    let declined = compact_answering(dir.path(), &store, "n\n");
+    assert!(!declined.status.success());
+    assert_eq!(ids(dir.path(), &store), before);
+
+    let accepted = compact_answering(dir.path(), &store, "y\n");
+    assert!(accepted.status.success());
+    let listed: Vec<String> = String::from_utf8(accepted.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.split('\t').next().unwrap().to_string())
+        .collect();
+    assert_eq!(listed.len(), 3);
+    let after = ids(dir.path(), &store);
+    assert_eq!(after.len(), 2);
+    for id in &before {
+        assert_ne!(after.contains(id), listed.contains(id), "id {id}");
+    }
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use std::thread;
+
+use common::{memstore, stdout, TempDir};
+
+const PROCESSES: usize = 8;
+const ADDS: usize = 5;
+
+#[test]
+fn concurrent_adds_all_land() {
+    let dir = TempDir::new("concurrent-add");
+    let store = dir.path().join("memories.hnsw");
+
+    // One thread per writer, each running its `add` processes back to back,
+    // so `PROCESSES` of them contend for the store lock at any time.
+    thread::scope(|scope| {
+        for p in 0..PROCESSES {
+            let (dir, store) = (dir.path(), &store);
+            scope.spawn(move || {
+                for n in 0..ADDS {
+                    stdout(
+                        memstore(dir)
+                            .args(["add", "--text", &format!("process {p} note {n}")])
+                            .arg("--path")
+                            .arg(store),
+                    );
+                }
+            });
+        }
+    });
+
+    let count = stdout(memstore(dir.path()).arg("count").arg("--path").arg(&store));
+    assert_eq!(count.trim(), (PROCESSES * ADDS).to_string());
+    let exported = stdout(
+        memstore(dir.path())
+            .args(["export", "--format", "jsonl", "--path"])
+            .arg(&store),
+    );
+    for p in 0..PROCESSES {
+        for n in 0..ADDS {
+            let text = format!("process {p} note {n}");
+            assert!(
+                exported.contains(&format!("\"{text}\"")),
+                "{text} is missing"
+            );
+        }
+    }
+    let verified = stdout(memstore(dir.path()).arg("verify").arg("--path").arg(&store));
+    assert!(verified.contains("ok\t"), "{verified}");
+}
@@ -0,0 +1,169 @@
+mod common;
+
+use std::fs;
+
+use common::{failure, memstore, stdout, TempDir};
+
+#[test]
+fn a_failed_export_keeps_the_previous_file() {
+    let dir = TempDir::new("export-atomic");
+    let store = dir.path().join("memories.hnsw");
+    let out = dir.path().join("memories.jsonl");
+    let tmp = dir.path().join("memories.jsonl.tmp");
+    stdout(
+        memstore(dir.path())
+            .args([
+                "add",
+                "--text",
+                "the release branch is cut on mondays",
+                "--path",
+            ])
+            .arg(&store),
+    );
+    stdout(
+        memstore(dir.path())
+            .args(["export", "--out"])
+            .arg(&out)
+            .arg("--path")
+            .arg(&store),
+    );
+    let exported = fs::read_to_string(&out).unwrap();
+    assert!(exported.contains("release branch"));
+    assert!(!tmp.exists());
+
+    stdout(
+        memstore(dir.path())
+            .args(["add", "--text", "staging is reset every night", "--path"])
+            .arg(&store),
+    );
+    // A flipped byte fails the checksum, after both records were written out.
+    let mut bytes = fs::read(&store).unwrap();
+    let at = bytes
+        .windows(7)
+        .position(|w| w == b"mondays")
+        .expect("record text in the store file");
+    bytes[at] = b'M';
+    fs::write(&store, bytes).unwrap();
+    failure(
+        memstore(dir.path())
+            .args(["export", "--out"])
+            .arg(&out)
+            .arg("--path")
+            .arg(&store),
+    );
+    assert_eq!(fs::read_to_string(&out).unwrap(), exported);
+    assert!(!tmp.exists());
+}
+
+#[cfg(feature = "parquet")]
+#[test]
+fn parquet_round_trips_through_import() {
+    let dir = TempDir::new("export-parquet");
+    let store = dir.path().join("memories.hnsw");
+    let copy = dir.path().join("copy.hnsw");
+    let out = dir.path().join("memories.parquet");
+    for text in ["the release branch is cut on mondays", "staging\tis reset every night"] {
+        stdout(
+            memstore(dir.path())
+                .args(["add", "--text", text, "--tag", "ops", "--path"])
+                .arg(&store),
+        );
+    }
+    stdout(
+        memstore(dir.path())
+            .args(["export", "--format", "parquet", "--with-vectors", "--out"])
+            .arg(&out)
+            .arg("--path")
+            .arg(&store),
+    );
+    assert!(fs::read(&out).unwrap().starts_with(b"PAR1"));
+    stdout(
+        memstore(dir.path())
+            .args(["import", "--format", "parquet"])
+            .arg(&out)
+            .arg("--path")
+            .arg(&copy),
+    );
+    let jsonl = |path: &std::path::Path| {
+        stdout(
+            memstore(dir.path())
+                .args(["export", "--with-vectors", "--path"])
+                .arg(path),
+        )
+    };
+    assert_eq!(jsonl(&copy), jsonl(&store));
+}
+
+#[cfg(not(feature = "parquet"))]
+#[test]
+fn parquet_names_the_feature_it_needs() {
+    let dir = TempDir::new("export-no-parquet");
+    let err = failure(memstore(dir.path()).args(["export", "--format", "parquet"]));
+    assert!(err.contains("--features parquet"), "{err}");
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn vectors_export_as_arrow_ipc() {
+    use arrow_array::cast::AsArray;
+    use arrow_ipc::reader::FileReader;
+
+    let dir = TempDir::new("export-arrow");
+    let store = dir.path().join("memories.hnsw");
+    let out = dir.path().join("vectors.arrow");
+    for text in ["the release branch is cut on mondays", "staging is reset every night"] {
+        stdout(
+            memstore(dir.path())
+                .args(["add", "--text", text, "--path"])
+                .arg(&store),
+        );
+    }
+    stdout(
+        memstore(dir.path())
+            .args(["export-vectors", "--format", "arrow", "--out"])
+            .arg(&out)
+            .arg("--path")
+            .arg(&store),
+    );
+    let ids: Vec<String> = stdout(
+        memstore(dir.path())
+            .args(["recent", "--path"])
+            .arg(&store),
+    )
+    .lines()
+    .map(|line| line.split('\t').nth(1).unwrap().to_string())
+    .collect();
+
+    let reader = FileReader::try_new(fs::File::open(&out).unwrap(), None).unwrap();
+    let batches: Vec<_> = reader.map(Result::unwrap).collect();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    let mut exported: Vec<String> = batch
+        .column_by_name("id")
+        .unwrap()
+        .as_string::<i32>()
+        .iter()
+        .map(|id| id.unwrap().to_string())
+        .collect();
+    exported.sort();
+    let mut expected = ids;
+    expected.sort();
+    assert_eq!(exported, expected);
+    assert_eq!(batch.num_rows(), 2);
+    let vectors = batch.column_by_name("vector").unwrap().as_fixed_size_list();
+    assert!(vectors.value_length() > 0);
+}
+
+#[cfg(not(feature = "arrow"))]
+#[test]
+fn arrow_names_the_feature_it_needs() {
+    let dir = TempDir::new("export-no-arrow");
+    let err = failure(memstore(dir.path()).args([
+        "export-vectors",
+        "--format",
+        "arrow",
+        "--out",
+        "vectors.arrow",
+    ]));
+    assert!(err.contains("--features arrow"), "{err}");
+}
@@ -0,0 +1,49 @@
+//! Loads the `--features node` addon into Node.js; skipped without `node`.
+#![cfg(feature = "node")]
+
+mod common;
+
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use common::TempDir;
+
+#[test]
+fn node_adds_and_searches_through_the_addon() {
+    if Command::new("node").arg("--version").output().is_err() {
+        eprintln!("node not found; skipping");
+        return;
+    }
+    let dir = TempDir::new("node-addon");
+    // The cdylib this test run built; the copy next to the binary may be from
+    // a build with other features.
+    let lib = Path::new(env!("CARGO_BIN_EXE_memstore"))
+        .with_file_name("deps")
+        .join(format!("{DLL_PREFIX}memstore{DLL_SUFFIX}"));
+    fs::copy(&lib, dir.path().join("memstore.node")).unwrap();
+    let script = r#"
+        const { MemStore } = require("./memstore.node");
+        const store = new MemStore("memories.hnsw");
+        const id = store.add("deploys need a database backup first", "decision", 1.5);
+        const hits = store.search("database backup", 5);
+        if (typeof id !== "bigint") throw new Error("id is " + typeof id);
+        if (hits.length !== 1 || hits[0].record.id !== id) throw new Error("wrong hits");
+        if (hits[0].record.kind !== "decision") throw new Error("wrong kind");
+        let threw = false;
+        try { new MemStore("not-a-store.txt"); } catch (e) { threw = true; }
+        if (!threw) throw new Error("opened a file that is not a store");
+    "#;
+    fs::write(dir.path().join("not-a-store.txt"), "hello").unwrap();
+    let out = Command::new("node")
+        .args(["-e", script])
+        .current_dir(dir.path())
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
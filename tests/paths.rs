@@ -0,0 +1,96 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{failure, memstore, stdout, TempDir};
+
+/// Adds, searches, compacts and verifies the store at `store`, the steps that
+/// create the lock, temp and index files next to it.
+fn exercise(dir: &Path, store: &Path) {
+    for text in [
+        "rollback needs the old schema",
+        "deploys go out on tuesdays",
+    ] {
+        stdout(
+            memstore(dir)
+                .args(["add", "--text", text, "--path"])
+                .arg(store),
+        );
+    }
+    let hits = stdout(
+        memstore(dir)
+            .args(["search", "--query", "rollback schema", "--path"])
+            .arg(store),
+    );
+    assert!(hits.contains("rollback needs the old schema"), "{hits}");
+    stdout(
+        memstore(dir)
+            .args(["compact", "--keep", "1", "--path"])
+            .arg(store),
+    );
+    let count = stdout(memstore(dir).arg("count").arg("--path").arg(store));
+    assert_eq!(count.trim(), "1");
+    stdout(memstore(dir).arg("verify").arg("--path").arg(store));
+    assert!(store.is_file());
+}
+
+/// Longer than Windows' `MAX_PATH` of 260 characters.
+#[test]
+fn long_paths_work() {
+    let dir = TempDir::new("long-path");
+    let mut nested = dir.path().to_path_buf();
+    for n in 0..8 {
+        nested.push(format!("{n}-{}", "d".repeat(40)));
+    }
+    fs::create_dir_all(&nested).unwrap();
+    let store = nested.join(format!("{}.hnsw", "m".repeat(100)));
+    assert!(store.as_os_str().len() > 400);
+    exercise(dir.path(), &store);
+
+    let out = nested.join(format!("{}.jsonl", "e".repeat(100)));
+    stdout(
+        memstore(dir.path())
+            .arg("export")
+            .arg("--out")
+            .arg(&out)
+            .arg("--path")
+            .arg(&store),
+    );
+    assert_eq!(fs::read_to_string(&out).unwrap().lines().count(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_paths_work() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+
+    let dir = TempDir::new("non-utf8");
+    let store = dir
+        .path()
+        .join(OsStr::from_bytes(b"caf\xe9 \xff memories.hnsw"));
+    exercise(dir.path(), &store);
+
+    let out = dir.path().join(OsStr::from_bytes(b"export-\xff.jsonl"));
+    stdout(
+        memstore(dir.path())
+            .arg("export")
+            .arg("--out")
+            .arg(&out)
+            .arg("--path")
+            .arg(&store),
+    );
+    assert!(out.is_file());
+
+    // Other arguments still have to be UTF-8.
+    let err = failure(
+        memstore(dir.path())
+            .arg("add")
+            .arg("--text")
+            .arg(OsStr::from_bytes(b"caf\xe9"))
+            .arg("--path")
+            .arg(&store),
+    );
+    assert!(err.contains("not valid UTF-8"), "{err}");
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use std::fs;
+use std::io::Write;
+use std::process::Stdio;
+
+use common::{memstore, stdout, TempDir};
+
+#[test]
+fn rpc_writes_follow_the_config_limits_and_auto_compact() {
+    let dir = TempDir::new("rpc");
+    let store = dir.path().join("memories.hnsw");
+    fs::write(
+        dir.path().join("config.toml"),
+        r#"auto_compact = { max_records = 3 }
+max_text = { max_bytes = "32B" }
+weight_range = { min = 0.0, max = 5.0 }
+"#,
+    )
+    .unwrap();
+
+    let mut lines = vec![
+        r#"{"jsonrpc":"2.0","id":1,"method":"add","params":{"text":"far more text than the thirty-two bytes allowed"}}"#.to_string(),
+        r#"{"jsonrpc":"2.0","id":2,"method":"add","params":{"text":"too heavy","weight":9.0}}"#.to_string(),
+        r#"{"jsonrpc":"2.0","id":3,"method":"tx","params":{"ops":[{"op":"add","text":"far more text than the thirty-two bytes allowed"}]}}"#.to_string(),
+    ];
+    for n in 0..5 {
+        lines.push(format!(
+            r#"{{"jsonrpc":"2.0","id":{},"method":"add","params":{{"text":"note {n}"}}}}"#,
+            10 + n
+        ));
+    }
+
+    let mut child = memstore(dir.path())
+        .arg("rpc")
+        .arg("--path")
+        .arg(&store)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(lines.join("\n").as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    let replies: Vec<&str> = std::str::from_utf8(&out.stdout).unwrap().lines().collect();
+    assert_eq!(replies.len(), lines.len());
+    for reply in &replies[..3] {
+        assert!(reply.contains(r#""code":-32602"#), "{reply}");
+    }
+    for reply in &replies[3..] {
+        assert!(reply.contains(r#""result""#), "{reply}");
+    }
+
+    let count = stdout(memstore(dir.path()).arg("count").arg("--path").arg(&store));
+    let count: usize = count.trim().parse().unwrap();
+    assert!((1..=3).contains(&count), "{count} records left");
+}
+
+/// Runs `lines` through one `rpc` session and returns the replies.
+fn rpc(dir: &std::path::Path, args: &[&str], lines: &[&str]) -> Vec<String> {
+    let mut child = memstore(dir)
+        .arg("rpc")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(lines.join("\n").as_bytes())
+        .unwrap();
+    let out = child.wait_with_output().unwrap();
+    assert!(out.status.success());
+    String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .map(str::to_string)
+        .collect()
+}
+
+#[test]
+fn rpc_searches_use_the_profile_settings() {
+    let dir = TempDir::new("rpc-profile");
+    let store = dir.path().join("memories.hnsw");
+    fs::write(
+        dir.path().join("config.toml"),
+        format!(
+            r#"[profiles.recent]
+path = {:?}
+default_max_age = "30d"
+
+[profiles.recent.scoring]
+kind_boost = {{ decision = 3.0 }}
+"#,
+            store.to_str().unwrap()
+        ),
+    )
+    .unwrap();
+    let input = dir.path().join("records.jsonl");
+    fs::write(
+        &input,
+        r#"{"id":1,"ts":1000000000,"kind":"summary","weight":1.0,"text":"old deploy checklist"}"#,
+    )
+    .unwrap();
+    stdout(
+        memstore(dir.path())
+            .arg("import")
+            .arg(&input)
+            .arg("--path")
+            .arg(&store),
+    );
+    stdout(
+        memstore(dir.path())
+            .args([
+                "add",
+                "--kind",
+                "decision",
+                "--text",
+                "deploy on fridays",
+                "--path",
+            ])
+            .arg(&store),
+    );
+
+    let search =
+        r#"{"jsonrpc":"2.0","id":1,"method":"search","params":{"query":"deploy","limit":5}}"#;
+    let path = store.to_str().unwrap();
+    let plain = rpc(dir.path(), &["--path", path], &[search]);
+    let profiled = rpc(dir.path(), &["--profile", "recent"], &[search]);
+    let hits = |reply: &str| -> Vec<f64> {
+        let reply: serde_json::Value = serde_json::from_str(reply).unwrap();
+        reply["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|hit| hit["score"].as_f64().unwrap())
+            .collect()
+    };
+    let (plain, profiled) = (hits(&plain[0]), hits(&profiled[0]));
+    assert_eq!(plain.len(), 2);
+    // default_max_age leaves out the old record; kind_boost scales the decision.
+    assert_eq!(profiled.len(), 1);
+    assert!(profiled[0] > plain[0] * 2.0, "{profiled:?} vs {plain:?}");
+}
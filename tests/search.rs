@@ -0,0 +1,64 @@
+mod common;
+
+use common::{memstore, stdout, TempDir};
+
+/// The best hit of an unrelated store must not tie with, or beat, a strong
+/// match from the store that holds the answer.
+#[test]
+fn stores_are_merged_on_comparable_scores() {
+    let dir = TempDir::new("search-merge");
+    let right = dir.path().join("right.hnsw");
+    let unrelated = dir.path().join("unrelated.hnsw");
+    for (store, text) in [
+        (&right, "rollback the database schema before deploys"),
+        (&unrelated, "cats like fish"),
+    ] {
+        stdout(
+            memstore(dir.path())
+                .args(["add", "--text", text, "--path"])
+                .arg(store),
+        );
+    }
+    let out = stdout(
+        memstore(dir.path())
+            .args(["search", "--query", "rollback database schema", "--path"])
+            .arg(&right)
+            .arg("--path")
+            .arg(&unrelated),
+    );
+    let hits: Vec<(f32, &str)> = out
+        .lines()
+        .map(|line| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            (cols[0].parse().unwrap(), cols[5])
+        })
+        .collect();
+    assert_eq!(hits.len(), 2, "{out}");
+    assert_eq!(hits[0].1, "rollback the database schema before deploys");
+    assert!(hits[1].0 < hits[0].0 && hits[0].0 < 1.0, "{out}");
+}
+
+/// Tabs and line breaks in record text must not add columns or rows to the
+/// TSV that search, grep and recent print.
+#[test]
+fn text_with_tabs_stays_in_one_field() {
+    let dir = TempDir::new("search-tsv");
+    let store = dir.path().join("memories.hnsw");
+    let text = "deploy\tsteps:\r\nrollback first";
+    stdout(
+        memstore(dir.path())
+            .args(["add", "--text", text, "--path"])
+            .arg(&store),
+    );
+    for (args, columns) in [
+        (vec!["search", "--query", "deploy rollback"], 5),
+        (vec!["grep", "--pattern", "rollback"], 4),
+        (vec!["recent"], 4),
+    ] {
+        let out = stdout(memstore(dir.path()).args(&args).arg("--path").arg(&store));
+        let rows: Vec<Vec<&str>> = out.lines().map(|line| line.split('\t').collect()).collect();
+        assert_eq!(rows.len(), 1, "{args:?}: {out:?}");
+        assert_eq!(rows[0].len(), columns, "{args:?}: {out:?}");
+        assert_eq!(rows[0][columns - 1], "deploy steps:  rollback first");
+    }
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use common::{memstore, stdout, TempDir};
+
+/// A store split into January and February 2024 shards: record 1 in
+/// January, records 2 and 3 in February, 1 and 2 tagged `person:alice`.
+fn sharded_store(dir: &Path) -> PathBuf {
+    let store = dir.join("memories.hnsw");
+    let lines = [
+        r#"{"id":1,"ts":1705276800,"kind":"fact","weight":1.0,"text":"alice prefers tea","tags":["person:alice"]}"#,
+        r#"{"id":2,"ts":1707955200,"kind":"fact","weight":1.0,"text":"alice is on call","tags":["person:alice"]}"#,
+        r#"{"id":3,"ts":1707955200,"kind":"fact","weight":1.0,"text":"the build uses nightly"}"#,
+    ];
+    let input = dir.join("records.jsonl");
+    fs::write(&input, lines.join("\n")).unwrap();
+    stdout(
+        memstore(dir)
+            .arg("import")
+            .arg(&input)
+            .arg("--path")
+            .arg(&store),
+    );
+    stdout(memstore(dir).arg("shard").arg("--path").arg(&store));
+    assert!(!store.exists());
+    store
+}
+
+fn count(dir: &Path, shard: &str) -> usize {
+    let out = stdout(
+        memstore(dir)
+            .arg("count")
+            .arg("--path")
+            .arg(dir.join(shard)),
+    );
+    out.trim().parse().unwrap()
+}
+
+#[test]
+fn forget_reaches_every_shard() {
+    let dir = TempDir::new("shards-forget");
+    let store = sharded_store(dir.path());
+    let out = memstore(dir.path())
+        .args(["forget", "--id", "1", "--id", "3", "--shred", "--path"])
+        .arg(&store)
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Forgot 2 of 2 records"));
+    assert!(!store.exists());
+    assert_eq!(count(dir.path(), "memories-2024-01.hnsw"), 0);
+    assert_eq!(count(dir.path(), "memories-2024-02.hnsw"), 1);
+}
+
+#[test]
+fn subject_export_and_erase_cover_every_shard() {
+    let dir = TempDir::new("shards-subject");
+    let store = sharded_store(dir.path());
+    let exported = stdout(
+        memstore(dir.path())
+            .args(["subject", "export", "--tag", "person:alice", "--path"])
+            .arg(&store),
+    );
+    assert_eq!(exported.lines().count(), 2, "{exported}");
+    stdout(
+        memstore(dir.path())
+            .args([
+                "subject",
+                "erase",
+                "--tag",
+                "person:alice",
+                "--yes",
+                "--path",
+            ])
+            .arg(&store),
+    );
+    assert!(!store.exists());
+    assert_eq!(count(dir.path(), "memories-2024-01.hnsw"), 0);
+    assert_eq!(count(dir.path(), "memories-2024-02.hnsw"), 1);
+}
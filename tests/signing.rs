@@ -0,0 +1,71 @@
+mod common;
+
+use std::fs;
+
+use common::{failure, memstore, stdout, TempDir};
+
+#[test]
+fn unsigned_store_is_refused_until_rekey_signs_it() {
+    let dir = TempDir::new("signing");
+    let store = dir.path().join("memories.hnsw");
+    let key_file = dir.path().join("store.key");
+    fs::write(&key_file, "s3cret\n").unwrap();
+
+    stdout(
+        memstore(dir.path())
+            .args(["add", "--text", "written before the key"])
+            .arg("--path")
+            .arg(&store),
+    );
+
+    let with_key = |args: &[&str]| {
+        let mut cmd = memstore(dir.path());
+        cmd.env("MEMSTORE_SIGNING_KEY", "s3cret")
+            .args(args)
+            .arg("--path")
+            .arg(&store);
+        cmd
+    };
+    let err = failure(&mut with_key(&["search", "--query", "key"]));
+    assert!(err.contains("not signed"), "{err}");
+    failure(&mut with_key(&["verify"]));
+    failure(&mut with_key(&["add", "--text", "refused"]));
+
+    let mut rekey = with_key(&["rekey"]);
+    rekey.arg("--new-key-file").arg(&key_file);
+    stdout(&mut rekey);
+
+    let hits = stdout(&mut with_key(&[
+        "search",
+        "--query",
+        "written before the key",
+    ]));
+    assert!(hits.contains("written before the key"), "{hits}");
+    stdout(&mut with_key(&["verify", "--strict"]));
+
+    let mut wrong = with_key(&["search", "--query", "key"]);
+    wrong.env("MEMSTORE_SIGNING_KEY", "other");
+    let err = failure(&mut wrong);
+    assert!(err.contains("signature mismatch"), "{err}");
+}
+
+#[test]
+fn signed_store_is_not_rewritten_without_the_key() {
+    let dir = TempDir::new("signing-keep");
+    let store = dir.path().join("memories.hnsw");
+    stdout(
+        memstore(dir.path())
+            .env("MEMSTORE_SIGNING_KEY", "s3cret")
+            .args(["add", "--text", "signed from the start", "--path"])
+            .arg(&store),
+    );
+    let signed = fs::read(&store).unwrap();
+
+    let err = failure(
+        memstore(dir.path())
+            .args(["add", "--text", "written without the key", "--path"])
+            .arg(&store),
+    );
+    assert!(err.contains("MEMSTORE_SIGNING_KEY"), "{err}");
+    assert_eq!(fs::read(&store).unwrap(), signed);
+}